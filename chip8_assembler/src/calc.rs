@@ -0,0 +1,124 @@
+// Constant expressions of :calc and :byte { ... }. Like Octo there is no operator precedence,
+// expressions are evaluated right to left: 2 * 3 + 1 is 2 * (3 + 1). Use parentheses to group
+// the other way. Every name must already be defined when the expression is read.
+
+use crate::AsmError;
+use crate::lexer::Token;
+
+const UNARY: [&str; 13] = [
+    "-", "~", "!", "sin", "cos", "tan", "exp", "log", "abs", "sqrt", "sign", "ceil", "floor",
+];
+const BINARY: [&str; 19] = [
+    "-", "+", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", "==", "!=", ">=", ">",
+];
+
+// `end` is the closing brace, used for errors when the expression stops short
+pub fn evaluate(tokens: &[Token], end: &Token, lookup: &dyn Fn(&str) -> Option<f64>) -> Result<f64, AsmError> {
+    let mut parser = Parser { tokens, pos: 0, end, lookup };
+
+    let val: f64 = parser.expr()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(AsmError::at(token, format!("unexpected '{}' in expression", token.text)));
+    }
+
+    Ok(val)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    end: &'a Token,
+    lookup: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Result<&Token, AsmError> {
+        let token: &Token = self.tokens.get(self.pos).ok_or_else(|| AsmError::at(self.end, "incomplete expression"))?;
+        self.pos += 1;
+
+        Ok(token)
+    }
+
+    fn expr(&mut self) -> Result<f64, AsmError> {
+        let left: f64 = self.term()?;
+
+        let Some(op) = self.tokens.get(self.pos).filter(|token| BINARY.contains(&token.text.as_str())) else {
+            return Ok(left);
+        };
+        let op: String = op.text.clone();
+        self.pos += 1;
+
+        let right: f64 = self.expr()?;
+        Ok(binary(&op, left, right))
+    }
+
+    fn term(&mut self) -> Result<f64, AsmError> {
+        let token: Token = self.next()?.clone();
+
+        if token.text == "(" {
+            let val: f64 = self.expr()?;
+            let close: &Token = self.next()?;
+            if close.text != ")" {
+                return Err(AsmError::at(close, format!("expected ')', found '{}'", close.text)));
+            }
+            return Ok(val);
+        }
+
+        if UNARY.contains(&token.text.as_str()) {
+            let val: f64 = self.term()?;
+            return Ok(unary(&token.text, val));
+        }
+
+        if let Some(val) = crate::compiler::parse_number(&token.text) {
+            return Ok(val as f64);
+        }
+
+        (self.lookup)(&token.text).ok_or_else(|| AsmError::at(&token, format!("undefined name '{}'", token.text)))
+    }
+}
+
+fn unary(op: &str, val: f64) -> f64 {
+    match op {
+        "-" => -val,
+        "~" => !(val as i64) as f64,
+        "!" => (val == 0.0) as u8 as f64,
+        "sin" => val.sin(),
+        "cos" => val.cos(),
+        "tan" => val.tan(),
+        "exp" => val.exp(),
+        "log" => val.ln(),
+        "abs" => val.abs(),
+        "sqrt" => val.sqrt(),
+        "sign" => if val == 0.0 {0.0} else {val.signum()},
+        "ceil" => val.ceil(),
+        "floor" => val.floor(),
+        _ => unreachable!(),
+    }
+}
+
+fn binary(op: &str, left: f64, right: f64) -> f64 {
+    let (l, r) = (left as i64, right as i64);
+
+    match op {
+        "-" => left - right,
+        "+" => left + right,
+        "*" => left * right,
+        "/" => left / right,
+        "%" => left % right,
+        "&" => (l & r) as f64,
+        "|" => (l | r) as f64,
+        "^" => (l ^ r) as f64,
+        "<<" => l.wrapping_shl(r as u32) as f64,
+        ">>" => l.wrapping_shr(r as u32) as f64,
+        "pow" => left.powf(right),
+        "min" => left.min(right),
+        "max" => left.max(right),
+        "<" => (left < right) as u8 as f64,
+        "<=" => (left <= right) as u8 as f64,
+        "==" => (left == right) as u8 as f64,
+        "!=" => (left != right) as u8 as f64,
+        ">=" => (left >= right) as u8 as f64,
+        ">" => (left > right) as u8 as f64,
+        _ => unreachable!(),
+    }
+}
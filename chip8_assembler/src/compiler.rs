@@ -0,0 +1,793 @@
+// Single pass compiler. Instructions are encoded through chip8_engine::Instruction so the assembler and the
+// emulator can never disagree on an opcode. References to labels that are not defined yet are written as zero
+// and patched once the whole source has been read.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chip8_engine::Instruction;
+
+use crate::calc;
+use crate::lexer::{self, Token};
+use crate::{AsmError, Program};
+
+const START_ADDRESS: usize = 0x200;
+const MAX_ADDRESS: usize = 0x10000;             // XO-CHIP can address 64kB
+const MAX_MACRO_EXPANSIONS: usize = 65536;      // stops a macro that expands to itself
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+enum FixupKind {
+    Low12,              // nnn of a jump, call or i :=
+    Word,               // the address word after i := long
+    UnpackHigh(u8),     // :unpack, v0 := nibble << 4 | address >> 8
+    UnpackLow,          // :unpack, v1 := address & 0xFF
+}
+
+struct Fixup {
+    addr: usize,        // address of the instruction to patch
+    kind: FixupKind,
+    label: Token,
+}
+
+// Open control flow blocks, every jump is patched once the block closes
+enum Block {
+    Loop { start: usize, breaks: Vec<usize>, token: Token },
+    If { jump: usize, token: Token },
+    Else { jump: usize, token: Token },
+}
+
+enum Operand {
+    Reg(u8),
+    Byte(u8),
+}
+
+pub struct Compiler {
+    tokens: VecDeque<Token>,        // macro expansions are pushed back to the front
+    last: Token,                    // last token read, errors at the end of the input point here
+
+    rom: Vec<u8>,
+    here: usize,                    // address the next byte is written to
+
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    breakpoints: Vec<(u16, String)>,
+    lines: BTreeMap<u16, usize>,    // address of every instruction -> source line of its statement
+    statement_line: usize,
+
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    main_jump: bool,                // 0x200 holds a jump to main
+    expansions: usize,
+}
+
+impl Compiler {
+    pub fn new(source: &str) -> Self {
+        let tokens: VecDeque<Token> = lexer::tokenize(source).into();
+
+        Self {
+            tokens,
+            last: Token { text: String::new(), line: 1, column: 1 },
+
+            rom: Vec::new(),
+            here: START_ADDRESS,
+
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            breakpoints: Vec::new(),
+            lines: BTreeMap::new(),
+            statement_line: 1,
+
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            main_jump: false,
+            expansions: 0,
+        }
+    }
+
+    pub fn compile(mut self) -> Result<Program, AsmError> {
+        // Execution starts at main. Unless main comes first, 0x200 jumps to it
+        let main_first: bool = self.tokens.len() >= 2 && self.tokens[0].text == ":" && self.tokens[1].text == "main";
+        if !main_first {
+            self.main_jump = true;
+            self.emit(Instruction::Jump((START_ADDRESS + 2) as u16))?;
+        }
+
+        while let Some(token) = self.tokens.pop_front() {
+            self.last = token.clone();
+            self.statement_line = token.line;
+            self.statement(token)?;
+        }
+
+        if let Some(block) = self.blocks.pop() {
+            let (token, closer) = match block {
+                Block::Loop { token, .. } => (token, "again"),
+                Block::If { token, .. } | Block::Else { token, .. } => (token, "end"),
+            };
+            return Err(AsmError::at(&token, format!("'{}' is never closed with '{}'", token.text, closer)));
+        }
+
+        if self.main_jump && let Some(main) = self.labels.get("main").copied() {
+            self.patch_low12(START_ADDRESS, main as usize, &self.last.clone())?;
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(target) = self.labels.get(&fixup.label.text).copied() else {
+                return Err(AsmError::at(&fixup.label, format!("undefined label '{}'", fixup.label.text)));
+            };
+
+            match fixup.kind {
+                FixupKind::Low12 => self.patch_low12(fixup.addr, target as usize, &fixup.label)?,
+                FixupKind::Word => self.patch(fixup.addr + 2, target, 0xFFFF),
+                FixupKind::UnpackHigh(nibble) => {
+                    self.patch(fixup.addr, (nibble as u16) << 4 | (target >> 8) & 0xF, 0xFF)
+                },
+                FixupKind::UnpackLow => self.patch(fixup.addr, target & 0xFF, 0xFF),
+            }
+        }
+
+        Ok(Program { bytes: self.rom, symbols: self.labels, breakpoints: self.breakpoints, lines: self.lines })
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AsmError> {
+        match token.text.as_str() {
+            ":" => {
+                let name: Token = self.name()?;
+                if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) {
+                    return Err(AsmError::at(&name, format!("'{}' is already defined", name.text)));
+                }
+                self.labels.insert(name.text, self.here as u16);
+            },
+            ":alias" => {
+                let name: Token = self.name()?;
+                let reg: u8 = self.register()?;
+                self.aliases.insert(name.text, reg);
+            },
+            ":const" => {
+                let name: Token = self.name()?;
+                let val_token: Token = self.next()?;
+                let val: f64 = self.number(&val_token)? as f64;
+                self.define_constant(name, val)?;
+            },
+            ":calc" => {
+                let name: Token = self.name()?;
+                let val: f64 = self.braced_expression()?;
+                self.define_constant(name, val)?;
+            },
+            ":macro" => self.define_macro()?,
+            ":byte" => {
+                let val: i64 = if self.peek_is("{") {
+                    self.braced_expression()? as i64
+                } else {
+                    let val_token: Token = self.next()?;
+                    self.number(&val_token)?
+                };
+                let byte: u8 = self.fit_byte(&token, val)?;
+                self.emit_byte(byte)?;
+            },
+            ":org" => {
+                let addr_token: Token = self.next()?;
+                let addr: i64 = self.number(&addr_token)?;
+                if !(START_ADDRESS as i64..MAX_ADDRESS as i64).contains(&addr) {
+                    return Err(AsmError::at(&addr_token, format!("address 0x{:X} is outside 0x200-0xFFFF", addr)));
+                }
+                self.here = addr as usize;
+            },
+            ":call" => {
+                let target: Token = self.next()?;
+                let addr: u16 = self.address(&target, FixupKind::Low12)?;
+                self.emit(Instruction::Call(addr))?;
+            },
+            ":unpack" => {
+                let nibble_token: Token = self.next()?;
+                let nibble: u8 = self.nibble(&nibble_token)?;
+                let target: Token = self.next()?;
+
+                // Both halves are patched together once the label is known
+                match self.resolve(&target) {
+                    Some(addr) => {
+                        let addr: u16 = self.fit_address(&target, addr, 0xFFF)?;
+                        self.emit(Instruction::LoadByte(0x0, nibble << 4 | (addr >> 8) as u8))?;
+                        self.emit(Instruction::LoadByte(0x1, addr as u8))?;
+                    },
+                    None => {
+                        self.forward(&target, FixupKind::UnpackHigh(nibble), self.here + 1)?;
+                        self.emit(Instruction::LoadByte(0x0, 0))?;
+                        self.forward(&target, FixupKind::UnpackLow, self.here + 1)?;
+                        self.emit(Instruction::LoadByte(0x1, 0))?;
+                    },
+                }
+            },
+            ":breakpoint" => {
+                let name: Token = self.next()?;
+                self.breakpoints.push((self.here as u16, name.text));
+            },
+            ":monitor" => {
+                // Only meaningful to Octo's own debugger
+                self.next()?;
+                self.next()?;
+            },
+            ":proto" => {
+                // Forward declarations are not needed, every label can be used before it is defined
+                self.next()?;
+            },
+
+            "clear" => self.emit(Instruction::Cls)?,
+            "return" | ";" => self.emit(Instruction::Ret)?,
+            "exit" => self.emit(Instruction::Exit)?,
+            "lores" => self.emit(Instruction::Lores)?,
+            "hires" => self.emit(Instruction::Hires)?,
+            "scroll-left" => self.emit(Instruction::ScrollLeft)?,
+            "scroll-right" => self.emit(Instruction::ScrollRight)?,
+            "audio" => self.emit(Instruction::Audio)?,
+            "scroll-down" => {
+                let n: u8 = self.next_nibble()?;
+                self.emit(Instruction::ScrollDown(n))?;
+            },
+            "scroll-up" => {
+                let n: u8 = self.next_nibble()?;
+                self.emit(Instruction::ScrollUp(n))?;
+            },
+            "plane" => {
+                let n: u8 = self.next_nibble()?;
+                if n > 3 {
+                    return Err(AsmError::at(&self.last, "plane must be 0-3"));
+                }
+                self.emit(Instruction::Plane(n))?;
+            },
+            "bcd" => {
+                let x: u8 = self.register()?;
+                self.emit(Instruction::Bcd(x))?;
+            },
+            "save" | "load" => {
+                let x: u8 = self.register()?;
+                let store: bool = token.text == "save";
+
+                if self.peek_is("-") {
+                    self.next()?;
+                    let y: u8 = self.register()?;
+                    self.emit(if store {Instruction::SaveRange(x, y)} else {Instruction::LoadRange(x, y)})?;
+                } else {
+                    self.emit(if store {Instruction::Store(x)} else {Instruction::Load(x)})?;
+                }
+            },
+            "saveflags" => {
+                let x: u8 = self.register()?;
+                self.emit(Instruction::StoreFlags(x))?;
+            },
+            "loadflags" => {
+                let x: u8 = self.register()?;
+                self.emit(Instruction::LoadFlags(x))?;
+            },
+            "sprite" => {
+                let x: u8 = self.register()?;
+                let y: u8 = self.register()?;
+                let n: u8 = self.next_nibble()?;
+                self.emit(Instruction::Draw(x, y, n))?;
+            },
+            "jump" | "jump0" => {
+                let target: Token = self.next()?;
+                let addr: u16 = self.address(&target, FixupKind::Low12)?;
+                self.emit(if token.text == "jump" {Instruction::Jump(addr)} else {Instruction::JumpOffset(addr)})?;
+            },
+            "native" => {
+                let target: Token = self.next()?;
+                let addr: u16 = self.address(&target, FixupKind::Low12)?;
+                self.emit_word(addr)?;
+            },
+            "i" => self.index_statement()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x: u8 = self.register()?;
+                self.emit(match token.text.as_str() {
+                    "delay" => Instruction::SetDelay(x),
+                    "buzzer" => Instruction::SetSound(x),
+                    _ => Instruction::Pitch(x),
+                })?;
+            },
+
+            "if" => {
+                let (x, op, operand) = self.condition()?;
+                let keyword: Token = self.next()?;
+
+                match keyword.text.as_str() {
+                    // Skip the next statement when the condition does not hold
+                    "then" => self.emit_skip(x, &op, operand, false)?,
+
+                    // Skip the jump over the body when the condition holds
+                    "begin" => {
+                        self.emit_skip(x, &op, operand, true)?;
+                        self.blocks.push(Block::If { jump: self.here, token: token.clone() });
+                        self.emit(Instruction::Jump(0))?;
+                    },
+                    _ => return Err(AsmError::at(&keyword, format!("expected 'then' or 'begin', found '{}'", keyword.text))),
+                }
+            },
+            "else" => {
+                let Some(Block::If { jump, token: if_token }) = self.blocks.pop() else {
+                    return Err(AsmError::at(&token, "'else' without 'if ... begin'"));
+                };
+
+                let else_jump: usize = self.here;
+                self.emit(Instruction::Jump(0))?;
+                self.patch_low12(jump, self.here, &if_token)?;
+                self.blocks.push(Block::Else { jump: else_jump, token: if_token });
+            },
+            "end" => {
+                let (Some(Block::If { jump, token: if_token }) | Some(Block::Else { jump, token: if_token })) = self.blocks.pop() else {
+                    return Err(AsmError::at(&token, "'end' without 'if ... begin'"));
+                };
+                self.patch_low12(jump, self.here, &if_token)?;
+            },
+            "loop" => self.blocks.push(Block::Loop { start: self.here, breaks: Vec::new(), token: token.clone() }),
+            "while" => {
+                let (x, op, operand) = self.condition()?;
+
+                // Skip the jump out of the loop while the condition holds
+                self.emit_skip(x, &op, operand, true)?;
+                let addr: usize = self.here;
+                self.emit(Instruction::Jump(0))?;
+
+                let Some(Block::Loop { breaks, .. }) = self.blocks.iter_mut().rev().find(|block| matches!(block, Block::Loop { .. })) else {
+                    return Err(AsmError::at(&token, "'while' outside of 'loop'"));
+                };
+                breaks.push(addr);
+            },
+            "again" => {
+                let Some(Block::Loop { start, breaks, token: loop_token }) = self.blocks.pop() else {
+                    return Err(AsmError::at(&token, "'again' without 'loop'"));
+                };
+
+                let start: u16 = self.fit_address(&loop_token, start as i64, 0xFFF)?;
+                self.emit(Instruction::Jump(start))?;
+                for addr in breaks {
+                    self.patch_low12(addr, self.here, &loop_token)?;
+                }
+            },
+
+            _ if self.register_of(&token).is_some() => self.register_statement(&token)?,
+            _ if self.macros.contains_key(&token.text) => self.expand_macro(&token)?,
+            _ => {
+                // A bare number or constant is a data byte, a bare label is a subroutine call
+                if let Some(val) = parse_number(&token.text).or(self.constants.get(&token.text).map(|val| *val as i64)) {
+                    let byte: u8 = self.fit_byte(&token, val)?;
+                    self.emit_byte(byte)?;
+                } else if is_identifier(&token.text) {
+                    let addr: u16 = self.address(&token, FixupKind::Low12)?;
+                    self.emit(Instruction::Call(addr))?;
+                } else {
+                    return Err(AsmError::at(&token, format!("unexpected '{}'", token.text)));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    // i := nnn, i := long nnnn, i := hex vx, i := bighex vx, i += vx
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        let op: Token = self.next()?;
+
+        match op.text.as_str() {
+            "+=" => {
+                let x: u8 = self.register()?;
+                self.emit(Instruction::AddI(x))
+            },
+            ":=" => {
+                let target: Token = self.next()?;
+                match target.text.as_str() {
+                    "hex" => {
+                        let x: u8 = self.register()?;
+                        self.emit(Instruction::Font(x))
+                    },
+                    "bighex" => {
+                        let x: u8 = self.register()?;
+                        self.emit(Instruction::BigFont(x))
+                    },
+                    "long" => {
+                        let target: Token = self.next()?;
+                        let addr: u16 = self.address(&target, FixupKind::Word)?;
+                        self.emit(Instruction::LoadILong)?;
+                        self.emit_word(addr)
+                    },
+                    _ => {
+                        let addr: u16 = self.address(&target, FixupKind::Low12)?;
+                        self.emit(Instruction::LoadI(addr))
+                    },
+                }
+            },
+            _ => Err(AsmError::at(&op, format!("expected ':=' or '+=' after 'i', found '{}'", op.text))),
+        }
+    }
+
+    fn register_statement(&mut self, reg: &Token) -> Result<(), AsmError> {
+        let x: u8 = self.register_of(reg).unwrap();
+        let op: Token = self.next()?;
+        let rhs: Token = self.next()?;
+        let y: Option<u8> = self.register_of(&rhs);
+
+        let instruction: Instruction = match (op.text.as_str(), y) {
+            (":=", Some(y)) => Instruction::LoadReg(x, y),
+            (":=", None) => match rhs.text.as_str() {
+                "key" => Instruction::WaitKey(x),
+                "delay" => Instruction::LoadDelay(x),
+                "random" => {
+                    let mask: Token = self.next()?;
+                    Instruction::Random(x, self.byte(&mask)?)
+                },
+                _ => Instruction::LoadByte(x, self.byte(&rhs)?),
+            },
+            ("+=", Some(y)) => Instruction::AddReg(x, y),
+            ("+=", None) => Instruction::AddByte(x, self.byte(&rhs)?),
+            ("-=", Some(y)) => Instruction::Sub(x, y),
+            ("-=", None) => Instruction::AddByte(x, self.byte(&rhs)?.wrapping_neg()),
+            ("=-", Some(y)) => Instruction::SubN(x, y),
+            ("|=", Some(y)) => Instruction::Or(x, y),
+            ("&=", Some(y)) => Instruction::And(x, y),
+            ("^=", Some(y)) => Instruction::Xor(x, y),
+            (">>=", Some(y)) => Instruction::Shr(x, y),
+            ("<<=", Some(y)) => Instruction::Shl(x, y),
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(AsmError::at(&rhs, format!("'{}' needs a register, found '{}'", op.text, rhs.text)));
+            },
+            _ => return Err(AsmError::at(&op, format!("unknown operator '{}'", op.text))),
+        };
+
+        self.emit(instruction)
+    }
+
+    // vx == n, vx != vy, vx < n, vx key, vx -key ...
+    fn condition(&mut self) -> Result<(u8, Token, Option<Operand>), AsmError> {
+        let x: u8 = self.register()?;
+        let op: Token = self.next()?;
+
+        match op.text.as_str() {
+            "key" | "-key" => Ok((x, op, None)),
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {
+                let rhs: Token = self.next()?;
+                let operand: Operand = match self.register_of(&rhs) {
+                    Some(y) => Operand::Reg(y),
+                    None => Operand::Byte(self.byte(&rhs)?),
+                };
+                Ok((x, op, Some(operand)))
+            },
+            _ => Err(AsmError::at(&op, format!("unknown comparison '{}'", op.text))),
+        }
+    }
+
+    // Emit the instruction(s) that skip the next one when the condition equals `skip_when`
+    fn emit_skip(&mut self, x: u8, op: &Token, operand: Option<Operand>, skip_when: bool) -> Result<(), AsmError> {
+        let equal: bool = match op.text.as_str() {
+            "==" | "key" => skip_when,
+            "!=" | "-key" => !skip_when,
+            _ => {
+                // There is no ordered compare, subtract in VF and test the borrow flag.
+                // vf := rhs, vf -= vx leaves 1 when rhs >= vx, vf =- vx leaves 1 when vx >= rhs
+                let (flag, reverse) = match op.text.as_str() {
+                    ">" => (0, false),
+                    "<=" => (1, false),
+                    "<" => (0, true),
+                    _ => (1, true),
+                };
+
+                self.emit(match operand {
+                    Some(Operand::Reg(y)) => Instruction::LoadReg(0xF, y),
+                    Some(Operand::Byte(nn)) => Instruction::LoadByte(0xF, nn),
+                    None => unreachable!(),
+                })?;
+                self.emit(if reverse {Instruction::SubN(0xF, x)} else {Instruction::Sub(0xF, x)})?;
+
+                return self.emit(if skip_when {Instruction::SkipEqByte(0xF, flag)} else {Instruction::SkipNeByte(0xF, flag)});
+            },
+        };
+
+        self.emit(match (op.text.as_str(), operand) {
+            ("key" | "-key", _) => if equal {Instruction::SkipKey(x)} else {Instruction::SkipNotKey(x)},
+            (_, Some(Operand::Reg(y))) => if equal {Instruction::SkipEqReg(x, y)} else {Instruction::SkipNeReg(x, y)},
+            (_, Some(Operand::Byte(nn))) => if equal {Instruction::SkipEqByte(x, nn)} else {Instruction::SkipNeByte(x, nn)},
+            (_, None) => unreachable!(),
+        })
+    }
+
+    fn define_constant(&mut self, name: Token, val: f64) -> Result<(), AsmError> {
+        if self.labels.contains_key(&name.text) {
+            return Err(AsmError::at(&name, format!("'{}' is already defined", name.text)));
+        }
+        self.constants.insert(name.text, val);
+
+        Ok(())
+    }
+
+    // :macro name params... { body }
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name: Token = self.name()?;
+
+        let mut params: Vec<String> = Vec::new();
+        loop {
+            let param: Token = self.next()?;
+            if param.text == "{" {
+                break;
+            }
+            params.push(param.text);
+        }
+
+        let body: Vec<Token> = self.braced_tokens()?;
+        self.macros.insert(name.text, Macro { params, body });
+
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, name: &Token) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(AsmError::at(name, "too many macro expansions, does a macro expand to itself?"));
+        }
+
+        let param_count: usize = self.macros[&name.text].params.len();
+        let mut args: HashMap<String, String> = HashMap::new();
+        for idx in 0..param_count {
+            let arg: Token = self.next()?;
+            args.insert(self.macros[&name.text].params[idx].clone(), arg.text);
+        }
+
+        // Tokens keep the position they have in the macro body
+        for token in self.macros[&name.text].body.iter().rev() {
+            let mut token: Token = token.clone();
+            if let Some(arg) = args.get(&token.text) {
+                token.text = arg.clone();
+            }
+            self.tokens.push_front(token);
+        }
+
+        Ok(())
+    }
+
+    // Expects the opening brace next, reads the expression up to the closing one
+    fn braced_expression(&mut self) -> Result<f64, AsmError> {
+        self.expect("{")?;
+        let tokens: Vec<Token> = self.braced_tokens()?;
+        let end: Token = self.last.clone();
+
+        let here: f64 = self.here as f64;
+        let labels = &self.labels;
+        let constants = &self.constants;
+        let lookup = |name: &str| -> Option<f64> {
+            match name {
+                "HERE" => Some(here),
+                "PI" => Some(std::f64::consts::PI),
+                "E" => Some(std::f64::consts::E),
+                _ => constants.get(name).copied().or(labels.get(name).map(|addr| *addr as f64)),
+            }
+        };
+
+        calc::evaluate(&tokens, &end, &lookup)
+    }
+
+    // Everything up to the brace that closes the one just read, nested braces included
+    fn braced_tokens(&mut self) -> Result<Vec<Token>, AsmError> {
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut depth: usize = 1;
+
+        loop {
+            let token: Token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(tokens);
+                    }
+                },
+                _ => {},
+            }
+            tokens.push(token);
+        }
+    }
+
+    // TOKENS
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        let token: Token = self.tokens.pop_front().ok_or_else(|| AsmError::at(&self.last, "unexpected end of input"))?;
+        self.last = token.clone();
+
+        Ok(token)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.front().is_some_and(|token| token.text == text)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token: Token = self.next()?;
+        if token.text != text {
+            return Err(AsmError::at(&token, format!("expected '{}', found '{}'", text, token.text)));
+        }
+
+        Ok(())
+    }
+
+    // A name for a label, constant, alias or macro
+    fn name(&mut self) -> Result<Token, AsmError> {
+        let token: Token = self.next()?;
+        if !is_identifier(&token.text) || parse_register(&token.text).is_some() {
+            return Err(AsmError::at(&token, format!("'{}' is not a valid name", token.text)));
+        }
+
+        Ok(token)
+    }
+
+    fn register(&mut self) -> Result<u8, AsmError> {
+        let token: Token = self.next()?;
+        self.register_of(&token).ok_or_else(|| AsmError::at(&token, format!("expected a register, found '{}'", token.text)))
+    }
+
+    fn register_of(&self, token: &Token) -> Option<u8> {
+        self.aliases.get(&token.text).copied().or(parse_register(&token.text))
+    }
+
+    // VALUES
+
+    // A number, a constant or a label that is already defined
+    fn resolve(&self, token: &Token) -> Option<i64> {
+        parse_number(&token.text)
+            .or(self.constants.get(&token.text).map(|val| *val as i64))
+            .or(self.labels.get(&token.text).map(|addr| *addr as i64))
+    }
+
+    fn number(&self, token: &Token) -> Result<i64, AsmError> {
+        self.resolve(token).ok_or_else(|| AsmError::at(token, format!("undefined name '{}'", token.text)))
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, AsmError> {
+        let val: i64 = self.number(token)?;
+        self.fit_byte(token, val)
+    }
+
+    fn fit_byte(&self, token: &Token, val: i64) -> Result<u8, AsmError> {
+        if !(-128..=255).contains(&val) {
+            return Err(AsmError::at(token, format!("{} does not fit in a byte", val)));
+        }
+
+        Ok(val as u8)
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u8, AsmError> {
+        let val: i64 = self.number(token)?;
+        if !(0..=15).contains(&val) {
+            return Err(AsmError::at(token, format!("{} does not fit in a nibble", val)));
+        }
+
+        Ok(val as u8)
+    }
+
+    fn next_nibble(&mut self) -> Result<u8, AsmError> {
+        let token: Token = self.next()?;
+        self.nibble(&token)
+    }
+
+    // An address for the instruction about to be emitted, labels that are not defined yet are patched later
+    fn address(&mut self, token: &Token, kind: FixupKind) -> Result<u16, AsmError> {
+        let max: i64 = match kind {
+            FixupKind::Word => 0xFFFF,
+            _ => 0xFFF,
+        };
+
+        match self.resolve(token) {
+            Some(addr) => self.fit_address(token, addr, max),
+            None => {
+                self.forward(token, kind, self.here)?;
+                Ok(0)
+            },
+        }
+    }
+
+    fn fit_address(&self, token: &Token, addr: i64, max: i64) -> Result<u16, AsmError> {
+        if !(0..=max).contains(&addr) {
+            return Err(AsmError::at(token, format!("address 0x{:X} is out of range (max 0x{:X})", addr, max)));
+        }
+
+        Ok(addr as u16)
+    }
+
+    fn forward(&mut self, token: &Token, kind: FixupKind, addr: usize) -> Result<(), AsmError> {
+        if !is_identifier(&token.text) {
+            return Err(AsmError::at(token, format!("expected an address, found '{}'", token.text)));
+        }
+        self.fixups.push(Fixup { addr, kind, label: token.clone() });
+
+        Ok(())
+    }
+
+    // OUTPUT
+
+    fn emit(&mut self, instruction: Instruction) -> Result<(), AsmError> {
+        self.lines.insert(self.here as u16, self.statement_line);
+        self.emit_word(instruction.encode())
+    }
+
+    fn emit_word(&mut self, word: u16) -> Result<(), AsmError> {
+        self.emit_byte((word >> 8) as u8)?;
+        self.emit_byte(word as u8)
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), AsmError> {
+        if self.here >= MAX_ADDRESS {
+            return Err(AsmError::at(&self.last, "program does not fit in 64kB"));
+        }
+
+        let idx: usize = self.here - START_ADDRESS;
+        if idx >= self.rom.len() {
+            self.rom.resize(idx + 1, 0);
+        }
+        self.rom[idx] = byte;
+        self.here += 1;
+
+        Ok(())
+    }
+
+    // Point the nnn of the instruction at `addr` to `target`
+    fn patch_low12(&mut self, addr: usize, target: usize, token: &Token) -> Result<(), AsmError> {
+        if target > 0xFFF {
+            return Err(AsmError::at(token, format!("address 0x{:X} is out of range (max 0xFFF)", target)));
+        }
+        self.patch(addr, target as u16, 0x0FFF);
+
+        Ok(())
+    }
+
+    // Overwrite the bits in `mask` of the word at `addr` (or of the byte at `addr` with a mask of 0xFF)
+    fn patch(&mut self, addr: usize, val: u16, mask: u16) {
+        let idx: usize = addr - START_ADDRESS;
+
+        if mask == 0xFF {
+            self.rom[idx] = val as u8;
+            return;
+        }
+
+        let word: u16 = (self.rom[idx] as u16) << 8 | self.rom[idx + 1] as u16;
+        let word: u16 = (word & !mask) | (val & mask);
+        self.rom[idx] = (word >> 8) as u8;
+        self.rom[idx + 1] = word as u8;
+    }
+}
+
+pub fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+
+    let val: i64 = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative {-val} else {val})
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit: &str = text.strip_prefix('v').or(text.strip_prefix('V'))?;
+    if digit.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
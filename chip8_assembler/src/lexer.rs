@@ -0,0 +1,41 @@
+// Octo source is a stream of whitespace separated tokens, # starts a comment that runs to the end of the line
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,        // 1-based
+    pub column: usize,      // 1-based, in characters
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let mut current: String = String::new();
+        let mut start_column: usize = 0;
+
+        for (column_idx, c) in line.chars().enumerate() {
+            if c == '#' && current.is_empty() {
+                break;
+            }
+
+            if c.is_whitespace() {
+                if !current.is_empty() {
+                    tokens.push(Token { text: std::mem::take(&mut current), line: line_idx + 1, column: start_column + 1 });
+                }
+                continue;
+            }
+
+            if current.is_empty() {
+                start_column = column_idx;
+            }
+            current.push(c);
+        }
+
+        if !current.is_empty() {
+            tokens.push(Token { text: current, line: line_idx + 1, column: start_column + 1 });
+        }
+    }
+
+    tokens
+}
@@ -0,0 +1,51 @@
+// Assembler for Octo, the CHIP-8 assembly language (https://github.com/JohnEarnest/Octo).
+//
+// Supports labels, registers and their aliases, :const, :calc, :macro, :byte, :org, :call, :unpack,
+// structured control flow (if ... then, if ... begin ... else ... end, loop ... while ... again)
+// and the CHIP-8, SUPER-CHIP and XO-CHIP instructions. The output is a byte image meant to be loaded
+// at 0x200 and a symbol table with the address of every label. The symbol table can be saved as a symbol
+// file (see symbols.rs) for debuggers to map addresses back to the source.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+mod calc;
+mod compiler;
+mod lexer;
+mod symbols;
+
+use lexer::Token;
+
+pub use symbols::SymbolFile;
+
+pub struct Program {
+    pub bytes: Vec<u8>,                     // ROM image, starts at 0x200
+    pub symbols: BTreeMap<String, u16>,     // label -> address
+    pub breakpoints: Vec<(u16, String)>,    // :breakpoint markers, address and name
+    pub lines: BTreeMap<u16, usize>,        // address of every instruction -> source line it came from
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,        // 1-based
+    pub column: usize,      // 1-based
+    pub message: String,
+}
+
+impl AsmError {
+    fn at(token: &Token, message: impl Into<String>) -> Self {
+        Self { line: token.line, column: token.column, message: message.into() }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    compiler::Compiler::new(source).compile()
+}
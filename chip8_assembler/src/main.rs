@@ -0,0 +1,56 @@
+use chip8_assembler::*;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    // Command Line argument
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 2 && args.len() != 3 {
+        println!("Usage: cargo run path/to/source.8o [path/to/output.ch8]");
+        return;
+    }
+
+    let source: String = match fs::read_to_string(&args[1]) {
+        Ok(source) => source,
+        Err(err) => {
+            println!("Failed to read {}: {}", args[1], err);
+            return;
+        }
+    };
+
+    let program: Program = match assemble(&source) {
+        Ok(program) => program,
+        Err(err) => {
+            println!("{}:{}", args[1], err);
+            std::process::exit(1);
+        }
+    };
+
+    // Output next to the source unless told otherwise
+    let output: String = match args.get(2) {
+        Some(path) => path.clone(),
+        None => Path::new(&args[1]).with_extension("ch8").to_string_lossy().into_owned(),
+    };
+
+    if let Err(err) = fs::write(&output, &program.bytes) {
+        println!("Failed to write {}: {}", output, err);
+        return;
+    }
+
+    println!("{} bytes written to {}", program.bytes.len(), output);
+
+    // Symbol file next to the ROM, with an absolute source path so debuggers can open it from anywhere
+    let symbols_path: String = Path::new(&output).with_extension("sym").to_string_lossy().into_owned();
+    let source_path: String = fs::canonicalize(&args[1]).map(|path| path.to_string_lossy().into_owned()).unwrap_or(args[1].clone());
+    let symbols: SymbolFile = SymbolFile::new(&program, Some(&source_path));
+
+    if let Err(err) = fs::write(&symbols_path, symbols.to_string()) {
+        println!("Failed to write {}: {}", symbols_path, err);
+        return;
+    }
+
+    for (name, addr) in &program.symbols {
+        println!("0x{:04X} {}", addr, name);
+    }
+}
@@ -0,0 +1,107 @@
+// Symbol files carry what a debugger needs to show a ROM as the source it was assembled from.
+// Plain text, one entry per line, addresses in hex:
+//
+//   source <path of the .8o file>
+//   label <address> <name>
+//   line <address> <source line>
+//   breakpoint <address> <name>
+//
+// Lines starting with # are comments. Unknown entries are skipped so the format can grow.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::Program;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolFile {
+    pub source: Option<String>,
+    pub labels: BTreeMap<String, u16>,
+    pub lines: BTreeMap<u16, usize>,        // address of every instruction -> source line
+    pub breakpoints: Vec<(u16, String)>,
+}
+
+impl SymbolFile {
+    pub fn new(program: &Program, source: Option<&str>) -> Self {
+        Self {
+            source: source.map(|path| path.to_string()),
+            labels: program.symbols.clone(),
+            lines: program.lines.clone(),
+            breakpoints: program.breakpoints.clone(),
+        }
+    }
+
+    // Returns the 1-based line number of the first entry that does not parse
+    pub fn parse(text: &str) -> Result<Self, usize> {
+        let mut symbols: SymbolFile = SymbolFile::default();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            if kind == "source" {
+                symbols.source = Some(rest.to_string());
+                continue;
+            }
+
+            let (addr, val) = rest.split_once(' ').ok_or(idx + 1)?;
+            let addr: u16 = u16::from_str_radix(addr.trim_start_matches("0x"), 16).map_err(|_| idx + 1)?;
+
+            match kind {
+                "label" => {symbols.labels.insert(val.to_string(), addr);},
+                "line" => {symbols.lines.insert(addr, val.parse().map_err(|_| idx + 1)?);},
+                "breakpoint" => symbols.breakpoints.push((addr, val.to_string())),
+                _ => {},
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    // Source line of the instruction at addr, or of the closest instruction before it
+    pub fn line_of(&self, addr: u16) -> Option<usize> {
+        self.lines.range(..=addr).next_back().map(|(_, line)| *line)
+    }
+
+    // Address of the first instruction on `line`, or on the first line after it that has code
+    pub fn address_of_line(&self, line: usize) -> Option<(u16, usize)> {
+        self.lines
+            .iter()
+            .filter(|(_, instruction_line)| **instruction_line >= line)
+            .min_by_key(|(addr, instruction_line)| (**instruction_line, **addr))
+            .map(|(addr, instruction_line)| (*addr, *instruction_line))
+    }
+
+    // The label at or before addr, with the distance from it
+    pub fn label_of(&self, addr: u16) -> Option<(&str, u16)> {
+        self.labels
+            .iter()
+            .filter(|(_, label_addr)| **label_addr <= addr)
+            .max_by_key(|(_, label_addr)| **label_addr)
+            .map(|(name, label_addr)| (name.as_str(), addr - label_addr))
+    }
+}
+
+impl fmt::Display for SymbolFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# chip8_assembler symbols")?;
+
+        if let Some(source) = &self.source {
+            writeln!(f, "source {}", source)?;
+        }
+        for (name, addr) in &self.labels {
+            writeln!(f, "label {:04X} {}", addr, name)?;
+        }
+        for (addr, line) in &self.lines {
+            writeln!(f, "line {:04X} {}", addr, line)?;
+        }
+        for (addr, name) in &self.breakpoints {
+            writeln!(f, "breakpoint {:04X} {}", addr, name)?;
+        }
+
+        Ok(())
+    }
+}
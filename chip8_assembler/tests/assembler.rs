@@ -0,0 +1,126 @@
+// Octo sources assembled to the bytes Octo itself produces for them, and the position of the errors
+
+use chip8_assembler::*;
+use chip8_engine::Chip8;
+
+fn bytes(source: &str) -> Vec<u8> {
+    match assemble(source) {
+        Ok(program) => program.bytes,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+fn error(source: &str) -> AsmError {
+    match assemble(source) {
+        Ok(_) => panic!("assembled without an error:\n{}", source),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn forward_label() {
+    let program: Program = assemble(": main\n  jump done\n  v0 := 1\n: done\n  clear\n").unwrap();
+
+    assert_eq!(program.bytes, [0x12, 0x04, 0x60, 0x01, 0x00, 0xE0]);
+    assert_eq!(program.symbols["done"], 0x204);
+    assert_eq!(program.lines[&0x204], 5);
+}
+
+#[test]
+fn jump_to_main() {
+    // main does not come first, so 0x200 jumps over the subroutine to it
+    assert_eq!(bytes(": sub return : main sub"), [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+}
+
+#[test]
+fn calc() {
+    // Right to left without precedence, like Octo: 2 * (3 + 1)
+    assert_eq!(bytes(": main :calc size { 2 * 3 + 1 } v0 := size"), [0x60, 0x08]);
+    assert_eq!(bytes(": main :const base 2 :calc shifted { base << 3 } v0 := shifted"), [0x60, 0x10]);
+    assert_eq!(bytes(": main :byte { HERE >> 8 }"), [0x02]);
+}
+
+#[test]
+fn macro_expansion() {
+    let source: &str = ": main\n:macro set reg val { reg := val }\nset v3 7 set v4 9";
+
+    assert_eq!(bytes(source), [0x63, 0x07, 0x64, 0x09]);
+}
+
+#[test]
+fn if_then() {
+    // Skips the statement after 'then' when v0 is not 5
+    assert_eq!(bytes(": main if v0 == 5 then v1 := 1"), [0x40, 0x05, 0x61, 0x01]);
+}
+
+#[test]
+fn if_begin_else_end() {
+    let source: &str = ": main if v0 != 5 begin v1 := 1 else v1 := 2 end";
+
+    assert_eq!(bytes(source), [0x40, 0x05, 0x12, 0x08, 0x61, 0x01, 0x12, 0x0A, 0x61, 0x02]);
+}
+
+#[test]
+fn ordered_comparisons_run() {
+    // v1 counts the comparisons of v0 against 5 that hold
+    for v0 in [4, 5, 6] {
+        let source: String = format!(
+            ": main v0 := {} v1 := 0 \
+             if v0 < 5 then v1 += 1 if v0 <= 5 then v1 += 2 if v0 > 5 then v1 += 4 if v0 >= 5 then v1 += 8 \
+             : done jump done",
+            v0,
+        );
+
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_rom(&bytes(&source)).unwrap();
+        for _ in 0..30 {
+            chip8.tick().unwrap();
+        }
+
+        let expected: u8 = match v0 {
+            4 => 1 | 2,
+            5 => 2 | 8,
+            _ => 4 | 8,
+        };
+        assert_eq!(chip8.registers()[1], expected, "v0 = {}", v0);
+    }
+}
+
+#[test]
+fn loop_while_again() {
+    let source: &str = ": main v0 := 0 loop v0 += 1 while v0 != 3 again";
+
+    assert_eq!(bytes(source), [0x60, 0x00, 0x70, 0x01, 0x40, 0x03, 0x12, 0x0A, 0x12, 0x02]);
+}
+
+#[test]
+fn unpack() {
+    // Forward, patched at the end, and backward, resolved on the spot
+    assert_eq!(bytes(": main :unpack 0xA data return : data 0x12"), [0x60, 0xA2, 0x61, 0x06, 0x00, 0xEE, 0x12]);
+    assert_eq!(bytes(": data 0x34 : main :unpack 1 data"), [0x12, 0x03, 0x34, 0x60, 0x12, 0x61, 0x02]);
+}
+
+#[test]
+fn long_index() {
+    let rom: Vec<u8> = bytes(": main i := long far :org 0x1234 : far 0xAB");
+
+    assert_eq!(&rom[..4], [0xF0, 0x00, 0x12, 0x34]);
+    assert_eq!(rom.len(), 0x1235 - 0x200);
+    assert_eq!(rom[0x1234 - 0x200], 0xAB);
+}
+
+#[test]
+fn unknown_token_position() {
+    let err: AsmError = error(": main\n  v0 := 1\n  bogus! 3\n");
+
+    assert_eq!((err.line, err.column), (3, 3));
+    assert_eq!(err.message, "unexpected 'bogus!'");
+}
+
+#[test]
+fn undefined_label_position() {
+    let err: AsmError = error(": main\n  jump nowhere\n");
+
+    assert_eq!((err.line, err.column), (2, 8));
+    assert_eq!(err.to_string(), "2:8: undefined label 'nowhere'");
+}
@@ -0,0 +1,63 @@
+use std::io::{self, BufReader};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod protocol;
+mod session;
+
+use serde_json::Value;
+use session::Session;
+
+const FRAME_TIME: Duration = Duration::from_micros(16_667);     // how often a running game checks for requests
+
+// Debug adapter for editors that speak the Debug Adapter Protocol, talks over stdin and stdout.
+// Launch arguments: program (ROM path), symbols (symbol file, defaults to the ROM path with .sym),
+// stopOnEntry and quirks (vip, chip48, schip or xochip)
+fn main() {
+    // Requests are read on their own thread so a running game can be interrupted at any time
+    let (sender, requests): (_, Receiver<Value>) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(io::stdin());
+        while let Ok(Some(message)) = protocol::read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session::new(io::stdout());
+    let mut last_run: Instant = Instant::now();
+
+    loop {
+        // Block while paused, only peek while running
+        let request: Option<Value> = if session.is_running() {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => {
+                    last_run = Instant::now();      // the time spent paused is not caught up on
+                    Some(request)
+                },
+                Err(_) => return,
+            }
+        };
+
+        if let Some(request) = request {
+            if !session.handle(&request) {
+                return;
+            }
+            continue;
+        }
+
+        let now: Instant = Instant::now();
+        session.run_for(now - last_run);
+        last_run = now;
+
+        thread::sleep(FRAME_TIME);
+    }
+}
@@ -0,0 +1,83 @@
+// Message framing of the Debug Adapter Protocol: a Content-Length header, a blank line and a JSON body
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+// The next message, None once the client closed the stream
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length: Option<usize> = None;
+
+    loop {
+        let mut header: String = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header: &str = header.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+
+        if let Some(len) = header.strip_prefix("Content-Length:") {
+            content_length = len.trim().parse().ok();
+        }
+    }
+
+    let mut body: Vec<u8> = vec![0; content_length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body).map(Some).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub struct Output<W: Write> {
+    writer: W,
+    seq: u64,
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, seq: 1 }
+    }
+
+    pub fn response(&mut self, request: &Value, body: Value) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    pub fn error(&mut self, request: &Value, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }));
+    }
+
+    pub fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+
+    fn send(&mut self, mut message: Value) {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+
+        // Nothing sensible to do when the client is gone, the read side notices and ends the session
+        let body: String = message.to_string();
+        let _ = write!(self.writer, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let _ = self.writer.flush();
+    }
+}
@@ -0,0 +1,739 @@
+// One debug session: the requests of the Debug Adapter Protocol mapped onto Chip8 and chip8_engine's Debugger.
+//
+// Execution is driven from main.rs with the real time that went by (run_for), requests are handled in between,
+// so a running game can always be paused. With a symbol file from chip8_assembler, breakpoints are set on source
+// lines and stepping goes line by line, without one everything works on instruction addresses.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use chip8_assembler::SymbolFile;
+use chip8_engine::*;
+use serde_json::{json, Value};
+
+use crate::protocol::Output;
+
+const THREAD_ID: u64 = 1;
+const MAX_STEPS_PER_RUN: usize = 1000;       // instruction steps a single line step may take per run_for()
+const MEMORY_ROW: usize = 16;               // bytes per variable of the Memory scope
+
+// variablesReference of the three scopes
+const REGISTERS_REF: u64 = 1;
+const TIMERS_REF: u64 = 2;
+const MEMORY_REF: u64 = 3;
+
+// A step over or into a source line, repeated one instruction at a time until it reaches a different line
+struct LineStep {
+    over: bool,
+    line: usize,
+    depth: usize,
+}
+
+pub struct Session<W: Write> {
+    out: Output<W>,
+    chip8: Chip8,
+    debugger: Debugger,
+    symbols: Option<SymbolFile>,
+
+    launched: bool,
+    stop_on_entry: bool,
+    line_breakpoints: Vec<u16>,         // set through setBreakpoints, replaced on every call
+    instruction_breakpoints: Vec<u16>,  // set through setInstructionBreakpoints
+    line_step: Option<LineStep>,
+}
+
+impl<W: Write> Session<W> {
+    pub fn new(writer: W) -> Self {
+        // Nothing runs until the client is done configuring
+        let mut debugger: Debugger = Debugger::new();
+        debugger.pause();
+
+        Self {
+            out: Output::new(writer),
+            chip8: Chip8::new(),
+            debugger,
+            symbols: None,
+
+            launched: false,
+            stop_on_entry: false,
+            line_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+            line_step: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.launched && !self.debugger.is_paused()
+    }
+
+    // Returns false once the client disconnected
+    pub fn handle(&mut self, request: &Value) -> bool {
+        if request["type"] != "request" {
+            return true;
+        }
+
+        let args: &Value = &request["arguments"];
+        match request["command"].as_str().unwrap_or("") {
+            "initialize" => self.out.response(request, json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsSteppingGranularity": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(request),
+            "setBreakpoints" => self.set_breakpoints(request),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(request),
+            "setExceptionBreakpoints" => self.out.response(request, json!({})),
+            "configurationDone" => {
+                self.out.response(request, json!({}));
+                if self.stop_on_entry {
+                    self.stopped("entry", None);
+                } else {
+                    self.debugger.resume();
+                }
+            },
+            "threads" => self.out.response(request, json!({
+                "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }],
+            })),
+            "stackTrace" => self.stack_trace(request),
+            "scopes" => self.out.response(request, json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_REF, "expensive": false },
+                    { "name": "Timers", "variablesReference": TIMERS_REF, "expensive": false },
+                    {
+                        "name": "Memory",
+                        "variablesReference": MEMORY_REF,
+                        "indexedVariables": self.chip8.memory().len().div_ceil(MEMORY_ROW),
+                        "expensive": true,
+                    },
+                ],
+            })),
+            "variables" => self.variables(request),
+            "readMemory" => self.read_memory(request),
+            "continue" => {
+                self.line_step = None;
+                self.debugger.resume();
+                self.out.response(request, json!({ "allThreadsContinued": true }));
+            },
+            "next" | "stepIn" => {
+                let over: bool = request["command"] == "next";
+                let by_instruction: bool = args["granularity"] == "instruction" || self.symbols.is_none();
+
+                self.line_step = match (by_instruction, self.current_line()) {
+                    (false, Some(line)) => Some(LineStep { over, line, depth: self.chip8.stack().len() }),
+                    _ => None,
+                };
+                self.step_instruction(over);
+                self.out.response(request, json!({}));
+            },
+            "stepOut" => {
+                self.line_step = None;
+                self.debugger.step_out(&self.chip8);
+                self.out.response(request, json!({}));
+            },
+            "pause" => {
+                self.out.response(request, json!({}));
+                self.debugger.pause();
+                self.stopped("pause", None);
+            },
+            "disconnect" | "terminate" => {
+                self.out.response(request, json!({}));
+                if request["command"] == "terminate" {
+                    self.out.event("terminated", json!({}));
+                }
+                return request["command"] != "disconnect";
+            },
+            command => self.out.error(request, &format!("unsupported request '{}'", command)),
+        }
+
+        true
+    }
+
+    // Run the game for the time that went by, the engine's scheduler turns it into instructions and timer ticks
+    // at the speed set on the Chip8. Reports whatever stopped it
+    pub fn run_for(&mut self, elapsed: Duration) {
+        let mut result: Result<FrameResult, Stop> = self.debugger.run_for(&mut self.chip8, elapsed);
+
+        for _ in 0..MAX_STEPS_PER_RUN {
+            if self.chip8.is_halted() {
+                self.debugger.pause();
+                self.launched = false;
+                self.out.event("exited", json!({ "exitCode": 0 }));
+                self.out.event("terminated", json!({}));
+                return;
+            }
+
+            // Every instruction of a line step stops the frame it ran in, the next one goes on right away
+            match result {
+                Ok(_) => return,
+                Err(Stop::Step) if self.continue_line_step() => {
+                    result = self.debugger.run_frame(&mut self.chip8);
+                    continue;
+                },
+                Err(Stop::Step) => self.stopped("step", None),
+                Err(Stop::Breakpoint(_)) => self.stopped("breakpoint", None),
+                Err(stop @ (Stop::Watchpoint { .. } | Stop::Register { .. })) => {
+                    self.stopped("data breakpoint", Some(stop.to_string()))
+                },
+                Err(Stop::Error(err)) => self.stopped("exception", Some(err.to_string())),
+            }
+            return;
+        }
+    }
+
+    // REQUESTS
+
+    // launch { program, symbols?, stopOnEntry?, quirks? }
+    fn launch(&mut self, request: &Value) {
+        let args: &Value = &request["arguments"];
+
+        let Some(program) = args["program"].as_str() else {
+            return self.out.error(request, "launch needs the path of a ROM in 'program'");
+        };
+
+        let quirks: Quirks = match args["quirks"].as_str() {
+            None =>             Quirks::default(),
+            Some("vip") =>      Quirks::VIP,
+            Some("chip48") =>   Quirks::CHIP48,
+            Some("schip") =>    Quirks::SCHIP,
+            Some("xochip") =>   Quirks::XOCHIP,
+            Some(other) => return self.out.error(request, &format!("unknown quirk profile '{}'", other)),
+        };
+
+        let rom: Vec<u8> = match fs::read(program) {
+            Ok(rom) => rom,
+            Err(err) => return self.out.error(request, &format!("unable to read {}: {}", program, err)),
+        };
+
+        self.chip8 = Chip8::with_quirks(quirks);
+        self.chip8.set_xo_chip(quirks == Quirks::XOCHIP);
+        if let Err(err) = self.chip8.load_rom(&rom) {
+            return self.out.error(request, &format!("unable to load ROM: {}", err));
+        }
+
+        // The symbol file the assembler writes next to the ROM is picked up unless another one is given
+        let symbols_path: String = match args["symbols"].as_str() {
+            Some(path) => path.to_string(),
+            None => Path::new(program).with_extension("sym").to_string_lossy().into_owned(),
+        };
+        self.symbols = match fs::read_to_string(&symbols_path) {
+            Ok(text) => match SymbolFile::parse(&text) {
+                Ok(symbols) => Some(symbols),
+                Err(line) => return self.out.error(request, &format!("{}:{}: bad symbol file entry", symbols_path, line)),
+            },
+            Err(_) if args["symbols"].is_string() => {
+                return self.out.error(request, &format!("unable to read {}", symbols_path));
+            },
+            Err(_) => None,
+        };
+
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.launched = true;
+
+        self.out.response(request, json!({}));
+        self.out.event("initialized", json!({}));
+    }
+
+    fn set_breakpoints(&mut self, request: &Value) {
+        for addr in self.line_breakpoints.drain(..) {
+            self.debugger.remove_breakpoint(addr);
+        }
+
+        let path: &str = request["arguments"]["source"]["path"].as_str().unwrap_or("");
+        let requested: &[Value] = request["arguments"]["breakpoints"].as_array().map(|lines| lines.as_slice()).unwrap_or(&[]);
+
+        let mut breakpoints: Vec<Value> = Vec::new();
+        for breakpoint in requested {
+            let line: usize = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+
+            match self.symbols.as_ref().filter(|symbols| same_source(symbols, path)).and_then(|symbols| symbols.address_of_line(line)) {
+                Some((addr, actual_line)) => {
+                    self.debugger.add_breakpoint(addr);
+                    self.line_breakpoints.push(addr);
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "line": actual_line,
+                        "instructionReference": format!("0x{:04X}", addr),
+                    }));
+                },
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "no code at this line, or no symbol file for this source",
+                })),
+            }
+        }
+
+        // A line breakpoint may share its address with an instruction breakpoint
+        for addr in &self.instruction_breakpoints {
+            self.debugger.add_breakpoint(*addr);
+        }
+
+        self.out.response(request, json!({ "breakpoints": breakpoints }));
+    }
+
+    fn set_instruction_breakpoints(&mut self, request: &Value) {
+        for addr in self.instruction_breakpoints.drain(..) {
+            self.debugger.remove_breakpoint(addr);
+        }
+
+        let requested: &[Value] = request["arguments"]["breakpoints"].as_array().map(|list| list.as_slice()).unwrap_or(&[]);
+
+        let mut breakpoints: Vec<Value> = Vec::new();
+        for breakpoint in requested {
+            let offset: i64 = breakpoint["offset"].as_i64().unwrap_or(0);
+            let addr: Option<u16> = breakpoint["instructionReference"]
+                .as_str()
+                .and_then(parse_address)
+                .and_then(|addr| u16::try_from(addr as i64 + offset).ok());
+
+            match addr {
+                Some(addr) => {
+                    self.debugger.add_breakpoint(addr);
+                    self.instruction_breakpoints.push(addr);
+                    breakpoints.push(json!({ "verified": true, "instructionReference": format!("0x{:04X}", addr) }));
+                },
+                None => breakpoints.push(json!({ "verified": false, "message": "not an address" })),
+            }
+        }
+
+        for addr in &self.line_breakpoints {
+            self.debugger.add_breakpoint(*addr);
+        }
+
+        self.out.response(request, json!({ "breakpoints": breakpoints }));
+    }
+
+    // The innermost frame is the current instruction, every return address on the stack adds the 2nnn that pushed it
+    fn stack_trace(&mut self, request: &Value) {
+        let mut addresses: Vec<u16> = vec![self.chip8.pc()];
+        addresses.extend(self.chip8.stack().iter().rev().map(|ret| ret.wrapping_sub(2)));
+
+        let frames: Vec<Value> = addresses.iter().enumerate().map(|(id, addr)| self.frame(id, *addr)).collect();
+
+        self.out.response(request, json!({ "stackFrames": frames, "totalFrames": addresses.len() }));
+    }
+
+    fn frame(&self, id: usize, addr: u16) -> Value {
+        let location: String = match self.symbols.as_ref().and_then(|symbols| symbols.label_of(addr)) {
+            Some((label, 0)) => label.to_string(),
+            Some((label, offset)) => format!("{}+0x{:X}", label, offset),
+            None => format!("0x{:04X}", addr),
+        };
+
+        let mut frame: Value = json!({
+            "id": id,
+            "name": format!("{}: {}", location, Instruction::decode(self.chip8.opcode_at(addr))),
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": format!("0x{:04X}", addr),
+        });
+
+        if let Some(symbols) = &self.symbols
+            && let (Some(source), Some(line)) = (&symbols.source, symbols.line_of(addr))
+        {
+            let name: String = Path::new(source).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or(source.clone());
+            frame["source"] = json!({ "name": name, "path": source });
+            frame["line"] = json!(line);
+            frame["column"] = json!(1);
+        }
+
+        frame
+    }
+
+    // variables { variablesReference, start?, count? }
+    fn variables(&mut self, request: &Value) {
+        let args: &Value = &request["arguments"];
+        let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+
+        let variables: Vec<Value> = match args["variablesReference"].as_u64() {
+            Some(REGISTERS_REF) => {
+                let mut variables: Vec<Value> = self.chip8.registers()
+                    .iter()
+                    .enumerate()
+                    .map(|(reg, val)| variable(format!("V{:X}", reg), format!("0x{:02X} ({})", val, val)))
+                    .collect();
+
+                let mut index: Value = variable("I".to_string(), format!("0x{:04X}", self.chip8.index_register()));
+                index["memoryReference"] = json!(format!("0x{:04X}", self.chip8.index_register()));
+                variables.push(index);
+                variables.push(variable("PC".to_string(), format!("0x{:04X}", self.chip8.pc())));
+                variables.push(variable("SP".to_string(), self.chip8.stack().len().to_string()));
+                variables
+            },
+            Some(TIMERS_REF) => vec![
+                variable("DT".to_string(), self.chip8.delay_timer().to_string()),
+                variable("ST".to_string(), self.chip8.sound_timer().to_string()),
+            ],
+            // A row per variable, up to 4096 of them in XO-CHIP mode. The scope announces them as indexed variables
+            // so the client asks for a page at a time, no count means all the rest
+            Some(MEMORY_REF) => {
+                let start: usize = args["start"].as_u64().unwrap_or(0) as usize;
+                let count: usize = args["count"].as_u64().filter(|count| *count > 0).map_or(usize::MAX, |count| count as usize);
+
+                self.chip8.memory()
+                    .chunks(MEMORY_ROW)
+                    .enumerate()
+                    .skip(start)
+                    .take(count)
+                    .map(|(row, bytes)| {
+                        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                        variable(format!("0x{:04X}", row * MEMORY_ROW), hex.join(" "))
+                    })
+                    .collect()
+            },
+            _ => Vec::new(),
+        };
+
+        self.out.response(request, json!({ "variables": variables }));
+    }
+
+    // readMemory { memoryReference, offset?, count }
+    fn read_memory(&mut self, request: &Value) {
+        let args: &Value = &request["arguments"];
+        let Some(base) = args["memoryReference"].as_str().and_then(parse_address) else {
+            return self.out.error(request, "memoryReference is not an address");
+        };
+
+        let start: usize = (base as i64 + args["offset"].as_i64().unwrap_or(0)).max(0) as usize;
+        let count: usize = args["count"].as_u64().unwrap_or(0) as usize;
+
+        let memory: &[u8] = self.chip8.memory();
+        let end: usize = start.saturating_add(count).min(memory.len());
+        let bytes: &[u8] = memory.get(start..end).unwrap_or(&[]);
+
+        self.out.response(request, json!({
+            "address": format!("0x{:04X}", start),
+            "data": base64(bytes),
+            "unreadableBytes": count - bytes.len(),
+        }));
+    }
+
+    // EXECUTION
+
+    fn step_instruction(&mut self, over: bool) {
+        if over {
+            self.debugger.step_over(&self.chip8);
+        } else {
+            self.debugger.step();
+        }
+    }
+
+    // After an instruction step that is part of a line step: true when it keeps going, false once it stops here
+    fn continue_line_step(&mut self) -> bool {
+        let Some(step) = &self.line_step else {
+            return false;
+        };
+
+        let at_new_line: bool = match self.symbols.as_ref().and_then(|symbols| symbols.lines.get(&self.chip8.pc())) {
+            Some(line) => *line != step.line,
+            None => false,
+        };
+        let returned: bool = self.chip8.stack().len() < step.depth;
+
+        if at_new_line || returned {
+            self.line_step = None;
+            return false;
+        }
+
+        let over: bool = step.over;
+        self.step_instruction(over);
+        true
+    }
+
+    fn current_line(&self) -> Option<usize> {
+        self.symbols.as_ref().and_then(|symbols| symbols.line_of(self.chip8.pc()))
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) {
+        self.line_step = None;
+
+        let mut body: Value = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(text) = text {
+            body["text"] = json!(text);
+            body["description"] = json!(text);
+        }
+
+        self.out.event("stopped", body);
+    }
+}
+
+// The client may spell the path differently (relative, other case on some systems), compare the file names
+fn same_source(symbols: &SymbolFile, path: &str) -> bool {
+    match &symbols.source {
+        Some(source) => Path::new(source).file_name() == Path::new(path).file_name(),
+        None => true,
+    }
+}
+
+fn parse_address(reference: &str) -> Option<u16> {
+    match reference.strip_prefix("0x").or(reference.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => reference.parse().ok(),
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits: u32 = chunk.iter().enumerate().fold(0, |bits, (i, byte)| bits | (*byte as u32) << (16 - i * 8));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::env;
+    use std::io::{self, Cursor};
+    use std::path::PathBuf;
+    use std::rc::Rc;
+
+    use chip8_assembler::{assemble, Program};
+
+    use crate::protocol;
+
+    const SOURCE: &str = "\
+: main
+  v0 := 5
+  i := data
+  sub
+: spin
+  jump spin
+: sub
+  v1 := 7
+  return
+: data
+  0x12 0x34 0xAB
+";
+
+    const FRAME: Duration = Duration::from_nanos(16_666_667);
+
+    // Everything the session wrote, shared with the test
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct Fixture {
+        session: Session<Shared>,
+        output: Shared,
+        dir: PathBuf,
+        seq: u64,
+    }
+
+    impl Fixture {
+        // SOURCE assembled into a ROM and symbol file of its own, launched and stopped on entry
+        fn launch(name: &str) -> Self {
+            let dir: PathBuf = env::temp_dir().join(format!("chip8_dap_{}_{}", std::process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+
+            let program: Program = assemble(SOURCE).unwrap();
+            let source: PathBuf = dir.join("game.8o");
+            fs::write(dir.join("game.ch8"), &program.bytes).unwrap();
+            fs::write(dir.join("game.sym"), SymbolFile::new(&program, source.to_str()).to_string()).unwrap();
+
+            let output: Shared = Shared::default();
+            let mut fixture: Fixture = Self { session: Session::new(output.clone()), output, dir, seq: 0 };
+
+            let program: String = fixture.path("game.ch8");
+            fixture.request("launch", json!({ "program": program, "stopOnEntry": true }));
+            fixture.request("configurationDone", json!({}));
+
+            fixture
+        }
+
+        fn path(&self, file: &str) -> String {
+            self.dir.join(file).to_string_lossy().into_owned()
+        }
+
+        // The messages the session sent in reply, response first
+        fn request(&mut self, command: &str, arguments: Value) -> Vec<Value> {
+            self.seq += 1;
+            self.session.handle(&json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }));
+            self.messages()
+        }
+
+        fn body(&mut self, command: &str, arguments: Value) -> Value {
+            let response: Value = self.request(command, arguments).remove(0);
+            assert_eq!(response["success"], true, "{}", response);
+
+            response["body"].clone()
+        }
+
+        fn messages(&mut self) -> Vec<Value> {
+            let bytes: Vec<u8> = self.output.0.take();
+            let mut reader: Cursor<Vec<u8>> = Cursor::new(bytes);
+
+            std::iter::from_fn(|| protocol::read_message(&mut reader).unwrap()).collect()
+        }
+
+        // Run until something stops the game, returns the reason
+        fn run_until_stopped(&mut self) -> Value {
+            for _ in 0..100 {
+                self.session.run_for(FRAME);
+                if let Some(stopped) = self.messages().into_iter().find(|message| message["event"] == "stopped") {
+                    return stopped["body"]["reason"].clone();
+                }
+            }
+
+            panic!("never stopped");
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn set_breakpoints_on_source_lines() {
+        let mut fixture: Fixture = Fixture::launch("breakpoints");
+        let source: String = fixture.path("game.8o");
+
+        // Line 5 is a label, the breakpoint moves to the code on line 6. Line 40 has no code at all
+        let body: Value = fixture.body("setBreakpoints", json!({
+            "source": { "path": source },
+            "breakpoints": [{ "line": 4 }, { "line": 8 }, { "line": 5 }, { "line": 40 }],
+        }));
+        let breakpoints: &Vec<Value> = body["breakpoints"].as_array().unwrap();
+
+        let verified: Vec<(Value, Value)> = breakpoints[..3].iter().map(|bp| (bp["line"].clone(), bp["instructionReference"].clone())).collect();
+        assert_eq!(verified, [(json!(4), json!("0x0204")), (json!(8), json!("0x0208")), (json!(6), json!("0x0206"))]);
+        assert!(breakpoints[..3].iter().all(|bp| bp["verified"] == true));
+        assert_eq!(breakpoints[3]["verified"], false);
+
+        fixture.body("continue", json!({}));
+        assert_eq!(fixture.run_until_stopped(), "breakpoint");
+        assert_eq!(fixture.session.chip8.pc(), 0x204);
+
+        // Breakpoints in another file do not map onto this ROM
+        let body: Value = fixture.body("setBreakpoints", json!({ "source": { "path": "other.8o" }, "breakpoints": [{ "line": 4 }] }));
+        assert_eq!(body["breakpoints"][0]["verified"], false);
+    }
+
+    #[test]
+    fn stack_trace() {
+        let mut fixture: Fixture = Fixture::launch("stack_trace");
+        let source: String = fixture.path("game.8o");
+        fixture.body("setBreakpoints", json!({ "source": { "path": source }, "breakpoints": [{ "line": 8 }] }));
+        fixture.body("continue", json!({}));
+        assert_eq!(fixture.run_until_stopped(), "breakpoint");
+
+        let body: Value = fixture.body("stackTrace", json!({ "threadId": THREAD_ID }));
+        let frames: &Vec<Value> = body["stackFrames"].as_array().unwrap();
+        assert_eq!(body["totalFrames"], 2);
+
+        assert_eq!(frames[0]["name"], "sub: LD V1, 0x07");
+        assert_eq!(frames[0]["line"], 8);
+        assert_eq!(frames[0]["instructionPointerReference"], "0x0208");
+        assert_eq!(frames[0]["source"]["path"], source);
+
+        // The caller, on its 2nnn
+        assert_eq!(frames[1]["name"], "main+0x4: CALL 0x208");
+        assert_eq!(frames[1]["line"], 4);
+    }
+
+    #[test]
+    fn read_memory() {
+        let mut fixture: Fixture = Fixture::launch("read_memory");
+
+        let body: Value = fixture.body("readMemory", json!({ "memoryReference": "0x020C", "count": 3 }));
+        assert_eq!((&body["address"], &body["data"], &body["unreadableBytes"]), (&json!("0x020C"), &json!("EjSr"), &json!(0)));
+
+        // Offset from the reference, padded base64, and the part past the end of memory is unreadable
+        let body: Value = fixture.body("readMemory", json!({ "memoryReference": "0x0FFC", "offset": 2, "count": 4 }));
+        assert_eq!((&body["address"], &body["data"], &body["unreadableBytes"]), (&json!("0x0FFE"), &json!("AAA="), &json!(2)));
+
+        let response: Value = fixture.request("readMemory", json!({ "memoryReference": "data", "count": 1 })).remove(0);
+        assert_eq!(response["success"], false);
+    }
+
+    #[test]
+    fn line_steps() {
+        let mut fixture: Fixture = Fixture::launch("line_steps");
+
+        fixture.body("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(fixture.run_until_stopped(), "step");
+        assert_eq!(fixture.session.chip8.pc(), 0x202);
+
+        // Over the call to the line after it, then into it
+        fixture.body("next", json!({ "threadId": THREAD_ID }));
+        fixture.run_until_stopped();
+        fixture.body("next", json!({ "threadId": THREAD_ID }));
+        assert_eq!(fixture.run_until_stopped(), "step");
+        assert_eq!(fixture.session.chip8.pc(), 0x206);
+
+        let mut fixture: Fixture = Fixture::launch("step_in");
+        for _ in 0..3 {
+            fixture.body("stepIn", json!({ "threadId": THREAD_ID }));
+            fixture.run_until_stopped();
+        }
+        assert_eq!((fixture.session.chip8.pc(), fixture.session.chip8.stack().len()), (0x208, 1));
+    }
+
+    #[test]
+    fn memory_variables_are_paged() {
+        let mut fixture: Fixture = Fixture::launch("memory_variables");
+
+        let body: Value = fixture.body("scopes", json!({ "frameId": 0 }));
+        assert_eq!(body["scopes"][2]["indexedVariables"], 256);
+
+        let body: Value = fixture.body("variables", json!({ "variablesReference": MEMORY_REF, "start": 32, "count": 2 }));
+        let rows: &Vec<Value> = body["variables"].as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((&rows[0]["name"], &rows[1]["name"]), (&json!("0x0200"), &json!("0x0210")));
+        assert!(rows[0]["value"].as_str().unwrap().starts_with("60 05 A2 0C"));
+
+        // Past the last row the page comes back short, without a count it runs to the end
+        let body: Value = fixture.body("variables", json!({ "variablesReference": MEMORY_REF, "start": 255, "count": 10 }));
+        assert_eq!(body["variables"].as_array().unwrap().len(), 1);
+        let body: Value = fixture.body("variables", json!({ "variablesReference": MEMORY_REF, "start": 250 }));
+        assert_eq!(body["variables"].as_array().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn timers_run_at_60_hz() {
+        let mut fixture: Fixture = Fixture::launch("timers");
+        fixture.session.chip8.set_delay_timer(60);
+        fixture.body("continue", json!({}));
+
+        // Half a second of real time is 30 timer ticks, whatever the speed
+        fixture.session.chip8.set_speed(1200);
+        for _ in 0..5 {
+            fixture.session.run_for(Duration::from_millis(100));
+        }
+        assert_eq!(fixture.session.chip8.delay_timer(), 30);
+
+        // Paused, time stands still
+        fixture.body("pause", json!({}));
+        fixture.session.chip8.set_delay_timer(30);
+        fixture.session.run_for(Duration::from_millis(500));
+        assert_eq!(fixture.session.chip8.delay_timer(), 30);
+    }
+}
@@ -0,0 +1,130 @@
+// PCM sample generator for the buzzer, so every frontend does not have to write its own.
+//
+// The frontend tells the Beeper whether sound is active (Chip8::sound_active(), checked every frame) and pulls
+// samples from it in its audio callback. Starting and stopping fades over a few milliseconds instead of cutting
+// the wave mid-period, which is what makes the click. XO-CHIP programs can set a 128-bit pattern and a pitch
+// (F002 / Fx3A), set_pattern() plays that instead of the plain tone.
+
+use crate::*;
+
+const DEFAULT_FREQUENCY: f32 = 440.0;
+const DEFAULT_VOLUME: f32 = 0.25;
+const FADE_SECONDS: f32 = 0.005;            // 5 ms, long enough to not click, short enough to not be heard
+const PATTERN_BITS: f32 = (AUDIO_PATTERN_SIZE * 8) as f32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+}
+
+pub struct Beeper {
+    sample_rate: f32,
+    waveform: Waveform,
+    frequency: f32,                 // Hz
+    volume: f32,                    // 0.0 - 1.0
+    pattern: Option<([u8; AUDIO_PATTERN_SIZE], f32)>,   // XO-CHIP pattern and its playback rate in bits per second
+
+    active: bool,
+    phase: f32,                     // position in the current period (or in the pattern), 0.0 - 1.0
+    envelope: f32,                  // fades between 0.0 and 1.0 when sound starts and stops
+}
+
+impl Beeper {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1) as f32,
+            waveform: Waveform::Square,
+            frequency: DEFAULT_FREQUENCY,
+            volume: DEFAULT_VOLUME,
+            pattern: None,
+
+            active: false,
+            phase: 0.0,
+            envelope: 0.0,
+        }
+    }
+
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        self.frequency = frequency.max(0.0);
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // Play an XO-CHIP audio pattern, at 4000 * 2^((pitch - 64) / 48) bits per second
+    pub fn set_pattern(&mut self, pattern: &[u8; AUDIO_PATTERN_SIZE], pitch: u8) {
+        let rate: f32 = 4000.0 * libm::powf(2.0, (pitch as f32 - 64.0) / 48.0);     // libm, core has no powf or sin
+        self.pattern = Some((*pattern, rate));
+    }
+
+    // Back to the plain tone
+    pub fn clear_pattern(&mut self) {
+        self.pattern = None;
+    }
+
+    // Fill a buffer of mono samples, -1.0 - 1.0
+    pub fn fill(&mut self, out: &mut [f32]) {
+        let fade_step: f32 = 1.0 / (self.sample_rate * FADE_SECONDS);
+
+        for sample in out.iter_mut() {
+            self.envelope = match self.active {
+                true => (self.envelope + fade_step).min(1.0),
+                false => (self.envelope - fade_step).max(0.0),
+            };
+
+            // Silent: restart the wave so the next beep starts the same way
+            if self.envelope == 0.0 {
+                self.phase = 0.0;
+                *sample = 0.0;
+                continue;
+            }
+
+            *sample = self.wave() * self.volume * self.envelope;
+            self.advance();
+        }
+    }
+
+    fn wave(&self) -> f32 {
+        if let Some((pattern, _)) = &self.pattern {
+            let bit: usize = (self.phase * PATTERN_BITS) as usize % (AUDIO_PATTERN_SIZE * 8);
+            return match pattern[bit / 8] & (0x80 >> (bit % 8)) {
+                0 => -1.0,
+                _ => 1.0,
+            };
+        }
+
+        match self.waveform {
+            Waveform::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sine => libm::sinf(self.phase * core::f32::consts::TAU),
+        }
+    }
+
+    fn advance(&mut self) {
+        // The pattern loops once every 128 bits, a tone once every period
+        let rate: f32 = match &self.pattern {
+            Some((_, bits_per_second)) => bits_per_second / PATTERN_BITS,
+            None => self.frequency,
+        };
+
+        let phase: f32 = self.phase + rate / self.sample_rate;
+        self.phase = phase - libm::floorf(phase);
+    }
+}
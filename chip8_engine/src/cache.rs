@@ -0,0 +1,69 @@
+// Pre-decoded instruction cache, the execution core behind Core::Cached.
+//
+// The interpreter reads two bytes and decodes them on every tick. The cached core decodes each address the first
+// time it runs and keeps the Instruction (4 bytes, one entry per byte of memory), so a loop only pays for the
+// decoding once. Every write to memory goes through write_memory() or replaces memory as a whole (reset, load_rom,
+// load_state), and those drop the entries the write touched, so a program that modifies itself with Fx55, Fx33
+// or 5xy2 runs exactly as it does in the interpreter.
+
+use crate::*;
+
+impl Chip8 {
+    pub fn core(&self) -> Core {
+        self.core
+    }
+
+    // Switch execution core, the cache starts out empty
+    pub fn set_core(&mut self, core: Core) {
+        self.core = core;
+        self.decoded = Vec::new();
+        self.blocks = Vec::new();
+        self.block_cover = Vec::new();
+        self.invalidate_all();
+    }
+
+    // FETCH through the cache. A miss fetches and decodes like the interpreter (with the same errors) and keeps
+    // the result, a hit only moves the program counter on
+    pub(crate) fn fetch_cached(&mut self) -> Result<Instruction, Chip8Error> {
+        let pc: usize = self.pc as usize;
+        if let Some(Some(instruction)) = self.decoded.get(pc) {
+            self.pc = self.pc.wrapping_add(2);
+            return Ok(*instruction);
+        }
+
+        let instruction: Instruction = Instruction::decode(self.fetch()?);
+        if let Some(entry) = self.decoded.get_mut(pc) {
+            *entry = Some(instruction);
+        }
+
+        Ok(instruction)
+    }
+
+    // The byte at addr is part of the opcode starting there and of the one starting right before it. The
+    // recompiler drops the blocks it is part of
+    pub(crate) fn invalidate(&mut self, addr: usize) {
+        self.invalidate_blocks(addr);
+
+        if let Some(entry) = self.decoded.get_mut(addr) {
+            *entry = None;
+        }
+        if let Some(entry) = addr.checked_sub(1).and_then(|addr: usize| self.decoded.get_mut(addr)) {
+            *entry = None;
+        }
+    }
+
+    // Drop everything, after memory was replaced or changed size. Keeps the cache empty in the other cores
+    pub(crate) fn invalidate_all(&mut self) {
+        self.decoded.clear();
+        self.blocks.clear();
+        self.block_cover.clear();
+        match self.core {
+            Core::Interpreter => {}
+            Core::Cached => self.decoded.resize(self.memory_size(), None),
+            Core::Recompiler => {
+                self.blocks.resize(self.memory_size(), None);
+                self.block_cover.resize(self.memory_size(), false);
+            }
+        }
+    }
+}
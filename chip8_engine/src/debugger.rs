@@ -0,0 +1,290 @@
+// Debugger wraps Chip8::tick with breakpoints, watchpoints and stepping.
+//
+// It never blocks: a frontend calls run() once per frame with its usual instruction budget, exactly where it
+// would otherwise call tick() in a loop. run() executes until the budget is used up or something stops it,
+// after which the debugger stays paused (run() does nothing) until resume(), step(), step_over() or step_out().
+//
+// run_frame() and run_for() do the same on top of the frame scheduler, see scheduler.rs.
+//
+// Memory watchpoints are checked against the addresses an instruction is about to touch, worked out from the
+// decoded instruction: Fx55/5xy2 and Fx33 write, Fx65/5xy3 and Dxyn read. Register watchpoints compare V0 - VF
+// before and after every instruction.
+
+use alloc::collections::BTreeSet;
+use core::fmt;
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::*;
+use crate::scheduler::run_cycles;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,      // only used when setting a watchpoint, hits are always reported as Read or Write
+}
+
+// Why run() stopped. The instruction that hit a watchpoint has already executed, a breakpoint stops before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint(u16),
+    Watchpoint { addr: u16, access: Access },
+    Register { reg: u8, old: u8, new: u8 },
+    Step,                                       // step, step over or step out completed
+    Error(Chip8Error),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(addr) => write!(f, "breakpoint at 0x{:04X}", addr),
+            Stop::Watchpoint { addr, access: Access::Write } => write!(f, "watchpoint, write to 0x{:04X}", addr),
+            Stop::Watchpoint { addr, .. } => write!(f, "watchpoint, read from 0x{:04X}", addr),
+            Stop::Register { reg, old, new } => write!(f, "V{:X} changed from 0x{:02X} to 0x{:02X}", reg, old, new),
+            Stop::Step => write!(f, "step"),
+            Stop::Error(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    Step,
+    StepOver { return_pc: u16, depth: usize },     // until the call at pc returns
+    StepOut { depth: usize },                      // until a 00EE drops the stack below depth
+}
+
+pub struct Debugger {
+    mode: Mode,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<(u16, Access)>,
+    watched_registers: u16,             // bit n set when Vn is watched
+    resume_from: Option<u16>,           // the breakpoint we are paused on, passed over when execution continues
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    // Starts out running, so wrapping the gameloop in a debugger changes nothing until a breakpoint is set
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Running,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            watched_registers: 0,
+            resume_from: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    pub fn resume(&mut self) {
+        self.mode = Mode::Running;
+    }
+
+    // Execute a single instruction on the next run()
+    pub fn step(&mut self) {
+        self.mode = Mode::Step;
+    }
+
+    // Like step, but a 2nnn runs the whole subroutine and stops on the instruction after the call
+    pub fn step_over(&mut self, chip8: &Chip8) {
+        self.resume_from = Some(chip8.pc);
+        self.mode = match Instruction::decode(chip8.opcode_at(chip8.pc)) {
+            Instruction::Call(_) => Mode::StepOver { return_pc: chip8.pc.wrapping_add(2), depth: chip8.stack().len() },
+            _ => Mode::Step,
+        };
+    }
+
+    // Run until the current subroutine returns, stops on the instruction after its 2nnn
+    pub fn step_out(&mut self, chip8: &Chip8) {
+        self.resume_from = Some(chip8.pc);
+        self.mode = match chip8.stack().len() {
+            0 => Mode::Running,         // not in a subroutine, nothing to step out of
+            depth => Mode::StepOut { depth },
+        };
+    }
+
+    // BREAKPOINTS & WATCHPOINTS
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn watch_memory(&mut self, addr: u16, access: Access) {
+        self.unwatch_memory(addr);
+        self.watchpoints.push((addr, access));
+    }
+
+    pub fn unwatch_memory(&mut self, addr: u16) {
+        self.watchpoints.retain(|(watched, _)| *watched != addr);
+    }
+
+    pub fn watchpoints(&self) -> &[(u16, Access)] {
+        &self.watchpoints
+    }
+
+    pub fn watch_register(&mut self, reg: u8) {
+        self.watched_registers |= 1 << (reg & 0xF);
+    }
+
+    pub fn unwatch_register(&mut self, reg: u8) {
+        self.watched_registers &= !(1 << (reg & 0xF));
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+        self.watched_registers = 0;
+    }
+
+    // EXECUTION
+
+    // Execute up to `ticks` instructions. Returns why execution stopped early, None when the budget was used up
+    // (or the debugger was already paused)
+    pub fn run(&mut self, chip8: &mut Chip8, ticks: usize) -> Option<Stop> {
+        for _ in 0..ticks {
+            if self.mode == Mode::Paused || chip8.is_halted() {
+                return None;
+            }
+
+            // A single step always executes the instruction it starts on, breakpoint or not
+            let pc: u16 = chip8.pc;
+            if self.breakpoints.contains(&pc) && self.resume_from != Some(pc) && self.mode != Mode::Step {
+                self.mode = Mode::Paused;
+                self.resume_from = Some(pc);
+                return Some(Stop::Breakpoint(pc));
+            }
+            self.resume_from = None;
+
+            let instruction: Instruction = Instruction::decode(chip8.opcode_at(chip8.pc));
+            let (reads, writes) = memory_accesses(chip8, instruction);
+            let registers: [u8; V_REG_SIZE] = chip8.v_reg;
+
+            if let Err(err) = chip8.tick() {
+                self.mode = Mode::Paused;
+                return Some(Stop::Error(err));
+            }
+
+            if let Some(stop) = self.check_watchpoints(chip8, &registers, reads, writes) {
+                self.mode = Mode::Paused;
+                return Some(stop);
+            }
+
+            let done: bool = match self.mode {
+                Mode::Step => true,
+                Mode::StepOver { return_pc, depth } => chip8.pc == return_pc && chip8.stack().len() == depth,
+                Mode::StepOut { depth } => instruction == Instruction::Ret && chip8.stack().len() < depth,
+                Mode::Running | Mode::Paused => false,
+            };
+            if done {
+                self.mode = Mode::Paused;
+                return Some(Stop::Step);
+            }
+        }
+
+        None
+    }
+
+    // Debugger-aware versions of Chip8::run_frame() and Chip8::run_for(). While paused no emulated time passes,
+    // the timers hold and the time that went by is dropped instead of being caught up on resume
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<FrameResult, Stop> {
+        self.run_frames(chip8, 1)
+    }
+
+    pub fn run_for(&mut self, chip8: &mut Chip8, elapsed: Duration) -> Result<FrameResult, Stop> {
+        if self.mode == Mode::Paused {
+            chip8.discard_time();
+            return Ok(chip8.frame_result(0));
+        }
+
+        let frames: usize = chip8.frames_due(elapsed);
+        self.run_frames(chip8, frames)
+    }
+
+    fn run_frames(&mut self, chip8: &mut Chip8, frames: usize) -> Result<FrameResult, Stop> {
+        if self.mode == Mode::Paused {
+            return Ok(chip8.frame_result(0));
+        }
+
+        // Nothing that could stop it, so the cached core and the recompiler get to run whole blocks
+        if self.mode == Mode::Running && self.breakpoints.is_empty() && self.watchpoints.is_empty() && self.watched_registers == 0 {
+            self.resume_from = None;
+            return chip8.run_frames_with(frames, run_cycles).map_err(|err: Chip8Error| {
+                self.mode = Mode::Paused;
+                Stop::Error(err)
+            });
+        }
+
+        chip8.run_frames_with(frames, |chip8, cycles| match self.run(chip8, cycles) {
+            Some(stop) => Err(stop),
+            None => Ok(()),
+        })
+    }
+
+    fn check_watchpoints(&self, chip8: &Chip8, registers: &[u8; V_REG_SIZE], reads: Range<usize>, writes: Range<usize>) -> Option<Stop> {
+        for (addr, access) in &self.watchpoints {
+            let addr_idx: usize = *addr as usize;
+
+            if *access != Access::Read && writes.contains(&addr_idx) {
+                return Some(Stop::Watchpoint { addr: *addr, access: Access::Write });
+            }
+            if *access != Access::Write && reads.contains(&addr_idx) {
+                return Some(Stop::Watchpoint { addr: *addr, access: Access::Read });
+            }
+        }
+
+        (0..V_REG_SIZE)
+            .filter(|reg| self.watched_registers & (1 << reg) != 0 && registers[*reg] != chip8.v_reg[*reg])
+            .map(|reg| Stop::Register { reg: reg as u8, old: registers[reg], new: chip8.v_reg[reg] })
+            .next()
+    }
+}
+
+// The memory an instruction is about to read and write, as (reads, writes)
+fn memory_accesses(chip8: &Chip8, instruction: Instruction) -> (Range<usize>, Range<usize>) {
+    let i: usize = chip8.index_reg as usize;
+
+    match instruction {
+        Instruction::Store(x) => (0..0, i..i + x as usize + 1),
+        Instruction::Load(x) => (i..i + x as usize + 1, 0..0),
+        Instruction::SaveRange(x, y) => (0..0, i..i + x.abs_diff(y) as usize + 1),
+        Instruction::LoadRange(x, y) => (i..i + x.abs_diff(y) as usize + 1, 0..0),
+        Instruction::Bcd(_) => (0..0, i..i + 3),
+
+        // Nothing is drawn while the display wait quirk holds the sprite back. Rows clipped at the bottom edge
+        // are not actually read, the whole sprite is still reported
+        Instruction::Draw(..) if chip8.quirks.display_wait && !chip8.vblank => (0..0, 0..0),
+        Instruction::Draw(_, _, n) => {
+            let sprite_size: usize = if n == 0 {32} else {n as usize};
+            let planes: usize = chip8.plane_mask.count_ones() as usize;
+            (i..i + sprite_size * planes, 0..0)
+        },
+
+        _ => (0..0, 0..0),
+    }
+}
@@ -0,0 +1,472 @@
+// Typed CHIP-8 instructions. Chip8::execute decodes every opcode through Instruction::decode and dispatches on
+// the result, so this is the one place that knows how the opcode bits are laid out.
+//
+// Display prints the instruction with Cowgod's mnemonics (the ones used in the comments of execute),
+// the alternate form ("{:#}") prints it in Octo syntax.
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,                    // 0000
+    ScrollDown(u8),         // 00Cn (SUPER-CHIP)
+    ScrollUp(u8),           // 00Dn (XO-CHIP)
+    Cls,                    // 00E0
+    Ret,                    // 00EE
+    ScrollRight,            // 00FB (SUPER-CHIP)
+    ScrollLeft,             // 00FC (SUPER-CHIP)
+    Exit,                   // 00FD (SUPER-CHIP)
+    Lores,                  // 00FE (SUPER-CHIP)
+    Hires,                  // 00FF (SUPER-CHIP)
+    Jump(u16),              // 1nnn
+    Call(u16),              // 2nnn
+    SkipEqByte(u8, u8),     // 3xnn
+    SkipNeByte(u8, u8),     // 4xnn
+    SkipEqReg(u8, u8),      // 5xy0
+    SaveRange(u8, u8),      // 5xy2 (XO-CHIP)
+    LoadRange(u8, u8),      // 5xy3 (XO-CHIP)
+    LoadByte(u8, u8),       // 6xnn
+    AddByte(u8, u8),        // 7xnn
+    LoadReg(u8, u8),        // 8xy0
+    Or(u8, u8),             // 8xy1
+    And(u8, u8),            // 8xy2
+    Xor(u8, u8),            // 8xy3
+    AddReg(u8, u8),         // 8xy4
+    Sub(u8, u8),            // 8xy5
+    Shr(u8, u8),            // 8xy6
+    SubN(u8, u8),           // 8xy7
+    Shl(u8, u8),            // 8xyE
+    SkipNeReg(u8, u8),      // 9xy0
+    LoadI(u16),             // Annn
+    JumpOffset(u16),        // Bnnn
+    Random(u8, u8),         // Cxnn
+    Draw(u8, u8, u8),       // Dxyn
+    SkipKey(u8),            // Ex9E
+    SkipNotKey(u8),         // ExA1
+    LoadILong,              // F000 nnnn (XO-CHIP), the address is the next opcode-sized word in memory
+    Plane(u8),              // Fn01 (XO-CHIP)
+    Audio,                  // F002 (XO-CHIP)
+    LoadDelay(u8),          // Fx07
+    WaitKey(u8),            // Fx0A
+    SetDelay(u8),           // Fx15
+    SetSound(u8),           // Fx18
+    AddI(u8),               // Fx1E
+    Font(u8),               // Fx29
+    BigFont(u8),            // Fx30 (SUPER-CHIP)
+    Bcd(u8),                // Fx33
+    Pitch(u8),              // Fx3A (XO-CHIP)
+    Store(u8),              // Fx55
+    Load(u8),               // Fx65
+    StoreFlags(u8),         // Fx75 (SUPER-CHIP)
+    LoadFlags(u8),          // Fx85 (SUPER-CHIP)
+    Unknown(u16),
+}
+
+impl Instruction {
+    pub fn decode(op: u16) -> Instruction {
+        // DECODE
+        let nibbles: (u8, u8, u8, u8) = (
+            ((op & 0xF000) >> 12) as u8,    // extract the 1st nibble
+            ((op & 0x0F00) >> 8) as u8,     // extract the 2nd nibble
+            ((op & 0x00F0) >> 4) as u8,     // extract the 3rd nibble
+            (op & 0x000F) as u8             // extract the 4th nibble
+        );
+
+        let x: u8 = nibbles.1;
+        let y: u8 = nibbles.2;
+        let n: u8 = nibbles.3;
+        let nn: u8 = (op & 0x00FF) as u8;
+        let nnn: u16 = op & 0x0FFF;
+
+        match nibbles {
+            (0, 0, 0, 0) =>         Instruction::Nop,
+            (0, 0, 0xC, _) =>       Instruction::ScrollDown(n),
+            (0, 0, 0xD, _) =>       Instruction::ScrollUp(n),
+            (0, 0, 0xE, 0) =>       Instruction::Cls,
+            (0, 0, 0xE, 0xE) =>     Instruction::Ret,
+            (0, 0, 0xF, 0xB) =>     Instruction::ScrollRight,
+            (0, 0, 0xF, 0xC) =>     Instruction::ScrollLeft,
+            (0, 0, 0xF, 0xD) =>     Instruction::Exit,
+            (0, 0, 0xF, 0xE) =>     Instruction::Lores,
+            (0, 0, 0xF, 0xF) =>     Instruction::Hires,
+            (1, _, _, _) =>         Instruction::Jump(nnn),
+            (2, _, _, _) =>         Instruction::Call(nnn),
+            (3, _, _, _) =>         Instruction::SkipEqByte(x, nn),
+            (4, _, _, _) =>         Instruction::SkipNeByte(x, nn),
+            (5, _, _, 0) =>         Instruction::SkipEqReg(x, y),
+            (5, _, _, 2) =>         Instruction::SaveRange(x, y),
+            (5, _, _, 3) =>         Instruction::LoadRange(x, y),
+            (6, _, _, _) =>         Instruction::LoadByte(x, nn),
+            (7, _, _, _) =>         Instruction::AddByte(x, nn),
+            (8, _, _, 0) =>         Instruction::LoadReg(x, y),
+            (8, _, _, 1) =>         Instruction::Or(x, y),
+            (8, _, _, 2) =>         Instruction::And(x, y),
+            (8, _, _, 3) =>         Instruction::Xor(x, y),
+            (8, _, _, 4) =>         Instruction::AddReg(x, y),
+            (8, _, _, 5) =>         Instruction::Sub(x, y),
+            (8, _, _, 6) =>         Instruction::Shr(x, y),
+            (8, _, _, 7) =>         Instruction::SubN(x, y),
+            (8, _, _, 0xE) =>       Instruction::Shl(x, y),
+            (9, _, _, 0) =>         Instruction::SkipNeReg(x, y),
+            (0xA, _, _, _) =>       Instruction::LoadI(nnn),
+            (0xB, _, _, _) =>       Instruction::JumpOffset(nnn),
+            (0xC, _, _, _) =>       Instruction::Random(x, nn),
+            (0xD, _, _, _) =>       Instruction::Draw(x, y, n),
+            (0xE, _, 9, 0xE) =>     Instruction::SkipKey(x),
+            (0xE, _, 0xA, 1) =>     Instruction::SkipNotKey(x),
+            (0xF, 0, 0, 0) =>       Instruction::LoadILong,
+            (0xF, _, 0, 1) =>       Instruction::Plane(x),
+            (0xF, 0, 0, 2) =>       Instruction::Audio,
+            (0xF, _, 0, 7) =>       Instruction::LoadDelay(x),
+            (0xF, _, 0, 0xA) =>     Instruction::WaitKey(x),
+            (0xF, _, 1, 5) =>       Instruction::SetDelay(x),
+            (0xF, _, 1, 8) =>       Instruction::SetSound(x),
+            (0xF, _, 1, 0xE) =>     Instruction::AddI(x),
+            (0xF, _, 2, 9) =>       Instruction::Font(x),
+            (0xF, _, 3, 0) =>       Instruction::BigFont(x),
+            (0xF, _, 3, 3) =>       Instruction::Bcd(x),
+            (0xF, _, 3, 0xA) =>     Instruction::Pitch(x),
+            (0xF, _, 5, 5) =>       Instruction::Store(x),
+            (0xF, _, 6, 5) =>       Instruction::Load(x),
+            (0xF, _, 7, 5) =>       Instruction::StoreFlags(x),
+            (0xF, _, 8, 5) =>       Instruction::LoadFlags(x),
+            (_, _, _, _) =>         Instruction::Unknown(op),
+        }
+    }
+
+    // The opcode this instruction decodes from, decode(op).encode() == op for every op
+    pub fn encode(&self) -> u16 {
+        let xy = |prefix: u16, x: u8, y: u8, suffix: u16| prefix << 12 | (x as u16) << 8 | (y as u16) << 4 | suffix;
+        let xnn = |prefix: u16, x: u8, nn: u8| prefix << 12 | (x as u16) << 8 | nn as u16;
+        let fx = |x: u8, nn: u16| 0xF000 | (x as u16) << 8 | nn;
+
+        match *self {
+            Instruction::Nop =>                 0x0000,
+            Instruction::ScrollDown(n) =>       0x00C0 | n as u16,
+            Instruction::ScrollUp(n) =>         0x00D0 | n as u16,
+            Instruction::Cls =>                 0x00E0,
+            Instruction::Ret =>                 0x00EE,
+            Instruction::ScrollRight =>         0x00FB,
+            Instruction::ScrollLeft =>          0x00FC,
+            Instruction::Exit =>                0x00FD,
+            Instruction::Lores =>               0x00FE,
+            Instruction::Hires =>               0x00FF,
+            Instruction::Jump(nnn) =>           0x1000 | nnn,
+            Instruction::Call(nnn) =>           0x2000 | nnn,
+            Instruction::SkipEqByte(x, nn) =>   xnn(3, x, nn),
+            Instruction::SkipNeByte(x, nn) =>   xnn(4, x, nn),
+            Instruction::SkipEqReg(x, y) =>     xy(5, x, y, 0),
+            Instruction::SaveRange(x, y) =>     xy(5, x, y, 2),
+            Instruction::LoadRange(x, y) =>     xy(5, x, y, 3),
+            Instruction::LoadByte(x, nn) =>     xnn(6, x, nn),
+            Instruction::AddByte(x, nn) =>      xnn(7, x, nn),
+            Instruction::LoadReg(x, y) =>       xy(8, x, y, 0),
+            Instruction::Or(x, y) =>            xy(8, x, y, 1),
+            Instruction::And(x, y) =>           xy(8, x, y, 2),
+            Instruction::Xor(x, y) =>           xy(8, x, y, 3),
+            Instruction::AddReg(x, y) =>        xy(8, x, y, 4),
+            Instruction::Sub(x, y) =>           xy(8, x, y, 5),
+            Instruction::Shr(x, y) =>           xy(8, x, y, 6),
+            Instruction::SubN(x, y) =>          xy(8, x, y, 7),
+            Instruction::Shl(x, y) =>           xy(8, x, y, 0xE),
+            Instruction::SkipNeReg(x, y) =>     xy(9, x, y, 0),
+            Instruction::LoadI(nnn) =>          0xA000 | nnn,
+            Instruction::JumpOffset(nnn) =>     0xB000 | nnn,
+            Instruction::Random(x, nn) =>       xnn(0xC, x, nn),
+            Instruction::Draw(x, y, n) =>       xy(0xD, x, y, n as u16),
+            Instruction::SkipKey(x) =>          xnn(0xE, x, 0x9E),
+            Instruction::SkipNotKey(x) =>       xnn(0xE, x, 0xA1),
+            Instruction::LoadILong =>           0xF000,
+            Instruction::Plane(n) =>            fx(n, 0x01),
+            Instruction::Audio =>               0xF002,
+            Instruction::LoadDelay(x) =>        fx(x, 0x07),
+            Instruction::WaitKey(x) =>          fx(x, 0x0A),
+            Instruction::SetDelay(x) =>         fx(x, 0x15),
+            Instruction::SetSound(x) =>         fx(x, 0x18),
+            Instruction::AddI(x) =>             fx(x, 0x1E),
+            Instruction::Font(x) =>             fx(x, 0x29),
+            Instruction::BigFont(x) =>          fx(x, 0x30),
+            Instruction::Bcd(x) =>              fx(x, 0x33),
+            Instruction::Pitch(x) =>            fx(x, 0x3A),
+            Instruction::Store(x) =>            fx(x, 0x55),
+            Instruction::Load(x) =>             fx(x, 0x65),
+            Instruction::StoreFlags(x) =>       fx(x, 0x75),
+            Instruction::LoadFlags(x) =>        fx(x, 0x85),
+            Instruction::Unknown(op) =>         op,
+        }
+    }
+
+    // Bytes taken in memory, F000 nnnn is the only 4 byte instruction
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LoadILong => 4,
+            _ => 2,
+        }
+    }
+
+    // The opcode pattern this instruction belongs to, as written in the comments above: "8xy4", "Dxyn", ...
+    pub fn class(&self) -> &'static str {
+        match self {
+            Instruction::Nop =>                 "0000",
+            Instruction::ScrollDown(_) =>       "00Cn",
+            Instruction::ScrollUp(_) =>         "00Dn",
+            Instruction::Cls =>                 "00E0",
+            Instruction::Ret =>                 "00EE",
+            Instruction::ScrollRight =>         "00FB",
+            Instruction::ScrollLeft =>          "00FC",
+            Instruction::Exit =>                "00FD",
+            Instruction::Lores =>               "00FE",
+            Instruction::Hires =>               "00FF",
+            Instruction::Jump(_) =>             "1nnn",
+            Instruction::Call(_) =>             "2nnn",
+            Instruction::SkipEqByte(_, _) =>    "3xnn",
+            Instruction::SkipNeByte(_, _) =>    "4xnn",
+            Instruction::SkipEqReg(_, _) =>     "5xy0",
+            Instruction::SaveRange(_, _) =>     "5xy2",
+            Instruction::LoadRange(_, _) =>     "5xy3",
+            Instruction::LoadByte(_, _) =>      "6xnn",
+            Instruction::AddByte(_, _) =>       "7xnn",
+            Instruction::LoadReg(_, _) =>       "8xy0",
+            Instruction::Or(_, _) =>            "8xy1",
+            Instruction::And(_, _) =>           "8xy2",
+            Instruction::Xor(_, _) =>           "8xy3",
+            Instruction::AddReg(_, _) =>        "8xy4",
+            Instruction::Sub(_, _) =>           "8xy5",
+            Instruction::Shr(_, _) =>           "8xy6",
+            Instruction::SubN(_, _) =>          "8xy7",
+            Instruction::Shl(_, _) =>           "8xyE",
+            Instruction::SkipNeReg(_, _) =>     "9xy0",
+            Instruction::LoadI(_) =>            "Annn",
+            Instruction::JumpOffset(_) =>       "Bnnn",
+            Instruction::Random(_, _) =>        "Cxnn",
+            Instruction::Draw(_, _, _) =>       "Dxyn",
+            Instruction::SkipKey(_) =>          "Ex9E",
+            Instruction::SkipNotKey(_) =>       "ExA1",
+            Instruction::LoadILong =>           "F000",
+            Instruction::Plane(_) =>            "Fn01",
+            Instruction::Audio =>               "F002",
+            Instruction::LoadDelay(_) =>        "Fx07",
+            Instruction::WaitKey(_) =>          "Fx0A",
+            Instruction::SetDelay(_) =>         "Fx15",
+            Instruction::SetSound(_) =>         "Fx18",
+            Instruction::AddI(_) =>             "Fx1E",
+            Instruction::Font(_) =>             "Fx29",
+            Instruction::BigFont(_) =>          "Fx30",
+            Instruction::Bcd(_) =>              "Fx33",
+            Instruction::Pitch(_) =>            "Fx3A",
+            Instruction::Store(_) =>            "Fx55",
+            Instruction::Load(_) =>             "Fx65",
+            Instruction::StoreFlags(_) =>       "Fx75",
+            Instruction::LoadFlags(_) =>        "Fx85",
+            Instruction::Unknown(_) =>          "????",
+        }
+    }
+
+    // Instructions that only exist in XO-CHIP mode, everywhere else they are unknown opcodes
+    pub fn is_xo_chip(&self) -> bool {
+        matches!(self,
+            Instruction::ScrollUp(_)
+            | Instruction::SaveRange(_, _)
+            | Instruction::LoadRange(_, _)
+            | Instruction::LoadILong
+            | Instruction::Plane(_)
+            | Instruction::Audio
+            | Instruction::Pitch(_))
+    }
+
+    // Skips the instruction after it when its condition holds
+    pub fn is_skip(&self) -> bool {
+        matches!(self,
+            Instruction::SkipEqByte(_, _)
+            | Instruction::SkipNeByte(_, _)
+            | Instruction::SkipEqReg(_, _)
+            | Instruction::SkipNeReg(_, _)
+            | Instruction::SkipKey(_)
+            | Instruction::SkipNotKey(_))
+    }
+
+    fn fmt_cowgod(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Nop =>                 write!(f, "NOP"),
+            Instruction::ScrollDown(n) =>       write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) =>         write!(f, "SCU {}", n),
+            Instruction::Cls =>                 write!(f, "CLS"),
+            Instruction::Ret =>                 write!(f, "RET"),
+            Instruction::ScrollRight =>         write!(f, "SCR"),
+            Instruction::ScrollLeft =>          write!(f, "SCL"),
+            Instruction::Exit =>                write!(f, "EXIT"),
+            Instruction::Lores =>               write!(f, "LOW"),
+            Instruction::Hires =>               write!(f, "HIGH"),
+            Instruction::Jump(nnn) =>           write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) =>           write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SkipEqByte(x, nn) =>   write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipNeByte(x, nn) =>   write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipEqReg(x, y) =>     write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) =>     write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange(x, y) =>     write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LoadByte(x, nn) =>     write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::AddByte(x, nn) =>      write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::LoadReg(x, y) =>       write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) =>            write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) =>           write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) =>           write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg(x, y) =>        write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) =>           write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr(x, y) =>           write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubN(x, y) =>          write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl(x, y) =>           write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg(x, y) =>     write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(nnn) =>          write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JumpOffset(nnn) =>     write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Random(x, nn) =>       write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw(x, y, n) =>       write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey(x) =>          write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey(x) =>       write!(f, "SKNP V{:X}", x),
+            Instruction::LoadILong =>           write!(f, "LD I, nnnn"),     // the address is in the next word, see disassemble()
+            Instruction::Plane(n) =>            write!(f, "PLANE {}", n),
+            Instruction::Audio =>               write!(f, "AUDIO"),
+            Instruction::LoadDelay(x) =>        write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) =>          write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) =>         write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) =>         write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI(x) =>             write!(f, "ADD I, V{:X}", x),
+            Instruction::Font(x) =>             write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont(x) =>          write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd(x) =>              write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch(x) =>            write!(f, "PITCH V{:X}", x),
+            Instruction::Store(x) =>            write!(f, "LD [I], V{:X}", x),
+            Instruction::Load(x) =>             write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags(x) =>       write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) =>        write!(f, "LD V{:X}, R", x),
+            Instruction::Unknown(op) =>         write!(f, "DW 0x{:04X}", op),
+        }
+    }
+
+    // Skips read as the condition under which the next instruction runs, the way Octo's `if ... then` works
+    fn fmt_octo(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Nop =>                 write!(f, "0x00 0x00"),
+            Instruction::ScrollDown(n) =>       write!(f, "scroll-down {}", n),
+            Instruction::ScrollUp(n) =>         write!(f, "scroll-up {}", n),
+            Instruction::Cls =>                 write!(f, "clear"),
+            Instruction::Ret =>                 write!(f, "return"),
+            Instruction::ScrollRight =>         write!(f, "scroll-right"),
+            Instruction::ScrollLeft =>          write!(f, "scroll-left"),
+            Instruction::Exit =>                write!(f, "exit"),
+            Instruction::Lores =>               write!(f, "lores"),
+            Instruction::Hires =>               write!(f, "hires"),
+            Instruction::Jump(nnn) =>           write!(f, "jump 0x{:03x}", nnn),
+            Instruction::Call(nnn) =>           write!(f, ":call 0x{:03x}", nnn),
+            Instruction::SkipEqByte(x, nn) =>   write!(f, "if v{:x} != 0x{:02x} then", x, nn),
+            Instruction::SkipNeByte(x, nn) =>   write!(f, "if v{:x} == 0x{:02x} then", x, nn),
+            Instruction::SkipEqReg(x, y) =>     write!(f, "if v{:x} != v{:x} then", x, y),
+            Instruction::SaveRange(x, y) =>     write!(f, "save v{:x} - v{:x}", x, y),
+            Instruction::LoadRange(x, y) =>     write!(f, "load v{:x} - v{:x}", x, y),
+            Instruction::LoadByte(x, nn) =>     write!(f, "v{:x} := 0x{:02x}", x, nn),
+            Instruction::AddByte(x, nn) =>      write!(f, "v{:x} += 0x{:02x}", x, nn),
+            Instruction::LoadReg(x, y) =>       write!(f, "v{:x} := v{:x}", x, y),
+            Instruction::Or(x, y) =>            write!(f, "v{:x} |= v{:x}", x, y),
+            Instruction::And(x, y) =>           write!(f, "v{:x} &= v{:x}", x, y),
+            Instruction::Xor(x, y) =>           write!(f, "v{:x} ^= v{:x}", x, y),
+            Instruction::AddReg(x, y) =>        write!(f, "v{:x} += v{:x}", x, y),
+            Instruction::Sub(x, y) =>           write!(f, "v{:x} -= v{:x}", x, y),
+            Instruction::Shr(x, y) =>           write!(f, "v{:x} >>= v{:x}", x, y),
+            Instruction::SubN(x, y) =>          write!(f, "v{:x} =- v{:x}", x, y),
+            Instruction::Shl(x, y) =>           write!(f, "v{:x} <<= v{:x}", x, y),
+            Instruction::SkipNeReg(x, y) =>     write!(f, "if v{:x} == v{:x} then", x, y),
+            Instruction::LoadI(nnn) =>          write!(f, "i := 0x{:03x}", nnn),
+            Instruction::JumpOffset(nnn) =>     write!(f, "jump0 0x{:03x}", nnn),
+            Instruction::Random(x, nn) =>       write!(f, "v{:x} := random 0x{:02x}", x, nn),
+            Instruction::Draw(x, y, n) =>       write!(f, "sprite v{:x} v{:x} {}", x, y, n),
+            Instruction::SkipKey(x) =>          write!(f, "if v{:x} -key then", x),
+            Instruction::SkipNotKey(x) =>       write!(f, "if v{:x} key then", x),
+            Instruction::LoadILong =>           write!(f, "i := long"),
+            Instruction::Plane(n) =>            write!(f, "plane {}", n),
+            Instruction::Audio =>               write!(f, "audio"),
+            Instruction::LoadDelay(x) =>        write!(f, "v{:x} := delay", x),
+            Instruction::WaitKey(x) =>          write!(f, "v{:x} := key", x),
+            Instruction::SetDelay(x) =>         write!(f, "delay := v{:x}", x),
+            Instruction::SetSound(x) =>         write!(f, "buzzer := v{:x}", x),
+            Instruction::AddI(x) =>             write!(f, "i += v{:x}", x),
+            Instruction::Font(x) =>             write!(f, "i := hex v{:x}", x),
+            Instruction::BigFont(x) =>          write!(f, "i := bighex v{:x}", x),
+            Instruction::Bcd(x) =>              write!(f, "bcd v{:x}", x),
+            Instruction::Pitch(x) =>            write!(f, "pitch := v{:x}", x),
+            Instruction::Store(x) =>            write!(f, "save v{:x}", x),
+            Instruction::Load(x) =>             write!(f, "load v{:x}", x),
+            Instruction::StoreFlags(x) =>       write!(f, "saveflags v{:x}", x),
+            Instruction::LoadFlags(x) =>        write!(f, "loadflags v{:x}", x),
+            Instruction::Unknown(op) =>         write!(f, "0x{:02x} 0x{:02x}", op >> 8, op & 0xFF),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            self.fmt_octo(f)
+        } else {
+            self.fmt_cowgod(f)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    Cowgod,
+    Octo,
+}
+
+// A listing of a whole ROM loaded at origin, one instruction per line: address, raw bytes, then the instruction.
+// Data mixed in with the code is listed as whatever it happens to decode to, a trailing odd byte as a single byte
+pub fn disassemble(rom: &[u8], origin: u16, syntax: Syntax) -> String {
+    let mut out: String = String::new();
+    let mut offset: usize = 0;
+
+    while offset < rom.len() {
+        let addr: usize = origin as usize + offset;
+
+        if offset + 1 >= rom.len() {
+            out.push_str(&format!("{:04X}: {:02X}         ", addr, rom[offset]));
+            out.push_str(&match syntax {
+                Syntax::Cowgod => format!("DB 0x{:02X}\n", rom[offset]),
+                Syntax::Octo => format!("0x{:02x}\n", rom[offset]),
+            });
+            break;
+        }
+
+        let op: u16 = (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+        let instruction: Instruction = Instruction::decode(op);
+
+        // F000 nnnn carries its address in the next word, list both words on one line
+        let long_addr: Option<u16> = match instruction {
+            Instruction::LoadILong if offset + 3 < rom.len() => Some((rom[offset + 2] as u16) << 8 | rom[offset + 3] as u16),
+            _ => None,
+        };
+
+        let text: String = match syntax {
+            Syntax::Cowgod => format!("{}", instruction),
+            Syntax::Octo => format!("{:#}", instruction),
+        };
+
+        match long_addr {
+            Some(long_addr) => {
+                let long_text: String = match syntax {
+                    Syntax::Cowgod => format!("LD I, 0x{:04X}", long_addr),
+                    Syntax::Octo => format!("{} 0x{:04x}", text, long_addr),
+                };
+
+                out.push_str(&format!("{:04X}: {:04X} {:04X}  {}\n", addr, op, long_addr, long_text));
+                offset += 4;
+            }
+            None => {
+                out.push_str(&format!("{:04X}: {:04X}       {}\n", addr, op, text));
+                offset += 2;
+            }
+        }
+    }
+
+    out
+}
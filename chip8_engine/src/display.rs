@@ -0,0 +1,157 @@
+// The display, packed one bit per pixel so a sprite row is drawn with a single XOR.
+//
+// Every bitplane is an array of 64 rows of u128, wide enough for the 128 pixel hi-res mode. Pixel x of a row
+// is bit 127 - x (MSB first, like sprite data), lo-res only uses the upper 64 bits. Rows past the active
+// resolution are always blank.
+//
+// Every row that changes is marked in a dirty bitmap (bit n for row n) so frontends can redraw only what
+// changed, see dirty_rows() and take_dirty_rows(). Switching resolution marks every row.
+
+use crate::*;
+
+pub(crate) type Row = u128;
+
+const ROW_BITS: usize = Row::BITS as usize;
+const ALL_ROWS: u64 = u64::MAX;
+
+impl Chip8 {
+    // Rows changed since the last take_dirty_rows(), bit n for row n of the active resolution
+    pub fn dirty_rows(&self) -> u64 {
+        self.dirty_rows
+    }
+
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        core::mem::take(&mut self.dirty_rows)
+    }
+
+    // Packed rows of a bitplane in the active resolution, pixel x of a row is bit 127 - x
+    pub fn display_rows(&self, plane: usize) -> &[Row] {
+        let (_, height) = self.resolution();
+
+        &self.screen[plane % PLANES][..height]
+    }
+
+    // 2-bit colour index of a pixel, bit 0 from the first plane and bit 1 from the second. Pixels outside the
+    // active resolution are off
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        let (width, height) = self.resolution();
+        if x >= width || y >= height {
+            return 0;
+        }
+
+        let bit: Row = 1 << (ROW_BITS - 1 - x);
+
+        (0..PLANES)
+            .filter(|plane| self.screen[*plane][y] & bit != 0)
+            .fold(0, |color, plane| color | 1 << plane)
+    }
+
+    // Blank every bitplane, everything has to be redrawn
+    pub(crate) fn clear_display(&mut self) {
+        self.screen = [[0; HIRES_SCREEN_HEIGHT]; PLANES];
+        self.dirty_rows = ALL_ROWS;
+    }
+
+    // Blank the selected bitplanes
+    pub(crate) fn clear_planes(&mut self) {
+        let (_, height) = self.resolution();
+
+        for plane in self.selected_planes() {
+            for y in 0..height {
+                self.set_row(plane, y, 0);
+            }
+        }
+    }
+
+    // XOR one sprite row into a bitplane, `sprite` holds the sprite pixels MSB first starting at bit 127.
+    // Returns true when a pixel was switched off
+    pub(crate) fn draw_row(&mut self, plane: usize, x: usize, y: usize, sprite: Row, clip: bool) -> bool {
+        let (width, _) = self.resolution();
+        let mask: Row = row_mask(width);
+
+        // Pixels past the right edge are either dropped or wrapped around to the left one
+        let mut bits: Row = sprite >> x;
+        if !clip && x > 0 {
+            bits |= sprite << (width - x);
+        }
+        bits &= mask;
+
+        let row: Row = self.screen[plane][y];
+        self.set_row(plane, y, row ^ bits);
+
+        row & bits != 0
+    }
+
+    // Shift every row of the selected bitplanes down by n pixels, rows scrolled in from the top are blank
+    pub(crate) fn scroll_down(&mut self, n: usize) {
+        let (_, height) = self.resolution();
+
+        for plane in self.selected_planes() {
+            for y in (0..height).rev() {
+                let row: Row = if y >= n {self.screen[plane][y - n]} else {0};
+                self.set_row(plane, y, row);
+            }
+        }
+    }
+
+    // Shift every row of the selected bitplanes up by n pixels, rows scrolled in from the bottom are blank
+    pub(crate) fn scroll_up(&mut self, n: usize) {
+        let (_, height) = self.resolution();
+
+        for plane in self.selected_planes() {
+            for y in 0..height {
+                let row: Row = if y + n < height {self.screen[plane][y + n]} else {0};
+                self.set_row(plane, y, row);
+            }
+        }
+    }
+
+    // Shift every row of the selected bitplanes sideways, a positive amount scrolls right and a negative one left
+    pub(crate) fn scroll_horizontal(&mut self, amount: isize) {
+        let (width, height) = self.resolution();
+        let mask: Row = row_mask(width);
+        let shift: usize = amount.unsigned_abs();
+
+        for plane in self.selected_planes() {
+            for y in 0..height {
+                let row: Row = self.screen[plane][y];
+                let row: Row = if amount > 0 {row >> shift} else {row << shift};
+                self.set_row(plane, y, row & mask);
+            }
+        }
+    }
+
+    // Pixel i of a bitplane in the old one byte per pixel layout (x + width * y), used by the save states
+    pub(crate) fn linear_pixel(&self, plane: usize, i: usize) -> u8 {
+        let (width, height) = self.resolution();
+        let (x, y) = (i % width, i / width);
+
+        match y < height {
+            true => (self.screen[plane][y] >> (ROW_BITS - 1 - x)) as u8 & 1,
+            false => 0,
+        }
+    }
+
+    pub(crate) fn set_linear_pixel(&mut self, plane: usize, i: usize, on: bool) {
+        let (width, height) = self.resolution();
+        let (x, y) = (i % width, i / width);
+
+        if y < height {
+            let bit: Row = 1 << (ROW_BITS - 1 - x);
+            let row: Row = if on {self.screen[plane][y] | bit} else {self.screen[plane][y] & !bit};
+            self.set_row(plane, y, row);
+        }
+    }
+
+    fn set_row(&mut self, plane: usize, y: usize, row: Row) {
+        if self.screen[plane][y] != row {
+            self.screen[plane][y] = row;
+            self.dirty_rows |= 1 << y;
+        }
+    }
+}
+
+// The bits of a row that are on screen at this width
+fn row_mask(width: usize) -> Row {
+    Row::MAX << (ROW_BITS - width)
+}
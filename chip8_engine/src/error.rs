@@ -0,0 +1,28 @@
+use core::fmt;
+
+// Everything that can go wrong while loading or running a ROM. The engine never panics on a bad ROM,
+// it hands one of these back to the frontend, which can show it and stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    StackOverflow,                          // 2nnn with all 16 stack slots in use
+    StackUnderflow,                         // 00EE with an empty stack
+    MemoryOutOfBounds { addr: usize },      // fetch or memory access past the end of RAM
+    InvalidKey,                             // Ex9E/ExA1 with a Vx that is not a key (> 0xF)
+    UnknownOpcode { op: u16, pc: u16 },     // pc is the address the opcode was fetched from
+    RomTooLarge,                            // ROM does not fit between 0x200 and the end of RAM
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow"),
+            Chip8Error::MemoryOutOfBounds { addr } => write!(f, "memory access out of bounds at 0x{:04X}", addr),
+            Chip8Error::InvalidKey => write!(f, "invalid key"),
+            Chip8Error::UnknownOpcode { op, pc } => write!(f, "unknown opcode 0x{:04X} at 0x{:04X}", op, pc),
+            Chip8Error::RomTooLarge => write!(f, "ROM is too large to fit in memory"),
+        }
+    }
+}
+
+impl core::error::Error for Chip8Error {}
@@ -1,16 +1,80 @@
+// The engine is no_std, it only needs an allocator. The default std feature adds what needs an operating
+// system: seeding Cxnn from the OS random source in new() and TraceWriter. Without it new() uses a fixed seed,
+// call set_seed() with something that changes (a timer, an ADC reading) to get different numbers per run
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
 use rand::Rng;
 
+mod audio;
+mod cache;
+mod debugger;
+mod disasm;
+mod display;
+mod error;
+mod log;
+mod prng;
+mod profiler;
+mod quirks;
+mod recompiler;
+mod rewind;
+mod scheduler;
+mod script;
+mod state;
+mod trace;
+
+pub use audio::{Beeper, Waveform};
+pub use debugger::{Access, Debugger, Stop};
+pub use disasm::{disassemble, Instruction, Syntax};
+pub use error::Chip8Error;
+pub use log::Logger;
+pub use profiler::{AddressProfile, ProfileFormat, Profiler, SubroutineProfile};
+pub use quirks::{LoadStoreIncrement, Quirks};
+pub use rewind::Rewind;
+pub use scheduler::{FrameResult, DEFAULT_SPEED, TIMER_HZ};
+pub use script::{scripted_keypad, KeyPress};
+pub use state::{crc32, StateError};
+pub use trace::{CpuState, TraceBuffer, TraceEntry, TraceFormat, TraceSink};
+#[cfg(feature = "std")]
+pub use trace::TraceWriter;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use display::Row;
+use prng::Prng;
+use recompiler::Block;
+
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
-const VERBOSE: bool = false;
+// SUPER-CHIP hi-res mode, toggled with 00FF/00FE
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
 
 const RAM_SIZE: usize = 4096;
+const XO_RAM_SIZE: usize = 65536;   // XO-CHIP address space, I can be loaded with a full 16-bit address
 const V_REG_SIZE: usize = 16;
 const STACK_REG_SIZE: usize = 16;
 const KEYPAD_SIZE: usize = 16;
+const RPL_SIZE: usize = 16;       // SUPER-CHIP has 8 flags, XO-CHIP extends them to 16
+const PLANES: usize = 2;          // XO-CHIP bitplanes, classic programs only ever draw to the first one
+const SCREEN_BUFFER_SIZE: usize = HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT;    // pixels in the largest display
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;     // pitch 64 plays the audio pattern at 4000 bits per second
 
 const START_ADDRESS: u16 = 0x200;
+#[cfg(not(feature = "std"))]
+const DEFAULT_SEED: u64 = 0x0C8_5EED;  // without std there is no random source to seed from
 
 const FONTSET_SIZE: usize = 80;
 
@@ -33,9 +97,50 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+// SUPER-CHIP 8x10 font used by Fx30, stored right after the small font.
+// SCHIP 1.1 only has the digits, A - F are the ones Octo uses
+const BIG_FONTSET_ADDRESS: usize = FONTSET_SIZE;
+const BIG_FONTSET_SIZE: usize = 160;
+
+const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
+// A copy of the first bitplane in the active resolution, one byte per pixel (row-major, 1 = on)
+pub struct Screen {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+// How the program is run. All of them run programs the same way, see cache.rs and recompiler.rs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Core {
+    #[default]
+    Interpreter,    // reads and decodes the opcode on every tick
+    Cached,         // decodes every address once and keeps the instruction until its bytes are written
+    Recompiler,     // translates basic blocks into closures, run_frame() runs a block at a time
+}
+
 pub struct Chip8{
     pc: u16,                        // program counter, 12 bytes
-    memory: [u8; RAM_SIZE],         // memory, 4kB/4096 bytes large
+    memory: Vec<u8>,                // memory, 4kB/4096 bytes large, grown to 64kB when XO-CHIP mode is first turned on.
+                                    // On the heap so a Chip8 stays small enough to build on a microcontroller's stack
     v_reg: [u8; V_REG_SIZE],        // V-Register, 8 bits
     index_reg: u16,                 // index register, 12 bytes
     stack: [u16; STACK_REG_SIZE],   // stack, 16 bytes (we could probs convert this vecdeque instead TODO)
@@ -44,20 +149,69 @@ pub struct Chip8{
     sound_timer: u8,                // sound timer, 8 bits
     delay_timer: u8,                // delay timer, 8 bits
 
-    screen: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],    // 1-bit screen or B&W
+    screen: [[Row; HIRES_SCREEN_HEIGHT]; PLANES], // packed rows per bitplane, see display.rs
+    hires: bool,                    // SUPER-CHIP 128x64 mode
+    dirty_rows: u64,                // bit n set when row n changed, cleared by take_dirty_rows()
+    plane_mask: u8,                 // XO-CHIP bitplanes affected by drawing, clearing and scrolling (Fn01)
+
+    rpl: [u8; RPL_SIZE],            // SUPER-CHIP RPL user flags, Fx75/Fx85
+    halted: bool,                   // set by 00FD, the interpreter stops fetching
+
+    xo_chip: bool,                  // XO-CHIP mode: 64kB memory and the XO-CHIP opcodes
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],  // XO-CHIP 128-bit audio pattern buffer (F002)
+    pitch: u8,                      // XO-CHIP audio pattern playback rate (Fx3A)
+
+    keypad: [bool; KEYPAD_SIZE],    // keypad, 16 keys (0 - 9, A - F)
+
+    rng: Prng,                      // random number source for Cxnn, seeded at construction
+
+    quirks: Quirks,                 // interpretation of the ambiguous opcodes, see quirks.rs
+    vblank: bool,                   // set on every timers() call, used by the display wait quirk
 
-    keypad: [bool; KEYPAD_SIZE]     // keypad, 16 keys (0 - 9, A - F)
+    speed: u32,                     // instructions per second, see scheduler.rs
+    cycle_remainder: u32,           // fraction of an instruction carried to the next frame, in 1/60ths
+    time_debt: u128,                // real time not yet emulated, in nanoseconds times 60
+
+    cycles: u64,                    // instructions executed since the last reset
+    tracer: Option<Box<dyn TraceSink>>, // gets every executed instruction when set, see trace.rs
+    profiler: Option<Profiler>,     // counts every executed instruction when set, see profiler.rs
+    logger: Option<Logger>,         // gets the diagnostics when set, see log.rs
+
+    core: Core,                     // see cache.rs
+    decoded: Vec<Option<Instruction>>, // instruction decoded from each address, only filled by Core::Cached
+    blocks: Vec<Option<Rc<Block>>>, // block compiled at each address, only filled by Core::Recompiler
+    block_cover: Vec<bool>,         // bytes that are part of a compiled block
 
 }
 
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Chip8 {
     pub fn new() -> Self{
-        let mut ram: [u8; 4096] = [0u8; RAM_SIZE];
-        ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        Self::with_quirks(Quirks::default())
+    }
+
+    // Deterministic machine: two instances with the same seed produce the same Cxnn numbers
+    pub fn with_seed(seed: u64) -> Self{
+        let mut chip8: Chip8 = Self::new();
+        chip8.set_seed(seed);
+
+        chip8
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Self{
+        #[cfg(feature = "std")]
+        let seed: u64 = rand::thread_rng().r#gen();    // r#gen instead of gen since rust has a keyword gen https://doc.rust-lang.org/edition-guide/rust-2024/gen-keyword.html
+        #[cfg(not(feature = "std"))]
+        let seed: u64 = DEFAULT_SEED;
 
         Self {
             pc: START_ADDRESS, 
-            memory: ram, 
+            memory: Self::init_memory(RAM_SIZE), 
             v_reg: [0; V_REG_SIZE], 
             index_reg: 0, 
             stack: [0; STACK_REG_SIZE],
@@ -66,15 +220,45 @@ impl Chip8 {
             sound_timer: 0,
             delay_timer: 0, 
             
-            screen: [0; SCREEN_WIDTH * SCREEN_HEIGHT], 
+            screen: [[0; HIRES_SCREEN_HEIGHT]; PLANES],
+            hires: false,
+            dirty_rows: u64::MAX,
+            plane_mask: 1,
+
+            rpl: [0; RPL_SIZE],
+            halted: false,
+
+            xo_chip: false,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
             
-            keypad: [false; KEYPAD_SIZE]
+            keypad: [false; KEYPAD_SIZE],
+
+            rng: Prng::new(seed),
+
+            quirks,
+            vblank: false,
+
+            speed: DEFAULT_SPEED,
+            cycle_remainder: 0,
+            time_debt: 0,
+
+            cycles: 0,
+            tracer: None,
+            profiler: None,
+            logger: None,
+
+            core: Core::Interpreter,
+            decoded: Vec::new(),
+            blocks: Vec::new(),
+            block_cover: Vec::new()
         }
     }
 
     pub fn reset(&mut self) {
             self.pc = START_ADDRESS;
-            self.memory = [0; RAM_SIZE]; 
+            self.memory = Self::init_memory(self.memory_size()); 
+            self.invalidate_all();
             self.v_reg = [0; V_REG_SIZE];
             self.index_reg = 0;
             self.stack = [0; STACK_REG_SIZE];
@@ -83,238 +267,538 @@ impl Chip8 {
             self.sound_timer = 0;
             self.delay_timer = 0; 
             
-            self.screen = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+            self.hires = false;
+            self.clear_display();
+            self.plane_mask = 1;
+
+            self.rpl = [0; RPL_SIZE];
+            self.halted = false;
+
+            self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+            self.pitch = DEFAULT_PITCH;
             
             self.keypad = [false; KEYPAD_SIZE];
+
+            self.vblank = false;
+
+            self.cycle_remainder = 0;
+            self.time_debt = 0;
+
+            self.cycles = 0;
     } 
 
-    pub fn get_display(&self) -> &[u8] {
-        &self.screen
+    // Fresh RAM with both fonts in place
+    fn init_memory(size: usize) -> Vec<u8> {
+        let mut ram: Vec<u8> = vec![0; size];
+        ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        ram[BIG_FONTSET_ADDRESS..BIG_FONTSET_ADDRESS + BIG_FONTSET_SIZE].copy_from_slice(&BIG_FONTSET);
+
+        ram
+    }
+
+    // Restart the random number source from seed, reset() leaves it running
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Prng::new(seed)
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks
+    }
+
+    // XO-CHIP mode is kept across reset(), like the quirks
+    pub fn is_xo_chip(&self) -> bool {
+        self.xo_chip
+    }
+
+    pub fn set_xo_chip(&mut self, enabled: bool) {
+        self.xo_chip = enabled;
+        if self.memory.len() < self.memory_size() {
+            self.memory.resize(self.memory_size(), 0);
+        }
+        self.invalidate_all();
+    }
+
+    // Addressable memory in the current mode
+    pub fn memory_size(&self) -> usize {
+        if self.xo_chip {XO_RAM_SIZE} else {RAM_SIZE}
+    }
+
+    pub fn get_display(&self) -> Screen {
+        let (width, height) = self.resolution();
+        let pixels: Vec<u8> = (0..width * height).map(|i| self.linear_pixel(0, i)).collect();
+
+        Screen { width, height, pixels }
     }
 
+    // Every bitplane combined into 2-bit colour indices, bit 0 from the first plane and bit 1 from the second.
+    // Uses the same row-major layout as get_display(), outside XO-CHIP this only ever holds 0 and 1
+    pub fn get_color_display(&self) -> Vec<u8> {
+        let (width, height) = self.resolution();
+
+        (0..width * height)
+            .map(|i| self.pixel(i % width, i / width))
+            .collect()
+    }
+
+    // XO-CHIP audio pattern buffer and pitch register, for the frontends to play while the sound timer runs
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    // The buzzer sounds while the sound timer is above zero
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    // Active resolution as (width, height)
+    pub fn resolution(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    // True once the ROM has executed 00FD
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // Registers and memory, for debuggers
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc
+    }
+
+    pub fn registers(&self) -> &[u8; V_REG_SIZE] {
+        &self.v_reg
+    }
+
+    // V0 - VF, anything past VF is ignored
+    pub fn set_register(&mut self, reg: usize, val: u8) {
+        if let Some(v) = self.v_reg.get_mut(reg) {
+            *v = val
+        }
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_reg
+    }
+
+    pub fn set_index_register(&mut self, val: u16) {
+        self.index_reg = val
+    }
+
+    // Return addresses currently on the stack, oldest first
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn set_delay_timer(&mut self, val: u8) {
+        self.delay_timer = val
+    }
+
+    pub fn set_sound_timer(&mut self, val: u8) {
+        self.sound_timer = val
+    }
+
+    // Addressable memory in the current mode
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size()]
+    }
+
+    // The opcode at addr without fetching it, 0 past the end of memory
+    pub fn opcode_at(&self, addr: u16) -> u16 {
+        let memory: &[u8] = self.memory();
+
+        match (memory.get(addr as usize), memory.get(addr as usize + 1)) {
+            (Some(high), Some(low)) => (*high as u16) << 8 | *low as u16,
+            _ => 0,
+        }
+    }
+
+    // The instruction at PC jumps to itself, the usual way for a CHIP-8 program to end
+    pub fn is_spinning(&self) -> bool {
+        self.pc <= 0x0FFF && self.opcode_at(self.pc) == 0x1000 | self.pc
+    }
+
+    pub fn set_memory(&mut self, addr: usize, val: u8) -> Result<(), Chip8Error> {
+        self.write_memory(addr, val)
+    }
+
+    // Keys 0 - F, anything past F is ignored
     pub fn set_keypad(&mut self, idx: usize, key_down: bool){
-        self.keypad[idx] = key_down
+        if let Some(key) = self.keypad.get_mut(idx) {
+            *key = key_down
+        }
     }
 
-    pub fn load_rom(&mut self, rom_data: &[u8]){
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), Chip8Error>{
         let start_addr = START_ADDRESS as usize;
         let end_addr = start_addr + rom_data.len();
 
+        if end_addr > self.memory_size() {
+            return Err(Chip8Error::RomTooLarge);
+        }
+
         self.memory[start_addr..end_addr].copy_from_slice(rom_data);
+        self.invalidate_all();
+
+        Ok(())
     }
 
-    fn push(&mut self, val: u16){
+    fn push(&mut self, val: u16) -> Result<(), Chip8Error>{
+        if self.stack_pointer as usize >= STACK_REG_SIZE {
+            return Err(Chip8Error::StackOverflow);
+        }
+
         self.stack[self.stack_pointer as usize] = val;
         self.stack_pointer += 1;
+
+        Ok(())
     }
 
-    fn pop(&mut self) -> u16{
+    fn pop(&mut self) -> Result<u16, Chip8Error>{
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
+
         self.stack_pointer -= 1;
-        self.stack[self.stack_pointer as usize]
+        Ok(self.stack[self.stack_pointer as usize])
     }
 
-    pub fn tick(&mut self){
-        // FETCH
-        let op: u16 = self.fetch();
+    // Every memory access of the ROM goes through these two, so addresses past the end of RAM
+    // (4kB, or 64kB in XO-CHIP mode) become an error instead of a panic
+    fn read_memory(&self, addr: usize) -> Result<u8, Chip8Error>{
+        if addr >= self.memory_size() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr });
+        }
+
+        Ok(self.memory[addr])
+    }
+
+    fn write_memory(&mut self, addr: usize, val: u8) -> Result<(), Chip8Error>{
+        if addr >= self.memory_size() {
+            return Err(Chip8Error::MemoryOutOfBounds { addr });
+        }
+
+        self.memory[addr] = val;
+        self.invalidate(addr);
 
-        // DECODE & EXECUTE
-        self.execute(op);
-        
+        Ok(())
     }
 
-    pub fn fetch(&mut self) -> u16{
+    pub fn tick(&mut self) -> Result<(), Chip8Error>{
+        if self.halted {
+            return Ok(());
+        }
+
+        // FETCH & DECODE
+        let pc: u16 = self.pc;
+        let instruction: Instruction = match self.core {
+            Core::Interpreter | Core::Recompiler => Instruction::decode(self.fetch()?),
+            Core::Cached => self.fetch_cached()?,
+        };
+
+        // EXECUTE
+        let before: Option<CpuState> = self.tracer.as_ref().map(|_| self.cpu_state());
+        self.execute_instruction(instruction)?;
+
+        if let Some(before) = before {
+            self.trace(pc, instruction.encode(), before);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, instruction.encode());
+        }
+        self.cycles += 1;
+
+        Ok(())
+    }
+
+    pub fn fetch(&mut self) -> Result<u16, Chip8Error>{
         // chip8 stores opcodes in Big-Endian, hence higher bytes are stored in lower memory
-        let higher_byte: u16 = self.memory[self.pc as usize] as u16;         // Fetch the higher byte
-        let lower_byte: u16 = self.memory[(self.pc + 1) as usize] as u16;    // Fetch the lower byte
+        let higher_byte: u16 = self.read_memory(self.pc as usize)? as u16;      // Fetch the higher byte
+        let lower_byte: u16 = self.read_memory(self.pc as usize + 1)? as u16;   // Fetch the lower byte
 
         let op: u16 = higher_byte << 8 | lower_byte;     // Combine the bytes into a 16-bit value
 
-        self.pc += 2;   // increment the program counter by 2 since opcodes are 16-bits and memory are only 8-bits
+        self.pc = self.pc.wrapping_add(2);  // increment the program counter by 2 since opcodes are 16-bits and memory are only 8-bits
 
-        return op;
+        Ok(op)
     }
 
     pub fn timers(&mut self){
+        self.vblank = true;
+
         if self.delay_timer > 0{
             self.delay_timer -= 1;
         }
 
+        // The frontends play the buzzer for as long as sound_active() is true, see audio.rs
         if self.sound_timer > 0{
-            if self.sound_timer == 1{
-                // PLAY BEEP
-                // TODO
-            }
             self.sound_timer -= 1;
         }
     }
 
-    pub fn execute(&mut self, op: u16){
+    pub fn execute(&mut self, op: u16) -> Result<(), Chip8Error>{
         // DECODE
-        let nibbles: (u8, u8, u8, u8) = (
-            ((op & 0xF000) >> 12) as u8,    // extract the 1st nibble
-            ((op & 0x0F00) >> 8) as u8,     // extract the 2nd nibble
-            ((op & 0x00F0) >> 4) as u8,     // extract the 3rd nibble
-            (op & 0x000F) as u8           // extract the 4th nibble
-        );
-
-        let x: usize = nibbles.1 as usize;
-        let y: usize = nibbles.2 as usize;
-        let n: usize = nibbles.3 as usize;
-        let nn: u8 = (op & 0x00FF) as u8;
-        let nnn: usize = (op & 0x0FFF) as usize;
-
-        match(nibbles.0, nibbles.1, nibbles.2, nibbles.3){
+        let instruction: Instruction = Instruction::decode(op);
+
+        self.execute_instruction(instruction)
+    }
+
+    // Everything after the decoding, the cached core (cache.rs) comes in here with instructions it decoded earlier
+    pub(crate) fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Chip8Error>{
+        // The XO-CHIP instructions are unknown opcodes on every other platform
+        if instruction.is_xo_chip() && !self.xo_chip {
+            return Err(Chip8Error::UnknownOpcode { op: instruction.encode(), pc: self.pc.wrapping_sub(2) });
+        }
+
+        // EXECUTE
+        match instruction {
             // NOP (0000): DO NOTHING
-            (0, 0, 0, 0) => {
-                return;
+            Instruction::Nop => {}
+
+            // SCD nibble (00Cn): SCROLL the screen down by n pixels (SUPER-CHIP)
+            Instruction::ScrollDown(n) => {
+                self.scroll_down(n as usize);
+            }
+
+            // SCU nibble (00Dn): SCROLL the screen up by n pixels (XO-CHIP)
+            Instruction::ScrollUp(n) => {
+                self.scroll_up(n as usize);
             }
 
-            // CLS (00e0): CLEAR SCREEN
-            (0, 0, 0xE, 0) => {
-                self.screen = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+            // CLS (00e0): CLEAR SCREEN, only the selected bitplanes in XO-CHIP
+            Instruction::Cls => {
+                self.clear_planes();
             }
 
             // RET (00ee): RETURN from Subroutine
-            (0, 0, 0xE, 0xE) => {
-                let return_add: u16 = self.pop();
+            Instruction::Ret => {
+                let return_add: u16 = self.pop()?;
                 self.pc = return_add;
             }
 
+            // SCR (00FB): SCROLL the screen right by 4 pixels (SUPER-CHIP)
+            Instruction::ScrollRight => {
+                self.scroll_horizontal(4);
+            }
+
+            // SCL (00FC): SCROLL the screen left by 4 pixels (SUPER-CHIP)
+            Instruction::ScrollLeft => {
+                self.scroll_horizontal(-4);
+            }
+
+            // EXIT (00FD): EXIT the interpreter (SUPER-CHIP)
+            Instruction::Exit => {
+                self.halted = true;
+                self.pc = self.pc.wrapping_sub(2);   // keep pointing at 00FD so the state shows where the program stopped
+            }
+
+            // LOW (00FE): SWITCH to 64x32 lo-res mode and clear the screen (SUPER-CHIP)
+            Instruction::Lores => {
+                self.hires = false;
+                self.clear_display();
+            }
+
+            // HIGH (00FF): SWITCH to 128x64 hi-res mode and clear the screen (SUPER-CHIP)
+            Instruction::Hires => {
+                self.hires = true;
+                self.clear_display();
+            }
+
             // JMP NNN (1nnn): JUMP to address nnn
-            (1, _, _, _) => {
-                self.pc = nnn as u16;
+            Instruction::Jump(nnn) => {
+                self.pc = nnn;
             }
 
             // CALL NNN (2nnn): CALL Subroutine, similar to JMP but needs to remember where it came from
-            (2, _, _, _) => {
-                self.push(self.pc);
-                self.pc = nnn as u16
+            Instruction::Call(nnn) => {
+                self.push(self.pc)?;
+                self.pc = nnn
             }
 
             // SE Vx, byte (3xnn): SKIP IF VX == NN
-            (3, _, _, _) => {
-                if self.v_reg[x] == nn {
-                    self.pc += 2;
+            Instruction::SkipEqByte(x, nn) => {
+                if self.v_reg[x as usize] == nn {
+                    self.skip_next();
                 }
             }
 
             // SNE VX, byte (4xnn): SKIP IF VX != NN
-            (4, _, _, _) => {
-                if self.v_reg[x] != nn {
-                    self.pc += 2;
+            Instruction::SkipNeByte(x, nn) => {
+                if self.v_reg[x as usize] != nn {
+                    self.skip_next();
                 }
             }   
             
             // SE Vx, Vy (5xy0): SKIP IF VX == VY
-            (5, _, _, 0) => {
-                if self.v_reg[x] == self.v_reg[y] {
-                    self.pc += 2;
+            Instruction::SkipEqReg(x, y) => {
+                if self.v_reg[x as usize] == self.v_reg[y as usize] {
+                    self.skip_next();
+                }
+            }
+
+            // SAVE Vx - Vy (5xy2): STORE Vx to Vy in memory starting from address I, in either order, I is left untouched (XO-CHIP)
+            Instruction::SaveRange(x, y) => {
+                let start_idx = self.index_reg as usize;
+
+                for (offset, reg) in Self::register_range(x as usize, y as usize).enumerate() {
+                    self.write_memory(start_idx + offset, self.v_reg[reg])?;
+                }
+            }
+
+            // LOAD Vx - Vy (5xy3): LOAD Vx to Vy from memory starting from address I, in either order, I is left untouched (XO-CHIP)
+            Instruction::LoadRange(x, y) => {
+                let start_idx = self.index_reg as usize;
+
+                for (offset, reg) in Self::register_range(x as usize, y as usize).enumerate() {
+                    self.v_reg[reg] = self.read_memory(start_idx + offset)?;
                 }
             }
 
             // LD Vx, byte (6xnn): LOAD Vx = nn
-            (6, _, _, _) => {
-                self.v_reg[x] = nn
+            Instruction::LoadByte(x, nn) => {
+                self.v_reg[x as usize] = nn
             }
 
             // ADD Vx, byte (7xnn): ADD Vx = Vx + nn
-            (7, _, _, _) => {
+            Instruction::AddByte(x, nn) => {
 
-                // self.v_reg[x] += nn;                                                 // panic on overflow
-                // self.v_reg[x] = self.v_reg[x].wrapping_add(nn);                      // silent wrap around on overflow
-                let (sum, overflow) = self.v_reg[x].overflowing_add(nn);      // wrap around on overflow and notifies if it happens
+                // self.v_reg[x as usize] += nn;                                                 // panic on overflow
+                let (sum, overflow) = self.v_reg[x as usize].overflowing_add(nn);      // wrap around on overflow and tell the logger (log.rs)
 
-                if overflow && VERBOSE{
-                    println!("OVERFLOW: opcode 7xnn, x={}, nn={}", x, nn);
+                if overflow {
+                    self.log(format_args!("OVERFLOW: opcode 7xnn, x={}, nn={}", x, nn));
                 }
 
-                self.v_reg[x] = sum
-                
+                self.v_reg[x as usize] = sum;
             }
 
             // LD Vx, Vy (8xy0): LOAD Vx = Vy
-            (8, _, _, 0) => {
-                self.v_reg[x] = self.v_reg[y]
+            Instruction::LoadReg(x, y) => {
+                self.v_reg[x as usize] = self.v_reg[y as usize]
             }
 
             // OR Vx, Vy (8xy1): Vx = Vx | Vy
-            (8, _, _, 1) => {
-                self.v_reg[x] |= self.v_reg[y];
+            Instruction::Or(x, y) => {
+                self.v_reg[x as usize] |= self.v_reg[y as usize];
+
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xF] = 0;
+                }
             }
 
             // AND Vx, Vy (8xy2): Vx = Vx & Vy
-            (8, _, _, 2) => {
-                self.v_reg[x] &= self.v_reg[y];
+            Instruction::And(x, y) => {
+                self.v_reg[x as usize] &= self.v_reg[y as usize];
+
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xF] = 0;
+                }
             }
 
             // XOR Vx, Vy (8xy3): Vx = Vx ^ Vy
-            (8, _, _, 3) => {
-                self.v_reg[x] ^= self.v_reg[y];
+            Instruction::Xor(x, y) => {
+                self.v_reg[x as usize] ^= self.v_reg[y as usize];
+
+                if self.quirks.logic_resets_vf {
+                    self.v_reg[0xF] = 0;
+                }
             }
 
             // ADD Vx, Vy (8xy4): Vx = Vx + Vy. Set VF for carry
-            (8, _, _, 4) => {
-                let (sum, carry) = self.v_reg[x].overflowing_add(self.v_reg[y]);
+            Instruction::AddReg(x, y) => {
+                let (sum, carry) = self.v_reg[x as usize].overflowing_add(self.v_reg[y as usize]);
 
-                self.v_reg[0xF] = if carry {1} else {0};
-
-                self.v_reg[x] = sum
+                self.v_reg[x as usize] = sum;
+                self.v_reg[0xF] = if carry {1} else {0};   // SET VF last so that VF as the destination ends up with the flag
             }
 
             // SUB Vx, Vy (8xy5): Vx = Vx - Vy, SET VF for borrow
-            (8, _, _, 5) => {
-                let (diff, borrow) = self.v_reg[x].overflowing_sub(self.v_reg[y]);
-
-                self.v_reg[0xF] = if borrow {0} else {1};   // Note that SET VF = NOT borrow
+            Instruction::Sub(x, y) => {
+                let (diff, borrow) = self.v_reg[x as usize].overflowing_sub(self.v_reg[y as usize]);
 
-                self.v_reg[x] = diff
+                self.v_reg[x as usize] = diff;
+                self.v_reg[0xF] = if borrow {0} else {1};   // Note that SET VF = NOT borrow, last like the shifts
             }
 
             // Vx SHR 1 (8xy6): SET VF for Vx's least significant bit, then SET Vx = Vx >> 1 (basically Vx / 2), 
-            (8, _, _, 6) => {
-                self.v_reg[0xF] = self.v_reg[x] & 0x1;
+            Instruction::Shr(x, y) => {
+                if self.quirks.shift_uses_vy {
+                    self.v_reg[x as usize] = self.v_reg[y as usize];
+                }
+
+                let lsb: u8 = self.v_reg[x as usize] & 0x1;
 
-                self.v_reg[x] >>= 1
+                self.v_reg[x as usize] >>= 1;
+                self.v_reg[0xF] = lsb    // SET VF last so that VF as the destination ends up with the flag
             }
 
             // SUBN Vx, Vy (8xy7): Vx = Vy - Vx, SET VF for borrow
-            (8, _, _, 7) => {
-                let (diff, borrow) = self.v_reg[y].overflowing_sub(self.v_reg[x]);
+            Instruction::SubN(x, y) => {
+                let (diff, borrow) = self.v_reg[y as usize].overflowing_sub(self.v_reg[x as usize]);
 
-                self.v_reg[0xF] = if borrow {0} else {1};   // Note that SET VF = NOT borrow
-
-                self.v_reg[x] = diff
+                self.v_reg[x as usize] = diff;
+                self.v_reg[0xF] = if borrow {0} else {1};   // Note that SET VF = NOT borrow, last like the shifts
             }
 
             // Vx SHL 1 (8xyE): SET VF = Vx's most significant bit, then SET Vx = Vx << 1 (basically Vx * 2)
-            (8, _, _, 0xE) => {
-                self.v_reg[0xF] = (self.v_reg[x] & 0x80) >> 7;
+            Instruction::Shl(x, y) => {
+                if self.quirks.shift_uses_vy {
+                    self.v_reg[x as usize] = self.v_reg[y as usize];
+                }
+
+                let msb: u8 = (self.v_reg[x as usize] & 0x80) >> 7;
 
-                self.v_reg[x] <<= 1
+                self.v_reg[x as usize] <<= 1;
+                self.v_reg[0xF] = msb
             }
             
             // SNE Vx, Vy (9xy0): SKIP NEXT instruction Vx != Vy
-            (9, _, _, 0) => {
-                if self.v_reg[x] != self.v_reg[y] {
-                    self.pc += 2;
+            Instruction::SkipNeReg(x, y) => {
+                if self.v_reg[x as usize] != self.v_reg[y as usize] {
+                    self.skip_next();
                 }
             }
 
             // LD I, addr (Annn): LOAD I (index register) = nnn
-            (0xA, _, _, _) => {
-                self.index_reg = nnn as u16
+            Instruction::LoadI(nnn) => {
+                self.index_reg = nnn
             }
 
-            // JP V0, addr (Bnnn): JUMP to addr + V0
-            (0xB, _, _, _) => {
-                self.pc = self.v_reg[0] as u16 + nnn as u16;
+            // JP V0, addr (Bnnn): JUMP to addr + V0, or addr + Vx with the jump quirk (Bxnn)
+            Instruction::JumpOffset(nnn) => {
+                let offset_reg: usize = if self.quirks.jump_uses_vx {(nnn >> 8) as usize} else {0};
+
+                self.pc = self.v_reg[offset_reg] as u16 + nnn;
             }
 
             // RND Vx, byte (Cxnn): SET Vx = random byte AND nnn
-            (0xC, _, _, _) => {
-                let rng: u8 = rand::thread_rng().r#gen();   // r#gen instead of gen since rust has a keyword gen https://doc.rust-lang.org/edition-guide/rust-2024/gen-keyword.html
+            Instruction::Random(x, nn) => {
+                let rng: u8 = self.rng.next_u8();
 
-                self.v_reg[x] = rng & nn;
+                self.v_reg[x as usize] = rng & nn;
             }
 
             // DRW Vx, Vy, nibble (Dxyn): DRAW n-byte sprite starting at I (Vx, Vy), set VF = collision
@@ -325,135 +809,258 @@ impl Chip8 {
             // This pretty much guarantees that every sprite could be n pixels high and 8 pixels wide.
             // The sprites or the pixels are drawn using XOR operation
             // If this XOR causes the pixel to flip, we will set the Vf = 1
-            (0xD, _, _, _) => {
+            Instruction::Draw(x, y, n) => {
                 // x = x coordinate, y = y coordinate, n = sprite height
 
+                // With the display wait quirk, sprites are only drawn right after a vblank. Until then we keep
+                // executing this same instruction, just like Fx0A does while waiting for a key
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        self.pc = self.pc.wrapping_sub(2);
+                        return Ok(());
+                    }
+                    self.vblank = false;
+                }
+
                 self.v_reg[0xF] = 0;    // Reset every call to avoid issues if Vf is set in previous calls
+
+                let (width, height) = self.resolution();
+
+                // SUPER-CHIP: Dxy0 draws a 16x16 sprite, every row is 2 bytes wide
+                let (sprite_width, sprite_height): (usize, usize) = if n == 0 {(16, 16)} else {(8, n as usize)};
+                let row_bytes: usize = sprite_width / 8;
+
+                // The starting coordinates always wrap around the screen
+                let start_x: usize = self.v_reg[x as usize] as usize % width;
+                let start_y: usize = self.v_reg[y as usize] as usize % height;
                 
-                // We iterate per byte
-                for row in 0..n {
-                    
-                    let addr: u16 = self.index_reg + row as u16;        // Get the address of sprite rows (I, I+1, I+2, ...)
-                    let pixel_data: u8 = self.memory[addr as usize];    // Then find it in the RAM
-
-                    let y: usize = (self.v_reg[y] as usize + row) % SCREEN_HEIGHT;  // Find the y coodinate of the sprite, use modulo to wrap around the screen
-                    
-                    // Now we iterate per bit from MSB to LSB
-                    for column in 0..8{
-                        
-                        let x: usize = (self.v_reg[x] as usize + column) % SCREEN_WIDTH;    // Find the x coordinate of the sprite, use modulo to wrap around the screen
-
-                        let sprite_pixel: u8 = (pixel_data >> (7 - column as u8)) & 1;      // Extract each bit and check then flip if value is 1, // We can honestly use if else here, but using AND operation is just the same
-
-                        let screen_idx: usize = x + (SCREEN_WIDTH * y);                     // Flip the Vf flag if there is a collision (or if the sprite pixel erased the current pixel)
-                        self.v_reg[0xF] |= sprite_pixel & self.screen[screen_idx];
-                        
-                        
-                        self.screen[screen_idx] ^= sprite_pixel                             // Flip the pixel on the screen
+                // XO-CHIP: every selected bitplane gets its own sprite, stored one after the other starting at I
+                let sprite_size: usize = row_bytes * sprite_height;
+
+                for (plane_idx, plane) in self.selected_planes().enumerate() {
+                    let sprite_addr: usize = self.index_reg as usize + plane_idx * sprite_size;
+
+                    // We iterate per row
+                    for row in 0..sprite_height {
+
+                        // With the clipping quirk, rows past the bottom edge are not drawn
+                        if self.quirks.clip_sprites && start_y + row >= height {
+                            break;
+                        }
+
+                        // Get the address of sprite rows (I, I+1, I+2, ... or I, I+2, I+4, ... for 16x16), then find it in the RAM
+                        let addr: usize = sprite_addr + row * row_bytes;
+                        let pixel_data: u16 = if row_bytes == 2 {
+                            (self.read_memory(addr)? as u16) << 8 | self.read_memory(addr + 1)? as u16
+                        } else {
+                            (self.read_memory(addr)? as u16) << 8
+                        };
+
+                        let y: usize = (start_y + row) % height;    // Find the y coodinate of the sprite, use modulo to wrap around the screen
+
+                        // The whole row at once: line the sprite up with the left edge of the packed row, draw_row()
+                        // moves it to x, wraps (or clips) it and flips the pixels. Any pixel switched off sets Vf
+                        let sprite: Row = (pixel_data as Row) << (Row::BITS - 16);
+                        if self.draw_row(plane, start_x, y, sprite, self.quirks.clip_sprites) {
+                            self.v_reg[0xF] = 1;
+                        }
                     }
                 }
             }
 
             // SKP Vx (Ex9E): SKIP NEXT instruction if value in Vx is pressed
-            (0xE, _, 9, 0xE) => {
-                let key: u8 = self.v_reg[x];
+            Instruction::SkipKey(x) => {
+                let key: usize = self.key_index(self.v_reg[x as usize])?;
 
-                if self.keypad[key as usize]{
-                    self.pc += 2;
+                if self.keypad[key]{
+                    self.skip_next();
                 }
             }
 
             // SKNP Vx (ExA1): SKIP NEXT instruction if value in Vx is not pressed
-            (0xE, _, 0xA, 1) => {
-                let key: u8 = self.v_reg[x];
+            Instruction::SkipNotKey(x) => {
+                let key: usize = self.key_index(self.v_reg[x as usize])?;
 
-                if !self.keypad[key as usize]{
-                    self.pc += 2;
+                if !self.keypad[key]{
+                    self.skip_next();
+                }
+            }
+
+            // LD I, long addr (F000 nnnn): LOAD I = the 16-bit address stored in the next 2 bytes (XO-CHIP)
+            Instruction::LoadILong => {
+                let higher_byte: u16 = self.read_memory(self.pc as usize)? as u16;
+                let lower_byte: u16 = self.read_memory(self.pc as usize + 1)? as u16;
+
+                self.index_reg = higher_byte << 8 | lower_byte;
+                self.pc = self.pc.wrapping_add(2);  // the address is part of this instruction, step over it
+            }
+
+            // PLANE n (Fn01): SELECT the bitplanes used by drawing, clearing and scrolling (XO-CHIP)
+            Instruction::Plane(x) => {
+                self.plane_mask = x & 0x3;
+            }
+
+            // AUDIO (F002): LOAD the 16-byte audio pattern buffer from memory starting from address I (XO-CHIP)
+            Instruction::Audio => {
+                let start_idx = self.index_reg as usize;
+
+                for offset in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[offset] = self.read_memory(start_idx + offset)?;
                 }
             }
 
             // LD Vx, DT (Fx07): SET Vx to the value of the delay timer
-            (0xF, _, 0, 7) => {
-                self.v_reg[x] = self.delay_timer;
+            Instruction::LoadDelay(x) => {
+                self.v_reg[x as usize] = self.delay_timer;
             }
 
             // LD Vx, K (Fx0A): Wait for key press and SET Vx to the value of the key pressed
-            (0xF, _, 0, 0xA) => {
+            Instruction::WaitKey(x) => {
                 let mut pressed: bool = false;
 
                 for keys in 0..self.keypad.len() {
                     if self.keypad[keys] {
-                        self.v_reg[x] = keys as u8;
+                        self.v_reg[x as usize] = keys as u8;
                         pressed = true;
                         break;  
                     }
                 }
                 
                 if !pressed{
-                    self.pc -= 2    // If no key is pressed, jump back to the previous instruction which is this same instruction
+                    self.pc = self.pc.wrapping_sub(2)    // If no key is pressed, jump back to the previous instruction which is this same instruction
                 }
             }
 
             // LD DT, Vx (Fx15): SET delay timer from Vx
-            (0xF, _, 1, 5) => {
-                self.delay_timer = self.v_reg[x];
+            Instruction::SetDelay(x) => {
+                self.delay_timer = self.v_reg[x as usize];
             }
 
             // LD ST, Vx (Fx18): SET sound timer from Vx
-            (0xF, _, 1, 8) => {
-                self.sound_timer = self.v_reg[x];
+            Instruction::SetSound(x) => {
+                self.sound_timer = self.v_reg[x as usize];
             }
 
             // ADD I, Vx (Fx1E): ADD I = I + VX
-            (0xF, _, 1, 0xE) => {
-                let (sum, overflow) = self.index_reg.overflowing_add(self.v_reg[x] as u16);
+            Instruction::AddI(x) => {
+                let (sum, overflow) = self.index_reg.overflowing_add(self.v_reg[x as usize] as u16);
 
-                if overflow && VERBOSE{
-                    println!("OVERFLOW: opcode Fx1E, x={}", x);
+                if overflow {
+                    self.log(format_args!("OVERFLOW: opcode Fx1E, x={}", x));
                 }
 
                 self.index_reg = sum;
             }
 
             // LD F, Vx (Fx29): SET I = location of sprite for Vx
-            (0xF, _, 2, 9) => {
-                let font_digit = self.v_reg[x] as u16;
+            Instruction::Font(x) => {
+                let font_digit = self.v_reg[x as usize] as u16;
 
                 self.index_reg = font_digit * 5;    // multiply by 5 since fonts are 5 bytes long/tall, we can use this to find the starting address of the font
             }
 
+            // LD HF, Vx (Fx30): SET I = location of the 8x10 big font sprite for Vx (SUPER-CHIP)
+            Instruction::BigFont(x) => {
+                let font_digit = (self.v_reg[x as usize] & 0xF) as u16;
+
+                self.index_reg = BIG_FONTSET_ADDRESS as u16 + font_digit * 10;   // big font characters are 10 bytes tall
+            }
+
             // LD B, Vx (Fx33): SET BCD representation of Vx in memory locations I, I+1, I+2
-            (0xF, _, 3, 3) => {
-                let dec = self.v_reg[x] as f32;
+            Instruction::Bcd(x) => {
+                let dec: u8 = self.v_reg[x as usize];
 
-                self.memory[self.index_reg as usize] = (dec / 100.0).floor() as u8;
-                self.memory[self.index_reg as usize + 1] = ((dec / 10.0) % 10.0).floor() as u8;
-                self.memory[self.index_reg as usize + 2] = (dec % 10.0) as u8;
+                self.write_memory(self.index_reg as usize, dec / 100)?;
+                self.write_memory(self.index_reg as usize + 1, dec / 10 % 10)?;
+                self.write_memory(self.index_reg as usize + 2, dec % 10)?;
             }
 
             // LD [I], Vx (Fx55): STORE V0 to Vx in memory starting from address I
-            (0xF, _, 5, 5) => {
+            Instruction::Store(x) => {
                 let start_idx = self.index_reg as usize;
 
-                for index in 0..=x{
-                    self.memory[index + start_idx] = self.v_reg[index];
+                for index in 0..=x as usize{
+                    self.write_memory(index + start_idx, self.v_reg[index])?;
                 }
+
+                self.index_reg = self.index_reg.wrapping_add(self.load_store_increment(x));
             }
 
             // LD Vx, [I] (Fx65): SET/LOAD V0 to Vx from memory starting from address I
-            (0xF, _, 6, 5) => {
+            Instruction::Load(x) => {
                 let start_idx = self.index_reg as usize;
 
-                for index in 0..=x{
-                    self.v_reg[index] = self.memory[index + start_idx];
+                for index in 0..=x as usize{
+                    self.v_reg[index] = self.read_memory(index + start_idx)?;
                 }
+
+                self.index_reg = self.index_reg.wrapping_add(self.load_store_increment(x));
+            }
+
+            // LD R, Vx (Fx75): STORE V0 to Vx in the RPL user flags (SUPER-CHIP)
+            Instruction::StoreFlags(x) => {
+                let last: usize = (x as usize).min(RPL_SIZE - 1);
+
+                self.rpl[..=last].copy_from_slice(&self.v_reg[..=last]);
+            }
+
+            // PITCH Vx (Fx3A): SET the audio pattern playback rate from Vx (XO-CHIP)
+            Instruction::Pitch(x) => {
+                self.pitch = self.v_reg[x as usize];
+            }
+
+            // LD Vx, R (Fx85): LOAD V0 to Vx from the RPL user flags (SUPER-CHIP)
+            Instruction::LoadFlags(x) => {
+                let last: usize = (x as usize).min(RPL_SIZE - 1);
+
+                self.v_reg[..=last].copy_from_slice(&self.rpl[..=last]);
             }
 
             // Unknown
-            (_, _, _, _) => {
-                println!("Unknown opcode: 0x{:04X}", op);
+            Instruction::Unknown(op) => {
+                return Err(Chip8Error::UnknownOpcode { op, pc: self.pc.wrapping_sub(2) });
             }
         }
+
+        Ok(())
     }
-}
 
+    // SKIP the next instruction. In XO-CHIP mode the next instruction may be the 4 byte F000 nnnn, which is skipped whole
+    fn skip_next(&mut self) {
+        let is_long_load: bool = self.xo_chip
+            && self.memory[self.pc as usize] == 0xF0
+            && self.memory[(self.pc as usize + 1) % XO_RAM_SIZE] == 0x00;
+
+        self.pc = self.pc.wrapping_add(if is_long_load {4} else {2});
+    }
+
+    // How far Fx55/Fx65 with registers V0 to Vx move I, see LoadStoreIncrement
+    fn load_store_increment(&self, x: u8) -> u16 {
+        match self.quirks.load_store_increments_i {
+            LoadStoreIncrement::None => 0,
+            LoadStoreIncrement::X => x as u16,
+            LoadStoreIncrement::XPlusOne => x as u16 + 1,
+        }
+    }
+
+    // Keypad index held in a register, only 0 - F are keys
+    fn key_index(&self, val: u8) -> Result<usize, Chip8Error> {
+        if val as usize >= KEYPAD_SIZE {
+            return Err(Chip8Error::InvalidKey);
+        }
+
+        Ok(val as usize)
+    }
+
+    // Bitplanes picked by the plane mask (Fn01), first plane first
+    fn selected_planes(&self) -> impl Iterator<Item = usize> + use<> {
+        let mask: u8 = self.plane_mask;
+
+        (0..PLANES).filter(move |plane| mask & (1 << plane) != 0)
+    }
+
+    // Registers from x to y inclusive, walking backwards when y < x (5xy2/5xy3)
+    fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+        (0..=x.abs_diff(y)).map(move |offset| if x <= y {x + offset} else {x - offset})
+    }
+}
@@ -0,0 +1,24 @@
+// Diagnostics hook. The engine never prints anything itself (it may not have anywhere to print to), notes about
+// odd things a program does, like a register wrapping around, go to the logger when one is set: eprintln! on a
+// desktop, a serial port on a microcontroller. Without one a note costs a branch and is never formatted.
+
+use crate::*;
+
+pub type Logger = Box<dyn FnMut(fmt::Arguments)>;
+
+impl Chip8 {
+    // Start sending diagnostics to logger, replacing the one set before
+    pub fn set_logger(&mut self, logger: impl FnMut(fmt::Arguments) + 'static) {
+        self.logger = Some(Box::new(logger));
+    }
+
+    pub fn take_logger(&mut self) -> Option<Logger> {
+        self.logger.take()
+    }
+
+    pub(crate) fn log(&mut self, args: fmt::Arguments) {
+        if let Some(logger) = &mut self.logger {
+            logger(args);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// Random numbers for Cxnn. The generator is owned by the Chip8 and is part of its save state,
+// so a run started from the same seed (or the same snapshot) always produces the same numbers.
+// SplitMix64 is tiny, has a single u64 of state and accepts any seed, including 0
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Prng {
+    state: u64,
+}
+
+impl Prng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
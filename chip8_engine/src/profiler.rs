@@ -0,0 +1,232 @@
+// Profiler for finding where a ROM spends its cycles. While one is set, tick() reports every executed
+// instruction to it and it counts them per address, per opcode class (8xy4, Dxyn, ...) and per subroutine.
+//
+// Subroutines are followed through 2nnn and 00EE. A subroutine is named by its entry address, whatever runs
+// outside of any call is "main". Exclusive cycles are the instructions of the subroutine itself, inclusive ones
+// add everything it called (a recursive subroutine is only counted once per outermost call). The call stack
+// is the profiler's own, it starts empty wherever profiling starts, so a profiler set in the middle of a
+// subroutine sees its 00EE as one without a call and keeps counting it to main.
+//
+// Reports come as text, JSON, or collapsed stacks (one "main;0x0300;0x0340 1234" line per call path) for
+// flamegraph.pl, inferno or speedscope.
+
+use alloc::collections::BTreeMap;
+
+use crate::*;
+
+const ROOT: &str = "main";
+const TEXT_REPORT_ROWS: usize = 20;     // hot addresses listed in the text report
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Text,
+    Json,
+    CollapsedStacks,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddressProfile {
+    pub opcode: u16,        // last opcode executed there, a ROM that modifies itself can have several
+    pub count: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubroutineProfile {
+    pub calls: u64,
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    cycles: u64,
+    addresses: BTreeMap<u16, AddressProfile>,
+    classes: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<Option<u16>, SubroutineProfile>,   // None is main
+    stacks: BTreeMap<Vec<u16>, u64>,                         // instructions per call path, outermost call first
+
+    path: Vec<u16>,             // entry addresses of the subroutines being run, outermost first
+    entered: Vec<u64>,          // cycle count when each of them was called
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Instructions counted so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn address(&self, addr: u16) -> Option<AddressProfile> {
+        self.addresses.get(&addr).copied()
+    }
+
+    pub fn class(&self, class: &str) -> u64 {
+        self.classes.get(class).copied().unwrap_or(0)
+    }
+
+    // None for main. Subroutines still running count up to now
+    pub fn subroutine(&self, entry: Option<u16>) -> Option<SubroutineProfile> {
+        self.subroutines().into_iter().find(|(addr, _)| *addr == entry).map(|(_, profile)| profile)
+    }
+
+    // Called by tick() with an instruction that ran without an error
+    pub(crate) fn record(&mut self, pc: u16, opcode: u16) {
+        self.cycles += 1;
+
+        let address: &mut AddressProfile = self.addresses.entry(pc).or_default();
+        address.opcode = opcode;
+        address.count += 1;
+
+        let instruction: Instruction = Instruction::decode(opcode);
+        *self.classes.entry(instruction.class()).or_default() += 1;
+
+        self.subroutines.entry(self.path.last().copied()).or_default().exclusive += 1;
+        match self.stacks.get_mut(&self.path[..]) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.path.clone(), 1);
+            }
+        }
+
+        // The call itself belongs to the caller, the return to the subroutine returning
+        match instruction {
+            Instruction::Call(addr) => {
+                self.subroutines.entry(Some(addr)).or_default().calls += 1;
+                self.path.push(addr);
+                self.entered.push(self.cycles);
+            }
+            Instruction::Ret => {
+                if let (Some(addr), Some(entered)) = (self.path.pop(), self.entered.pop())
+                    && !self.path.contains(&addr) {
+                    self.subroutines.entry(Some(addr)).or_default().inclusive += self.cycles - entered;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Every subroutine seen, with the ones still on the stack counted up to now. Most inclusive cycles first
+    pub fn subroutines(&self) -> Vec<(Option<u16>, SubroutineProfile)> {
+        let mut subroutines: BTreeMap<Option<u16>, SubroutineProfile> = self.subroutines.clone();
+        subroutines.entry(None).or_default().inclusive = self.cycles;
+
+        for (idx, (addr, entered)) in self.path.iter().zip(&self.entered).enumerate() {
+            if !self.path[..idx].contains(addr) {
+                subroutines.entry(Some(*addr)).or_default().inclusive += self.cycles - entered;
+            }
+        }
+
+        let mut subroutines: Vec<(Option<u16>, SubroutineProfile)> = subroutines.into_iter().collect();
+        subroutines.sort_by_key(|(addr, profile)| (u64::MAX - profile.inclusive, *addr));
+
+        subroutines
+    }
+
+    // Most executed first
+    pub fn addresses(&self) -> Vec<(u16, AddressProfile)> {
+        let mut addresses: Vec<(u16, AddressProfile)> = self.addresses.iter().map(|(addr, profile)| (*addr, *profile)).collect();
+        addresses.sort_by_key(|(addr, profile)| (u64::MAX - profile.count, *addr));
+
+        addresses
+    }
+
+    // Most executed first
+    pub fn classes(&self) -> Vec<(&'static str, u64)> {
+        let mut classes: Vec<(&'static str, u64)> = self.classes.iter().map(|(class, count)| (*class, *count)).collect();
+        classes.sort_by_key(|(class, count)| (u64::MAX - count, *class));
+
+        classes
+    }
+
+    pub fn report(&self, format: ProfileFormat) -> String {
+        match format {
+            ProfileFormat::Text => self.text_report(),
+            ProfileFormat::Json => self.json_report(),
+            ProfileFormat::CollapsedStacks => self.collapsed_stacks(),
+        }
+    }
+
+    fn text_report(&self) -> String {
+        let mut out: String = format!("{} instructions\n", self.cycles);
+        let percent = |count: u64| 100.0 * count as f64 / self.cycles.max(1) as f64;
+
+        out += &format!("\nHot addresses (top {})\n", TEXT_REPORT_ROWS);
+        for (addr, profile) in self.addresses().into_iter().take(TEXT_REPORT_ROWS) {
+            let instruction: Instruction = Instruction::decode(profile.opcode);
+            out += &format!("  {:04X}  {:>12} {:>6.2}%  {:04X}  {}\n", addr, profile.count, percent(profile.count), profile.opcode, instruction);
+        }
+
+        out += "\nOpcode classes\n";
+        for (class, count) in self.classes() {
+            out += &format!("  {}  {:>12} {:>6.2}%\n", class, count, percent(count));
+        }
+
+        out += &format!("\nSubroutines{:>16}{:>13}{:>21}\n", "calls", "inclusive", "exclusive");
+        for (addr, profile) in self.subroutines() {
+            out += &format!(
+                "  {:<8} {:>16} {:>12} {:>6.2}% {:>12} {:>6.2}%\n",
+                name(addr), profile.calls, profile.inclusive, percent(profile.inclusive), profile.exclusive, percent(profile.exclusive)
+            );
+        }
+
+        out
+    }
+
+    fn json_report(&self) -> String {
+        let addresses: Vec<String> = self.addresses().into_iter()
+            .map(|(addr, profile)| format!("{{\"address\":{},\"opcode\":{},\"count\":{}}}", addr, profile.opcode, profile.count))
+            .collect();
+        let classes: Vec<String> = self.classes().into_iter()
+            .map(|(class, count)| format!("{{\"class\":\"{}\",\"count\":{}}}", class, count))
+            .collect();
+        let subroutines: Vec<String> = self.subroutines().into_iter()
+            .map(|(addr, profile)| format!(
+                "{{\"name\":\"{}\",\"address\":{},\"calls\":{},\"inclusive\":{},\"exclusive\":{}}}",
+                name(addr), addr.map_or("null".to_string(), |addr: u16| addr.to_string()), profile.calls, profile.inclusive, profile.exclusive
+            ))
+            .collect();
+
+        format!(
+            "{{\"cycles\":{},\"addresses\":[{}],\"classes\":[{}],\"subroutines\":[{}]}}\n",
+            self.cycles, addresses.join(","), classes.join(","), subroutines.join(",")
+        )
+    }
+
+    fn collapsed_stacks(&self) -> String {
+        let mut lines: Vec<String> = self.stacks.iter()
+            .map(|(path, count)| {
+                let frames: Vec<String> = core::iter::once(ROOT.to_string()).chain(path.iter().map(|addr: &u16| name(Some(*addr)))).collect();
+                format!("{} {}\n", frames.join(";"), count)
+            })
+            .collect();
+        lines.sort();
+
+        lines.concat()
+    }
+}
+
+fn name(addr: Option<u16>) -> String {
+    match addr {
+        Some(addr) => format!("0x{:04X}", addr),
+        None => ROOT.to_string(),
+    }
+}
+
+impl Chip8 {
+    // Start profiling into profiler, replacing the one set before
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    // Stop profiling and hand the results back
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take()
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+}
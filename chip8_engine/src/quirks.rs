@@ -0,0 +1,66 @@
+// Quirks cover the opcodes that the different CHIP-8 interpreters never agreed on.
+// Every flag is honoured by the matching arm in Chip8::execute, so a ROM written for one
+// platform can be run by picking the preset of that platform.
+
+// The default (every flag off) keeps the behaviour this engine always had: in-place shifts, I untouched
+// by Fx55/Fx65, Bnnn on V0, VF untouched by the logic ops and sprites wrapping around the screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift_uses_vy: bool,        // 8xy6/8xyE: SET Vx = Vy before shifting (VIP) instead of shifting Vx in place
+    pub load_store_increments_i: LoadStoreIncrement,   // Fx55/Fx65: how far I moves after the transfer
+    pub jump_uses_vx: bool,         // Bnnn: JUMP to nnn + Vx where x is the highest nibble of nnn (CHIP-48/SCHIP) instead of nnn + V0
+    pub logic_resets_vf: bool,      // 8xy1/8xy2/8xy3: SET VF = 0 after the operation (VIP)
+    pub clip_sprites: bool,         // Dxyn: pixels past the screen edge are clipped instead of wrapping around
+    pub display_wait: bool,         // Dxyn: wait for the next vblank (timers() call) before drawing (VIP)
+}
+
+// How far Fx55/Fx65 move I after transferring V0 to Vx
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoadStoreIncrement {
+    #[default]
+    None,           // I is left untouched (SCHIP)
+    X,              // I = I + x (CHIP-48, which has an off-by-one against the VIP)
+    XPlusOne,       // I = I + x + 1, past the last byte transferred (VIP)
+}
+
+impl Quirks {
+    // COSMAC VIP, the original interpreter
+    pub const VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: LoadStoreIncrement::XPlusOne,
+        jump_uses_vx: false,
+        logic_resets_vf: true,
+        clip_sprites: true,
+        display_wait: true,
+    };
+
+    // CHIP-48 for the HP-48 calculators
+    pub const CHIP48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: LoadStoreIncrement::X,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    // SUPER-CHIP 1.1
+    pub const SCHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: LoadStoreIncrement::None,
+        jump_uses_vx: true,
+        logic_resets_vf: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    // XO-CHIP as implemented by Octo
+    pub const XOCHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: LoadStoreIncrement::XPlusOne,
+        jump_uses_vx: false,
+        logic_resets_vf: false,
+        clip_sprites: false,
+        display_wait: false,
+    };
+}
@@ -0,0 +1,178 @@
+// Block recompiler, the execution core behind Core::Recompiler.
+//
+// The first time the program counter lands on an address, the straight run of instructions starting there is
+// translated into a Block: a list of closures with the operands already bound, one per instruction. The block
+// ends at the first instruction that can move the program counter somewhere else (jumps, calls, returns,
+// skips, Fx0A and Dxyn, which can wait for a key or the vblank), that halts, or that writes memory (Fx55, Fx33,
+// 5xy2), so no block ever runs past code it may have just rewritten. run_frame() then runs whole blocks instead
+// of fetching and decoding every instruction.
+//
+// The most common register instructions get closures of their own, everything else goes through
+// execute_instruction() with the decoding already done, so every instruction behaves exactly as in the
+// interpreter. A write to memory drops every block covering the byte written (see invalidate() in cache.rs).
+// tick(), and with it the debugger, the tracer and the profiler, still goes one instruction at a time.
+
+use alloc::rc::Rc;
+
+use crate::*;
+
+const MAX_BLOCK_BYTES: usize = 128;     // a block is cut after this many bytes of code
+const MAX_BLOCK_SPAN: usize = MAX_BLOCK_BYTES + 4;  // bytes a block can cover, the last instruction may be F000 nnnn
+
+type Operation = Box<dyn Fn(&mut Chip8) -> Result<(), Chip8Error>>;
+
+pub(crate) struct Block {
+    steps: Vec<Step>,
+}
+
+struct Step {
+    next_pc: u16,           // where the program counter is while the instruction runs, like after a fetch
+    run: Operation,
+}
+
+impl Block {
+    // Translate the instructions starting at start. Empty when not even the first opcode can be fetched,
+    // tick() then reports the error
+    fn compile(chip8: &Chip8, start: usize) -> (Block, usize) {
+        let mut steps: Vec<Step> = Vec::new();
+        let mut addr: usize = start;
+
+        while addr + 1 < chip8.memory_size() && addr - start < MAX_BLOCK_BYTES {
+            let op: u16 = (chip8.memory[addr] as u16) << 8 | chip8.memory[addr + 1] as u16;
+            let instruction: Instruction = Instruction::decode(op);
+
+            steps.push(Step { next_pc: (addr as u16).wrapping_add(2), run: operation(instruction) });
+            addr += instruction.size() as usize;
+
+            if ends_block(instruction) {
+                break;
+            }
+        }
+
+        (Block { steps }, addr.min(chip8.memory_size()))
+    }
+}
+
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(instruction,
+        Instruction::Jump(_)
+        | Instruction::JumpOffset(_)
+        | Instruction::Call(_)
+        | Instruction::Ret
+        | Instruction::SkipEqByte(_, _)
+        | Instruction::SkipNeByte(_, _)
+        | Instruction::SkipEqReg(_, _)
+        | Instruction::SkipNeReg(_, _)
+        | Instruction::SkipKey(_)
+        | Instruction::SkipNotKey(_)
+        | Instruction::WaitKey(_)
+        | Instruction::Draw(_, _, _)
+        | Instruction::Exit
+        | Instruction::Store(_)
+        | Instruction::Bcd(_)
+        | Instruction::SaveRange(_, _)
+        | Instruction::Unknown(_)
+    )
+}
+
+// The closure for one instruction, the same as the matching arm of execute_instruction()
+fn operation(instruction: Instruction) -> Operation {
+    match instruction {
+        Instruction::LoadByte(x, nn) => Box::new(move |chip8: &mut Chip8| {
+            chip8.v_reg[x as usize] = nn;
+            Ok(())
+        }),
+        Instruction::AddByte(x, nn) => Box::new(move |chip8: &mut Chip8| {
+            let (sum, overflow) = chip8.v_reg[x as usize].overflowing_add(nn);
+            if overflow {
+                chip8.log(format_args!("OVERFLOW: opcode 7xnn, x={}, nn={}", x, nn));
+            }
+            chip8.v_reg[x as usize] = sum;
+            Ok(())
+        }),
+        Instruction::LoadReg(x, y) => Box::new(move |chip8: &mut Chip8| {
+            chip8.v_reg[x as usize] = chip8.v_reg[y as usize];
+            Ok(())
+        }),
+        Instruction::AddReg(x, y) => Box::new(move |chip8: &mut Chip8| {
+            let (sum, carry) = chip8.v_reg[x as usize].overflowing_add(chip8.v_reg[y as usize]);
+            chip8.v_reg[x as usize] = sum;
+            chip8.v_reg[0xF] = carry as u8;
+            Ok(())
+        }),
+        Instruction::LoadI(nnn) => Box::new(move |chip8: &mut Chip8| {
+            chip8.index_reg = nnn;
+            Ok(())
+        }),
+        _ => Box::new(move |chip8: &mut Chip8| chip8.execute_instruction(instruction)),
+    }
+}
+
+impl Chip8 {
+    // run_frame() runs blocks only when nothing needs to see the instructions one by one
+    pub(crate) fn runs_blocks(&self) -> bool {
+        self.core == Core::Recompiler && self.tracer.is_none() && self.profiler.is_none()
+    }
+
+    // Run the block at pc, at most `budget` instructions of it. Returns how many ran
+    pub(crate) fn run_block(&mut self, budget: usize) -> Result<usize, Chip8Error> {
+        let pc: usize = self.pc as usize;
+        let block: Rc<Block> = match self.blocks.get(pc) {
+            Some(Some(block)) => block.clone(),
+            Some(None) => self.compile_block(pc),
+            None => Rc::new(Block { steps: Vec::new() }),
+        };
+
+        if block.steps.is_empty() {
+            self.tick()?;
+            return Ok(1);
+        }
+
+        let mut ran: usize = 0;
+        for step in block.steps.iter().take(budget) {
+            self.pc = step.next_pc;
+            (step.run)(self)?;
+            self.cycles += 1;
+            ran += 1;
+        }
+
+        Ok(ran)
+    }
+
+    fn compile_block(&mut self, start: usize) -> Rc<Block> {
+        let (block, end) = Block::compile(self, start);
+        let block: Rc<Block> = Rc::new(block);
+
+        self.blocks[start] = Some(block.clone());
+        for covered in &mut self.block_cover[start..end] {
+            *covered = true;
+        }
+
+        block
+    }
+
+    // Drop the blocks covering addr. block_cover marks every byte that was ever part of a block, so a write
+    // anywhere else costs nothing
+    pub(crate) fn invalidate_blocks(&mut self, addr: usize) {
+        if !self.block_cover.get(addr).copied().unwrap_or(false) {
+            return;
+        }
+
+        for start in addr.saturating_sub(MAX_BLOCK_SPAN)..=addr {
+            let covers: bool = match &self.blocks[start] {
+                Some(block) => block.steps.last().is_some_and(|last: &Step| start <= addr && addr < block_end(last)),
+                None => false,
+            };
+            if covers {
+                self.blocks[start] = None;
+            }
+        }
+    }
+}
+
+// The byte after a block: its last instruction starts 2 bytes before next_pc and F000 nnnn is 4 bytes long.
+// Taken as the longest case, dropping a block that did not need it costs a recompile and nothing else.
+// next_pc wraps like the program counter, an instruction at 0xFFFE has a next_pc of 0
+fn block_end(last: &Step) -> usize {
+    last.next_pc.wrapping_sub(2) as usize + 4
+}
@@ -0,0 +1,193 @@
+// Rewind keeps a bounded history of save states so a frontend can step a running game back in time.
+//
+// A snapshot is taken every `interval` frames. Only the newest snapshot is stored whole, every older one is kept
+// as the XOR difference to the snapshot after it, run-length encoded. Between two frames a CHIP-8 only touches a
+// handful of registers, some screen bytes and rarely memory, so a difference is mostly zeros and shrinks to a few
+// dozen bytes instead of the 6kB (or 68kB in XO-CHIP mode) of a full state.
+
+use alloc::collections::VecDeque;
+
+use crate::*;
+
+pub struct Rewind {
+    interval: usize,                // frames between two snapshots
+    capacity: usize,                // snapshots kept, older ones are dropped
+    frames_since_snapshot: usize,
+    rewind_debt: usize,             // frames asked to rewind that did not add up to a snapshot yet
+
+    latest: Option<Vec<u8>>,        // newest snapshot, as a full save state
+    deltas: VecDeque<Delta>,        // deltas[i] turns snapshot i + 1 back into snapshot i, newest at the back
+}
+
+enum Delta {
+    Xor(Vec<u8>),       // run-length encoded XOR against the newer snapshot
+    Full(Vec<u8>),      // the newer snapshot had a different size (XO-CHIP mode was toggled), nothing to XOR against
+}
+
+impl Rewind {
+    pub fn new(interval: usize, capacity: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            frames_since_snapshot: 0,
+            rewind_debt: 0,
+
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    // Call once per emulated frame, a snapshot is taken every `interval` calls
+    pub fn record(&mut self, chip8: &Chip8) {
+        self.rewind_debt = 0;
+
+        if self.latest.is_some() && self.frames_since_snapshot + 1 < self.interval {
+            self.frames_since_snapshot += 1;
+            return;
+        }
+
+        self.frames_since_snapshot = 0;
+
+        let state: Vec<u8> = chip8.save_state();
+        if let Some(previous) = self.latest.take() {
+            let delta: Delta = if previous.len() == state.len() {
+                Delta::Xor(encode_xor(&previous, &state))
+            } else {
+                Delta::Full(previous)
+            };
+
+            self.deltas.push_back(delta);
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+
+        self.latest = Some(state);
+    }
+
+    // Move back one snapshot. The first step after running returns to the newest snapshot,
+    // every step after that goes one snapshot further back. Returns false once the history is exhausted
+    pub fn step_back(&mut self, chip8: &mut Chip8) -> bool {
+        let Some(latest) = self.latest.as_mut() else {
+            return false;
+        };
+
+        if self.frames_since_snapshot > 0 {
+            self.frames_since_snapshot = 0;
+        } else {
+            match self.deltas.pop_back() {
+                Some(Delta::Xor(encoded)) => apply_xor(latest, &encoded),
+                Some(Delta::Full(state)) => *latest = state,
+                None => return false,
+            }
+        }
+
+        chip8.load_state(latest).is_ok()
+    }
+
+    // Move back by `frames` frames. Frames that do not reach the next snapshot yet are carried over to the next
+    // call, so calling this with the frames that passed rewinds at the speed the game ran. Returns false once
+    // the history is exhausted
+    pub fn rewind(&mut self, chip8: &mut Chip8, frames: usize) -> bool {
+        self.rewind_debt += frames;
+
+        loop {
+            let Some(step) = self.frames_to_previous() else {
+                self.rewind_debt = 0;
+                return false;
+            };
+            if self.rewind_debt < step {
+                return true;
+            }
+
+            self.step_back(chip8);
+            self.rewind_debt -= step;
+        }
+    }
+
+    // How far back the next step_back() goes, None when there is nothing left to go back to
+    fn frames_to_previous(&self) -> Option<usize> {
+        match (&self.latest, self.frames_since_snapshot) {
+            (None, _) => None,
+            (Some(_), 0) if self.deltas.is_empty() => None,
+            (Some(_), 0) => Some(self.interval),
+            (Some(_), frames) => Some(frames),
+        }
+    }
+
+    // Number of frames that can still be rewound
+    pub fn available_frames(&self) -> usize {
+        match self.latest {
+            Some(_) => self.deltas.len() * self.interval + self.frames_since_snapshot,
+            None => 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames_since_snapshot = 0;
+        self.rewind_debt = 0;
+        self.latest = None;
+        self.deltas.clear();
+    }
+}
+
+// The XOR of two equally long states as (zero run, literal run, literal bytes) triples, runs as LEB128 varints
+fn encode_xor(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::new();
+    let mut pos: usize = 0;
+
+    while pos < newer.len() {
+        let zeros: usize = (pos..newer.len()).take_while(|i| older[*i] == newer[*i]).count();
+        let literal_start: usize = pos + zeros;
+        let literals: usize = (literal_start..newer.len()).take_while(|i| older[*i] != newer[*i]).count();
+
+        push_varint(&mut out, zeros);
+        push_varint(&mut out, literals);
+        out.extend((literal_start..literal_start + literals).map(|i| older[i] ^ newer[i]));
+
+        pos = literal_start + literals;
+    }
+
+    out
+}
+
+fn apply_xor(state: &mut [u8], encoded: &[u8]) {
+    let mut reader: usize = 0;
+    let mut pos: usize = 0;
+
+    while reader < encoded.len() {
+        pos += read_varint(encoded, &mut reader);
+        let literals: usize = read_varint(encoded, &mut reader);
+
+        for (byte, diff) in state[pos..pos + literals].iter_mut().zip(&encoded[reader..reader + literals]) {
+            *byte ^= diff;
+        }
+
+        reader += literals;
+        pos += literals;
+    }
+}
+
+fn push_varint(out: &mut Vec<u8>, mut val: usize) {
+    while val >= 0x80 {
+        out.push((val as u8 & 0x7F) | 0x80);
+        val >>= 7;
+    }
+    out.push(val as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut val: usize = 0;
+    let mut shift: u32 = 0;
+
+    loop {
+        let byte: u8 = data[*pos];
+        *pos += 1;
+
+        val |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return val;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,119 @@
+// Frame scheduler, so the speed of a game no longer depends on how often the frontend calls tick().
+//
+// The CPU runs at a configurable number of instructions per second and the delay and sound timers count down
+// at 60 Hz of emulated time. run_frame() emulates exactly one 60 Hz frame. run_for() emulates however many
+// frames fit in a stretch of real time and carries the rest over to the next call, so a frontend can call it
+// on every vsync (or every timer callback) with the time since the last one, whatever the refresh rate.
+
+use core::time::Duration;
+
+use crate::*;
+
+pub const TIMER_HZ: u32 = 60;
+pub const DEFAULT_SPEED: u32 = 600;         // instructions per second, the old 10 ticks per 60 Hz frame
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+const MAX_CATCH_UP_FRAMES: u128 = 6;        // after a stall (window dragged, tab in the background) skip ahead instead of racing
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameResult {
+    pub screen_changed: bool,       // something was drawn, cleared or scrolled since the previous result
+    pub dirty_rows: u64,            // which rows, bit n for row n (see display.rs)
+    pub sound_active: bool,         // the sound timer is running, the buzzer should be audible
+    pub frames: usize,              // frames emulated for this result, 0 while paused
+}
+
+impl Chip8 {
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    // Instructions per second, at least one instruction per frame
+    pub fn set_speed(&mut self, instructions_per_second: u32) {
+        self.speed = instructions_per_second.max(TIMER_HZ);
+        self.cycle_remainder = 0;
+    }
+
+    pub fn run_frame(&mut self) -> Result<FrameResult, Chip8Error> {
+        self.run_frames_with(1, run_cycles)
+    }
+
+    // One frame like run_frame(), but `run` executes the instructions it gets, e.g. one tick() at a time with a
+    // look at the machine in between. The timers count down once it returns Ok, an Err is passed on as it is
+    pub fn run_frame_with<E>(&mut self, run: impl FnMut(&mut Chip8, usize) -> Result<(), E>) -> Result<FrameResult, E> {
+        self.run_frames_with(1, run)
+    }
+
+    pub fn run_for(&mut self, elapsed: Duration) -> Result<FrameResult, Chip8Error> {
+        let frames: usize = self.frames_due(elapsed);
+
+        self.run_frames_with(frames, run_cycles)
+    }
+
+    // Add real time to the backlog and take the whole frames out of it. For a frontend that spends the time on
+    // something other than running, e.g. rewinding at the speed the game ran
+    pub fn frames_due(&mut self, elapsed: Duration) -> usize {
+        // Kept as nanoseconds times 60 so a frame is exactly one second worth of units
+        let frame: u128 = NANOS_PER_SECOND;
+        self.time_debt += elapsed.as_nanos() * TIMER_HZ as u128;
+
+        let frames: u128 = self.time_debt / frame;
+        self.time_debt %= frame;
+
+        frames.min(MAX_CATCH_UP_FRAMES) as usize
+    }
+
+    // Forget the backlog, used while the emulation is paused
+    pub(crate) fn discard_time(&mut self) {
+        self.time_debt = 0;
+    }
+
+    // Run `frames` frames, `run` executes the instructions of one frame. Timers count down after every frame
+    // that ran to completion, an error stops at once
+    pub(crate) fn run_frames_with<E>(&mut self, frames: usize, mut run: impl FnMut(&mut Chip8, usize) -> Result<(), E>) -> Result<FrameResult, E> {
+        for _ in 0..frames {
+            let cycles: usize = self.cycles_for_frame();
+            run(self, cycles)?;
+            self.timers();
+        }
+
+        Ok(self.frame_result(frames))
+    }
+
+    // Instructions in the next frame. Speeds that are not a multiple of 60 carry the fraction over,
+    // 700 instructions per second alternates between 11 and 12 per frame
+    fn cycles_for_frame(&mut self) -> usize {
+        self.cycle_remainder += self.speed;
+        let cycles: u32 = self.cycle_remainder / TIMER_HZ;
+        self.cycle_remainder %= TIMER_HZ;
+
+        cycles as usize
+    }
+
+    // What happened since the previous result, over `frames` frames
+    pub(crate) fn frame_result(&mut self, frames: usize) -> FrameResult {
+        let dirty_rows: u64 = self.take_dirty_rows();
+
+        FrameResult {
+            screen_changed: dirty_rows != 0,
+            dirty_rows,
+            sound_active: self.sound_active(),
+            frames,
+        }
+    }
+}
+
+// The recompiler runs a block at a time, cut short where the frame ends
+pub(crate) fn run_cycles(chip8: &mut Chip8, cycles: usize) -> Result<(), Chip8Error> {
+    let mut remaining: usize = cycles;
+    while remaining > 0 && !chip8.halted {
+        if chip8.runs_blocks() {
+            remaining -= chip8.run_block(remaining)?;
+        } else {
+            chip8.tick()?;
+            remaining -= 1;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+// Scripted key presses, for runs without a player at the keyboard: headless runs, CI, the integration tests.
+// A script is a list of presses, each holding one key down for a number of frames. Overlapping presses of
+// the same key just keep it down longer.
+
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub frame: u64,         // first frame the key is down
+    pub key: usize,         // 0x0 - 0xF
+    pub hold: u64,          // frames it stays down
+}
+
+impl KeyPress {
+    pub fn is_down(&self, frame: u64) -> bool {
+        frame >= self.frame && frame < self.frame.saturating_add(self.hold)
+    }
+}
+
+// The keys held down during `frame`, index 0x0 - 0xF
+pub fn scripted_keypad(presses: &[KeyPress], frame: u64) -> [bool; KEYPAD_SIZE] {
+    let mut keypad: [bool; KEYPAD_SIZE] = [false; KEYPAD_SIZE];
+
+    for press in presses.iter().filter(|press: &&KeyPress| press.is_down(frame)) {
+        if let Some(key) = keypad.get_mut(press.key) {
+            *key = true;
+        }
+    }
+
+    keypad
+}
@@ -0,0 +1,304 @@
+// Save states capture the whole machine so a session can be stored and resumed later, on either frontend.
+//
+// FORMAT (version 2), every multi-byte value is little-endian:
+//
+//   offset  size   field
+//   0       4      magic "C8ST"
+//   4       2      format version
+//   6       1      flags: bit 0 hi-res, bit 1 halted, bit 2 XO-CHIP mode, bit 3 vblank
+//   7       1      quirks: bit 0 shift_uses_vy, bit 1 load_store_increments_i is XPlusOne, bit 2 jump_uses_vx,
+//                  bit 3 logic_resets_vf, bit 4 clip_sprites, bit 5 display_wait, bit 6 load_store_increments_i is X
+//   8       2      program counter
+//   10      2      index register
+//   12      16     V0 - VF
+//   28      32     stack, 16 slots of 2 bytes
+//   60      1      stack pointer
+//   61      1      delay timer
+//   62      1      sound timer
+//   63      2      keypad, bit n set when key n is down
+//   65      16     RPL user flags
+//   81      16     XO-CHIP audio pattern
+//   97      1      XO-CHIP pitch
+//   98      1      XO-CHIP plane mask
+//   99      8      random number generator state
+//   107     2048   screen, 1024 bytes per bitplane (plane 0 first) with 8 pixels per byte, MSB first. Pixel (x, y)
+//                  of the active resolution is bit x + width * y, so in lo-res only the first 256 bytes of a plane
+//                  are used, at a 64 pixel stride, and the rest is zero
+//   2155    4      memory length (4096, or 65536 in XO-CHIP mode)
+//   2159    n      memory
+//   2159+n  4      CRC-32 of every byte before it
+//
+// Version 1 is the same without the random number generator state, loading one keeps the current generator.
+
+use core::fmt;
+
+use crate::*;
+
+const MAGIC: [u8; 4] = *b"C8ST";
+const VERSION: u16 = 2;
+
+const FLAG_HIRES: u8 = 1 << 0;
+const FLAG_HALTED: u8 = 1 << 1;
+const FLAG_XO_CHIP: u8 = 1 << 2;
+const FLAG_VBLANK: u8 = 1 << 3;
+
+const PACKED_PLANE_SIZE: usize = SCREEN_BUFFER_SIZE / 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,                       // not a save state
+    UnsupportedVersion(u16),        // written by a newer (or unknown) version of the format
+    Truncated,                      // shorter than the format says it should be
+    ChecksumMismatch,               // damaged after it was written
+    Corrupt,                        // checksum is fine but a field holds an impossible value
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a CHIP-8 save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "unsupported save state version {}", version),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::ChecksumMismatch => write!(f, "save state checksum mismatch"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl core::error::Error for StateError {}
+
+impl Chip8 {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::with_capacity(2163 + self.memory_size());
+
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        let mut flags: u8 = 0;
+        if self.hires {flags |= FLAG_HIRES}
+        if self.halted {flags |= FLAG_HALTED}
+        if self.xo_chip {flags |= FLAG_XO_CHIP}
+        if self.vblank {flags |= FLAG_VBLANK}
+        out.push(flags);
+        out.push(quirks_to_bits(self.quirks));
+
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.index_reg.to_le_bytes());
+        out.extend_from_slice(&self.v_reg);
+        for slot in self.stack {
+            out.extend_from_slice(&slot.to_le_bytes());
+        }
+        out.push(self.stack_pointer as u8);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+
+        let keypad: u16 = self.keypad.iter().enumerate().fold(0, |bits, (key, down)| bits | (*down as u16) << key);
+        out.extend_from_slice(&keypad.to_le_bytes());
+
+        out.extend_from_slice(&self.rpl);
+        out.extend_from_slice(&self.audio_pattern);
+        out.push(self.pitch);
+        out.push(self.plane_mask);
+        out.extend_from_slice(&self.rng.state().to_le_bytes());
+
+        // Same layout as before the display was packed into rows, pixel x + width * y of the active resolution
+        for plane in 0..PLANES {
+            for start in (0..SCREEN_BUFFER_SIZE).step_by(8) {
+                out.push((start..start + 8).fold(0, |byte, i| byte << 1 | self.linear_pixel(plane, i)));
+            }
+        }
+
+        let memory: &[u8] = &self.memory[..self.memory_size()];
+        out.extend_from_slice(&(memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(memory);
+
+        let checksum: u32 = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+
+        out
+    }
+
+    // The machine is only touched once the whole state has been validated, a bad state leaves it as it was
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() < MAGIC.len() + 2 {
+            return Err(StateError::Truncated);
+        }
+        if data[..4] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version: u16 = u16::from_le_bytes([data[4], data[5]]);
+        if version == 0 || version > VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let (body, checksum) = data.split_at(data.len() - 4);
+        if crc32(body) != u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) {
+            return Err(StateError::ChecksumMismatch);
+        }
+
+        let mut reader = Reader { data: body, pos: 6 };
+
+        let flags: u8 = reader.u8()?;
+        let quirks: Quirks = quirks_from_bits(reader.u8()?);
+
+        let pc: u16 = reader.u16()?;
+        let index_reg: u16 = reader.u16()?;
+        let mut v_reg: [u8; V_REG_SIZE] = [0; V_REG_SIZE];
+        v_reg.copy_from_slice(reader.bytes(V_REG_SIZE)?);
+        let mut stack: [u16; STACK_REG_SIZE] = [0; STACK_REG_SIZE];
+        for slot in stack.iter_mut() {
+            *slot = reader.u16()?;
+        }
+        let stack_pointer: u16 = reader.u8()? as u16;
+        let delay_timer: u8 = reader.u8()?;
+        let sound_timer: u8 = reader.u8()?;
+        let keypad: u16 = reader.u16()?;
+
+        let mut rpl: [u8; RPL_SIZE] = [0; RPL_SIZE];
+        rpl.copy_from_slice(reader.bytes(RPL_SIZE)?);
+        let mut audio_pattern: [u8; AUDIO_PATTERN_SIZE] = [0; AUDIO_PATTERN_SIZE];
+        audio_pattern.copy_from_slice(reader.bytes(AUDIO_PATTERN_SIZE)?);
+        let pitch: u8 = reader.u8()?;
+        let plane_mask: u8 = reader.u8()?;
+        let rng: Option<Prng> = if version >= 2 {Some(Prng::new(reader.u64()?))} else {None};
+
+        let mut packed_planes: [&[u8]; PLANES] = [&[]; PLANES];
+        for plane in packed_planes.iter_mut() {
+            *plane = reader.bytes(PACKED_PLANE_SIZE)?;
+        }
+
+        let memory_len: usize = reader.u32()? as usize;
+        let memory: &[u8] = reader.bytes(memory_len)?;
+
+        let xo_chip: bool = flags & FLAG_XO_CHIP != 0;
+        let expected_memory_len: usize = if xo_chip {XO_RAM_SIZE} else {RAM_SIZE};
+        if memory_len != expected_memory_len || stack_pointer as usize > STACK_REG_SIZE || plane_mask > 3 {
+            return Err(StateError::Corrupt);
+        }
+        if reader.pos != body.len() {
+            return Err(StateError::Corrupt);
+        }
+
+        // Everything checks out, overwrite the machine
+        self.pc = pc;
+        self.index_reg = index_reg;
+        self.v_reg = v_reg;
+        self.stack = stack;
+        self.stack_pointer = stack_pointer;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        for (key, down) in self.keypad.iter_mut().enumerate() {
+            *down = keypad & (1 << key) != 0;
+        }
+
+        self.rpl = rpl;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.plane_mask = plane_mask;
+        if let Some(rng) = rng {
+            self.rng = rng;
+        }
+
+        self.memory = memory.to_vec();
+
+        // The packed pixels depend on the resolution, so the screen goes in after it
+        self.hires = flags & FLAG_HIRES != 0;
+        self.clear_display();
+        for (plane, packed) in packed_planes.iter().enumerate() {
+            for (i, byte) in packed.iter().enumerate() {
+                for bit in 0..8 {
+                    self.set_linear_pixel(plane, i * 8 + bit, byte & (0x80 >> bit) != 0);
+                }
+            }
+        }
+        self.halted = flags & FLAG_HALTED != 0;
+        self.xo_chip = xo_chip;
+        self.vblank = flags & FLAG_VBLANK != 0;
+        self.quirks = quirks;
+
+        // New memory, and maybe a different amount of it
+        self.invalidate_all();
+
+        Ok(())
+    }
+}
+
+// Sequential little-endian reads that fail with Truncated instead of panicking
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let bytes: &[u8] = self.data.get(self.pos..self.pos + len).ok_or(StateError::Truncated)?;
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes: &[u8] = self.bytes(2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        let bytes: &[u8] = self.bytes(4)?;
+
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes: [u8; 8] = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+fn quirks_to_bits(quirks: Quirks) -> u8 {
+    (quirks.shift_uses_vy as u8)
+        | ((quirks.load_store_increments_i == LoadStoreIncrement::XPlusOne) as u8) << 1
+        | (quirks.jump_uses_vx as u8) << 2
+        | (quirks.logic_resets_vf as u8) << 3
+        | (quirks.clip_sprites as u8) << 4
+        | (quirks.display_wait as u8) << 5
+        | ((quirks.load_store_increments_i == LoadStoreIncrement::X) as u8) << 6
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    Quirks {
+        shift_uses_vy: bits & (1 << 0) != 0,
+        load_store_increments_i: match (bits & (1 << 1) != 0, bits & (1 << 6) != 0) {
+            (true, _) => LoadStoreIncrement::XPlusOne,
+            (false, true) => LoadStoreIncrement::X,
+            (false, false) => LoadStoreIncrement::None,
+        },
+        jump_uses_vx: bits & (1 << 2) != 0,
+        logic_resets_vf: bits & (1 << 3) != 0,
+        clip_sprites: bits & (1 << 4) != 0,
+        display_wait: bits & (1 << 5) != 0,
+    }
+}
+
+// CRC-32 (IEEE 802.3, the one used by zip and PNG), bit by bit since states are only saved now and then.
+// Public for the frontends that write PNGs or check states of their own
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask: u32 = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,237 @@
+// Execution tracer. While a sink is set, tick() hands it one TraceEntry per executed instruction: the cycle
+// number, where it ran, the opcode and what it decodes to, and the registers before and after.
+//
+// A sink is anything implementing TraceSink: a TraceWriter around a file (or any io::Write, std only), a TraceBuffer
+// keeping the last N entries, or a closure. Entries print as text for reading or as JSON lines for tools,
+// both one instruction per line so two runs can be diffed.
+
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+use crate::*;
+
+// The registers an instruction can change, recorded on both sides of it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuState {
+    pub v: [u8; V_REG_SIZE],
+    pub i: u16,
+    pub sp: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub cycle: u64,                 // instructions executed before this one since the last reset
+    pub pc: u16,                    // address the opcode was fetched from
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub before: CpuState,
+    pub after: CpuState,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    JsonLines,
+}
+
+pub trait TraceSink {
+    fn record(&mut self, entry: &TraceEntry);
+}
+
+// Callback sink
+impl<F: FnMut(&TraceEntry)> TraceSink for F {
+    fn record(&mut self, entry: &TraceEntry) {
+        self(entry)
+    }
+}
+
+// Lets the caller keep a handle to a sink it gave to the machine, e.g. to read a TraceBuffer while tracing
+impl<S: TraceSink> TraceSink for Rc<RefCell<S>> {
+    fn record(&mut self, entry: &TraceEntry) {
+        self.borrow_mut().record(entry)
+    }
+}
+
+impl TraceEntry {
+    // One line, without the newline
+    pub fn format(&self, format: TraceFormat) -> String {
+        match format {
+            TraceFormat::Text => self.to_string(),
+            TraceFormat::JsonLines => self.json(),
+        }
+    }
+
+    fn json(&self) -> String {
+        format!(
+            "{{\"cycle\":{},\"pc\":{},\"opcode\":{},\"mnemonic\":\"{}\",\"before\":{},\"after\":{}}}",
+            self.cycle, self.pc, self.opcode, escape_json(&self.instruction.to_string()),
+            self.before.json(), self.after.json()
+        )
+    }
+}
+
+// cycle, PC, opcode, mnemonic, then the registers before | after
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mnemonic: String = self.instruction.to_string();
+        write!(f, "{:>10} {:04X} {:04X} {:<20} {} | {}", self.cycle, self.pc, self.opcode, mnemonic, self.before, self.after)
+    }
+}
+
+impl CpuState {
+    fn json(&self) -> String {
+        let v: Vec<String> = self.v.iter().map(|value: &u8| value.to_string()).collect();
+        format!("{{\"v\":[{}],\"i\":{},\"sp\":{}}}", v.join(","), self.i, self.sp)
+    }
+}
+
+impl fmt::Display for CpuState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v: Vec<String> = self.v.iter().map(|value: &u8| format!("{:02X}", value)).collect();
+        write!(f, "V={} I={:04X} SP={:X}", v.join(" "), self.i, self.sp)
+    }
+}
+
+// The mnemonics never have anything else that needs escaping
+fn escape_json(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Writes every entry as a line. The first IO error stops the writing and is kept for error()
+#[cfg(feature = "std")]
+pub struct TraceWriter<W: Write> {
+    writer: W,
+    format: TraceFormat,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> TraceWriter<W> {
+    pub fn new(writer: W, format: TraceFormat) -> Self {
+        Self { writer, format, error: None }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    // Also where a caller that never checked error() finds out the trace is incomplete
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn record(&mut self, entry: &TraceEntry) {
+        if self.error.is_some() {
+            return;
+        }
+
+        if let Err(err) = writeln!(self.writer, "{}", entry.format(self.format)) {
+            self.error = Some(err);
+        }
+    }
+}
+
+// Keeps the last `capacity` entries, the usual way to see what led up to a crash without a huge file
+pub struct TraceBuffer {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl TraceBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: VecDeque::new(),
+        }
+    }
+
+    // Oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // Every entry, one per line
+    pub fn dump(&self, format: TraceFormat) -> String {
+        self.entries.iter().map(|entry: &TraceEntry| entry.format(format) + "\n").collect()
+    }
+}
+
+impl TraceSink for TraceBuffer {
+    fn record(&mut self, entry: &TraceEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(*entry);
+    }
+}
+
+impl Chip8 {
+    // Start tracing into sink, replacing the one set before
+    pub fn set_tracer(&mut self, sink: impl TraceSink + 'static) {
+        self.tracer = Some(Box::new(sink));
+    }
+
+    // Stop tracing and hand the sink back
+    pub fn take_tracer(&mut self) -> Option<Box<dyn TraceSink>> {
+        self.tracer.take()
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    // Instructions executed since the last reset
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub(crate) fn cpu_state(&self) -> CpuState {
+        CpuState {
+            v: self.v_reg,
+            i: self.index_reg,
+            sp: self.stack_pointer,
+        }
+    }
+
+    // Called by tick() after the instruction at pc ran, before the cycle count moves on
+    pub(crate) fn trace(&mut self, pc: u16, opcode: u16, before: CpuState) {
+        let entry: TraceEntry = TraceEntry {
+            cycle: self.cycles,
+            pc,
+            opcode,
+            instruction: Instruction::decode(opcode),
+            before,
+            after: self.cpu_state(),
+        };
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(&entry);
+        }
+    }
+}
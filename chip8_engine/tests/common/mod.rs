@@ -0,0 +1,97 @@
+// Shared harness for the integration tests. ROMs run headlessly the way the frontends run them, a frame at a
+// time through run_frame(), and the display is compared with golden snapshots in tests/snapshots.
+//
+// Snapshots are text dumps, one character per pixel (see PIXELS). Run the tests with UPDATE_SNAPSHOTS=1 to
+// write them instead of checking them, then look at the diff before committing it.
+
+#![allow(dead_code)]    // every test binary uses a different part of the harness
+
+use chip8_engine::*;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+pub const START_ADDRESS: u16 = 0x200;
+pub const MAX_FRAMES: usize = 600;          // 10 seconds, far longer than any of the test programs need
+
+const PIXELS: [char; 4] = ['.', '#', '+', '@'];
+
+// A machine with the quirks of a preset, XO-CHIP mode for the XO-CHIP preset, and a fixed seed
+pub fn machine(quirks: Quirks) -> Chip8 {
+    let mut chip8: Chip8 = Chip8::with_quirks(quirks);
+    chip8.set_xo_chip(quirks == Quirks::XOCHIP);
+    chip8.set_seed(0);
+
+    chip8
+}
+
+// Opcodes to ROM bytes, followed by a jump to itself so the program has a place to end
+pub fn program(ops: &[u16]) -> Vec<u8> {
+    let end: u16 = START_ADDRESS + 2 * ops.len() as u16;
+
+    ops.iter()
+        .chain(std::iter::once(&(0x1000 | end)))
+        .flat_map(|op| op.to_be_bytes())
+        .collect()
+}
+
+// Load a program built by program() and run it to its end
+pub fn run_program(quirks: Quirks, ops: &[u16]) -> Chip8 {
+    let mut chip8: Chip8 = machine(quirks);
+    chip8.load_rom(&program(ops)).unwrap();
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    chip8
+}
+
+// Run up to `frames` frames with the scripted key presses, stop early on 00FD or a jump to itself.
+// Returns the number of frames that ran
+pub fn run(chip8: &mut Chip8, frames: usize, keys: &[KeyPress]) -> Result<usize, Chip8Error> {
+    for frame in 0..frames {
+        for (key, down) in scripted_keypad(keys, frame as u64).into_iter().enumerate() {
+            chip8.set_keypad(key, down);
+        }
+
+        chip8.run_frame()?;
+
+        if chip8.is_halted() || chip8.is_spinning() {
+            return Ok(frame + 1);
+        }
+    }
+
+    Ok(frames)
+}
+
+pub fn screen_text(chip8: &Chip8) -> String {
+    let (width, height) = chip8.resolution();
+
+    (0..height)
+        .map(|y| (0..width).map(|x| PIXELS[chip8.pixel(x, y) as usize]).collect::<String>() + "\n")
+        .collect()
+}
+
+// Compare the display with tests/snapshots/<name>.txt
+pub fn assert_snapshot(name: &str, chip8: &Chip8) {
+    let path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.txt", name));
+    let actual: String = screen_text(chip8);
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(expected) => assert!(expected == actual, "{} does not match the display:\n{}", path.display(), actual),
+        Err(_) => panic!("no snapshot at {}, run with UPDATE_SNAPSHOTS=1 to record this display:\n{}", path.display(), actual),
+    }
+}
+
+// A public test ROM from tests/roms, see the README there
+pub fn public_rom(file: &str) -> Vec<u8> {
+    let path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms").join(file);
+
+    match fs::read(&path) {
+        Ok(rom) => rom,
+        Err(err) => panic!("unable to read {}: {} (see tests/roms/README.md)", path.display(), err),
+    }
+}
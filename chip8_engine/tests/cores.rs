@@ -0,0 +1,278 @@
+// The cached core and the recompiler against the interpreter. The same program has to leave the same state
+// after every frame, including programs that rewrite their own code
+
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use chip8_engine::*;
+use common::*;
+
+const RANDOM_PROGRAMS: usize = 300;
+const RANDOM_PROGRAM_SIZE: usize = 48;      // opcodes
+const RANDOM_FRAMES: usize = 60;
+const RANDOM_SPEEDS: [u32; 4] = [DEFAULT_SPEED, 700, 1000, 60];    // frames end at different points of a block
+
+const CORES: [Core; 3] = [Core::Interpreter, Core::Cached, Core::Recompiler];
+
+fn with_core(core: Core, quirks: Quirks, rom: &[u8]) -> Chip8 {
+    let mut chip8: Chip8 = machine(quirks);
+    chip8.set_core(core);
+    chip8.load_rom(rom).unwrap();
+
+    chip8
+}
+
+// What a program can change, cheaper to get at than a whole save state
+fn same_machine(a: &Chip8, b: &Chip8) -> bool {
+    a.pc() == b.pc()
+        && a.registers() == b.registers()
+        && a.index_register() == b.index_register()
+        && a.stack() == b.stack()
+        && (a.delay_timer(), a.sound_timer()) == (b.delay_timer(), b.sound_timer())
+        && a.memory() == b.memory()
+        && a.display_rows(0) == b.display_rows(0)
+        && a.display_rows(1) == b.display_rows(1)
+}
+
+// Run all cores side by side, frame by frame, until the first error
+fn compare(name: &str, quirks: Quirks, speed: u32, rom: &[u8], frames: usize) {
+    // On the heap, a machine has room for 64kB of memory
+    let mut machines: Vec<Chip8> = CORES.iter()
+        .map(|core: &Core| {
+            let mut chip8: Chip8 = with_core(*core, quirks, rom);
+            chip8.set_speed(speed);
+            chip8
+        })
+        .collect();
+    let (interpreter, others) = machines.split_first_mut().unwrap();
+
+    for frame in 0..frames {
+        let expected: Result<FrameResult, Chip8Error> = interpreter.run_frame();
+        for other in others.iter_mut() {
+            assert_eq!(other.run_frame(), expected, "{}, {:?}: frame {}", name, other.core(), frame);
+            assert!(same_machine(other, interpreter), "{}, {:?}: machines differ after frame {}", name, other.core(), frame);
+        }
+
+        if expected.is_err() {
+            break;
+        }
+    }
+
+    for other in others.iter() {
+        assert!(other.save_state() == interpreter.save_state(), "{}, {:?}: save states differ", name, other.core());
+    }
+}
+
+// Fast enough for the small programs below to run in one frame, so the recompiler runs whole blocks
+fn run_all(rom: &[u8]) -> Vec<Chip8> {
+    CORES.iter()
+        .map(|core: &Core| {
+            let mut chip8: Chip8 = with_core(*core, Quirks::default(), rom);
+            chip8.set_speed(100 * TIMER_HZ);
+            run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+            chip8
+        })
+        .collect()
+}
+
+fn to_bytes(ops: &[u16]) -> Vec<u8> {
+    ops.iter().flat_map(|op: &u16| op.to_be_bytes()).collect()
+}
+
+#[test]
+fn store_rewrites_an_instruction_already_run() {
+    // 0x204 runs as VB = 1, then F055 writes 7 over its second byte and it runs again as VB = 7
+    let rom: Vec<u8> = to_bytes(&[
+        0xA205, 0x6007,
+        0x6B01,                     // 0x204, patched to 6B07
+        0x7C01, 0x3C02, 0x120E,
+        0x120C,                     // 0x20C: done
+        0xF055, 0x1200,             // 0x20E: patch and start over
+    ]);
+
+    for chip8 in run_all(&rom) {
+        assert_eq!((chip8.registers()[0xB], chip8.registers()[0xC]), (7, 2), "{:?}", chip8.core());
+    }
+}
+
+#[test]
+fn bcd_rewrites_two_instructions() {
+    // BCD of 200 writes 2, 0, 0 from 0x205: 0x204 becomes VB = 2 and 0x206 becomes 0000
+    let rom: Vec<u8> = to_bytes(&[
+        0xA205, 0x60C8,
+        0x6B01,                     // 0x204, patched to 6B02
+        0x7D01,                     // 0x206, patched to 0000
+        0x7C01, 0x3C02, 0x1210,
+        0x120E,                     // 0x20E: done
+        0xF033, 0x1200,             // 0x210: patch and start over
+    ]);
+
+    for chip8 in run_all(&rom) {
+        let v: &[u8; 16] = chip8.registers();
+        assert_eq!((v[0xB], v[0xC], v[0xD]), (2, 2, 1), "{:?}", chip8.core());
+    }
+}
+
+#[test]
+fn loading_a_state_drops_the_cache() {
+    let first: Vec<u8> = program(&[0x6A01, 0x7B01]);
+    let second: Vec<u8> = program(&[0x6A02, 0x7B02]);
+
+    let mut other: Chip8 = machine(Quirks::default());
+    other.load_rom(&second).unwrap();
+    let state: Vec<u8> = other.save_state();
+
+    // Everything of the first program is cached or compiled by now
+    for core in [Core::Cached, Core::Recompiler] {
+        let mut chip8: Chip8 = with_core(core, Quirks::default(), &first);
+        run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+        chip8.load_state(&state).unwrap();
+        run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+        assert_eq!((chip8.registers()[0xA], chip8.registers()[0xB]), (2, 2), "{:?}", core);
+    }
+}
+
+#[test]
+fn switching_cores_mid_run() {
+    let rom: Vec<u8> = program(&[0x6005, 0x7001, 0x3010, 0x1202]);
+    let mut chip8: Chip8 = with_core(Core::Cached, Quirks::default(), &rom);
+
+    chip8.run_frame().unwrap();
+    chip8.set_core(Core::Recompiler);
+    chip8.run_frame().unwrap();
+    chip8.set_core(Core::Interpreter);
+    chip8.run_frame().unwrap();
+    chip8.set_core(Core::Cached);
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    assert_eq!(chip8.registers()[0], 0x10);
+}
+
+#[test]
+fn blocks_are_cut_at_the_end_of_a_frame() {
+    // A 20 instruction block at 1 instruction per frame: every frame stops inside it and the next one goes on
+    // from there
+    let mut ops: Vec<u16> = (0..20).map(|idx: u16| 0x7001 | (idx % 4) << 8).collect();
+    ops.push(0x1200);
+    let rom: Vec<u8> = to_bytes(&ops);
+
+    let mut chip8: Chip8 = with_core(Core::Recompiler, Quirks::default(), &rom);
+    chip8.set_speed(60);
+    for _ in 0..7 {
+        chip8.run_frame().unwrap();
+    }
+
+    assert_eq!(chip8.pc(), 0x20E);
+    assert_eq!(chip8.cycles(), 7);
+    assert_eq!(&chip8.registers()[..4], &[2, 2, 2, 1]);
+}
+
+#[test]
+fn block_at_the_top_of_memory() {
+    // 0xFFFC: VB = 1, 0xFFFE: VC += 1, and the program counter wraps round to a jump to itself at 0x0000. The
+    // block ends at 0xFFFE, rewriting that instruction has to drop it like anywhere else
+    for core in CORES {
+        let mut chip8: Chip8 = with_core(core, Quirks::XOCHIP, &[]);
+        for (addr, byte) in [(0xFFFC, 0x6B), (0xFFFD, 0x01), (0xFFFE, 0x7C), (0xFFFF, 0x01), (0x0000, 0x10), (0x0001, 0x00)] {
+            chip8.set_memory(addr, byte).unwrap();
+        }
+
+        chip8.set_pc(0xFFFC);
+        chip8.run_frame().unwrap();
+        assert_eq!((chip8.pc(), chip8.registers()[0xC]), (0x0000, 1), "{:?}", core);
+
+        chip8.set_memory(0xFFFF, 0x05).unwrap();
+        chip8.set_pc(0xFFFC);
+        chip8.run_frame().unwrap();
+        assert_eq!(chip8.registers()[0xC], 6, "{:?}", core);
+    }
+}
+
+#[test]
+fn tracing_runs_the_recompiler_an_instruction_at_a_time() {
+    let rom: Vec<u8> = program(&[0x6005, 0x7001, 0x3010, 0x1202]);
+    let mut chip8: Chip8 = with_core(Core::Recompiler, Quirks::default(), &rom);
+    let buffer: Rc<RefCell<TraceBuffer>> = Rc::new(RefCell::new(TraceBuffer::new(100_000)));
+
+    chip8.set_tracer(buffer.clone());
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    assert_eq!(chip8.registers()[0], 0x10);
+    assert_eq!(buffer.borrow().len() as u64, chip8.cycles());
+}
+
+// A small xorshift so the programs are the same on every run
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u16 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 32) as u16
+    }
+}
+
+// Opcodes that mostly do something: jumps, calls and I stay inside the program, so Fx55, Fx33 and 5xy2
+// write over the code that is running
+fn random_program(random: &mut Random) -> Vec<u8> {
+    let size: u16 = RANDOM_PROGRAM_SIZE as u16 * 2;
+    let ops: Vec<u16> = (0..RANDOM_PROGRAM_SIZE)
+        .map(|_| {
+            let value: u16 = random.next();
+            let address: u16 = 0x200 + random.next() % size;
+            let (x, y) = ((value >> 8) & 0xF, (value >> 4) & 0xF);
+
+            match random.next() % 16 {
+                0 => 0x1000 | (address & !1),
+                1 => 0x2000 | (address & !1),
+                2 => [0x00EE, 0x00E0, 0x00FB, 0x00C2][value as usize % 4],
+                3 => 0x3000 | (value & 0x0FFF),
+                4 => 0x4000 | (value & 0x0FFF),
+                5 => 0x5000 | (value & 0x0FF0) | [0, 2, 3][value as usize % 3],
+                6 => 0x6000 | (value & 0x0FFF),
+                7 => 0x7000 | (value & 0x0FFF),
+                8 => 0x8000 | (value & 0x0FF0) | [0, 1, 2, 3, 4, 5, 6, 7, 0xE][value as usize % 9],
+                9 => 0xA000 | address,
+                10 => 0xC000 | (value & 0x0FFF),
+                11 => 0xD000 | (value & 0x0FFF),
+                12 => 0xE09E | x << 8,
+                13 => 0xF000 | x << 8 | [0x07, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65][y as usize % 8],
+                _ => 0x9000 | (value & 0x0FF0),
+            }
+        })
+        .collect();
+
+    to_bytes(&ops)
+}
+
+#[test]
+fn random_programs_match() {
+    let mut random: Random = Random(0x5EED_CAFE_F00D_0001);
+
+    for idx in 0..RANDOM_PROGRAMS {
+        let rom: Vec<u8> = random_program(&mut random);
+        let quirks: Quirks = [Quirks::VIP, Quirks::SCHIP, Quirks::XOCHIP][idx % 3];
+        let speed: u32 = RANDOM_SPEEDS[idx % RANDOM_SPEEDS.len()];
+
+        compare(&format!("program {}", idx), quirks, speed, &rom, RANDOM_FRAMES);
+    }
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn public_roms_match() {
+    let roms: [(&str, Quirks); 4] = [
+        ("3-corax+.ch8", Quirks::default()),
+        ("4-flags.ch8", Quirks::default()),
+        ("5-quirks.ch8", Quirks::XOCHIP),
+        ("BC_test.ch8", Quirks::default()),
+    ];
+
+    for (file, quirks) in roms {
+        compare(file, quirks, DEFAULT_SPEED, &public_rom(file), MAX_FRAMES);
+    }
+}
@@ -0,0 +1,199 @@
+// Breakpoints, watchpoints and stepping on a program with one subroutine call
+
+use chip8_engine::*;
+
+const BUDGET: usize = 100;
+
+// 0x200  V0 = 5, I = 0x300, call 0x210, store V0 at I, load V0 from I, spin
+// 0x210  V1 = 7, V2 = 8, return
+const PROGRAM: [u16; 11] = [
+    0x6005, 0xA300, 0x2210, 0xF055, 0xF065, 0x120A, 0x0000, 0x0000,
+    0x6107, 0x6208, 0x00EE,
+];
+
+fn machine() -> Chip8 {
+    let rom: Vec<u8> = PROGRAM.iter().flat_map(|op: &u16| op.to_be_bytes()).collect();
+    let mut chip8: Chip8 = Chip8::with_seed(0);
+    chip8.load_rom(&rom).unwrap();
+
+    chip8
+}
+
+#[test]
+fn breakpoint() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.add_breakpoint(0x204);
+
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Breakpoint(0x204)));
+    assert_eq!(chip8.pc(), 0x204);
+    assert!(debugger.is_paused());
+
+    // Paused, nothing runs
+    assert_eq!(debugger.run(&mut chip8, BUDGET), None);
+    assert_eq!(chip8.pc(), 0x204);
+
+    // Resuming passes over the breakpoint it stopped on
+    debugger.resume();
+    assert_eq!(debugger.run(&mut chip8, 1), None);
+    assert_eq!(chip8.pc(), 0x210);
+}
+
+#[test]
+fn write_watchpoint() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.watch_memory(0x300, Access::Write);
+
+    // The store has executed, the load that reads the same byte does not stop
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Watchpoint { addr: 0x300, access: Access::Write }));
+    assert_eq!(chip8.pc(), 0x208);
+    assert_eq!(chip8.memory()[0x300], 5);
+
+    debugger.resume();
+    assert_eq!(debugger.run(&mut chip8, BUDGET), None);
+}
+
+#[test]
+fn read_watchpoint() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.watch_memory(0x300, Access::Read);
+
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Watchpoint { addr: 0x300, access: Access::Read }));
+    assert_eq!(chip8.pc(), 0x20A);
+}
+
+#[test]
+fn read_write_watchpoint() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.watch_memory(0x300, Access::ReadWrite);
+
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Watchpoint { addr: 0x300, access: Access::Write }));
+    debugger.resume();
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Watchpoint { addr: 0x300, access: Access::Read }));
+
+    debugger.unwatch_memory(0x300);
+    assert!(debugger.watchpoints().is_empty());
+}
+
+#[test]
+fn register_watchpoint() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.watch_register(1);
+
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Register { reg: 1, old: 0, new: 7 }));
+    assert_eq!(chip8.pc(), 0x212);
+}
+
+#[test]
+fn step() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.pause();
+
+    debugger.step();
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Step));
+    assert_eq!(chip8.pc(), 0x202);
+
+    // Into the subroutine
+    debugger.step();
+    debugger.run(&mut chip8, BUDGET);
+    debugger.step();
+    debugger.run(&mut chip8, BUDGET);
+    assert_eq!(chip8.pc(), 0x210);
+    assert_eq!(chip8.stack(), [0x206]);
+}
+
+#[test]
+fn step_over_a_call() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.add_breakpoint(0x204);
+    debugger.run(&mut chip8, BUDGET);
+
+    // The whole subroutine runs, it stops on the instruction after the 2nnn
+    debugger.step_over(&chip8);
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Step));
+    assert_eq!(chip8.pc(), 0x206);
+    assert_eq!(&chip8.registers()[1..3], [7, 8]);
+    assert!(chip8.stack().is_empty());
+
+    // Anything other than a call is a single step
+    debugger.step_over(&chip8);
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Step));
+    assert_eq!(chip8.pc(), 0x208);
+}
+
+#[test]
+fn step_out() {
+    let mut chip8: Chip8 = machine();
+    let mut debugger: Debugger = Debugger::new();
+    debugger.add_breakpoint(0x212);
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Breakpoint(0x212)));
+
+    debugger.step_out(&chip8);
+    assert_eq!(debugger.run(&mut chip8, BUDGET), Some(Stop::Step));
+    assert_eq!(chip8.pc(), 0x206);
+    assert_eq!(chip8.registers()[2], 8);
+    assert!(chip8.stack().is_empty());
+
+    // Outside of a subroutine there is nothing to step out of, it just runs
+    debugger.step_out(&chip8);
+    assert_eq!(debugger.run(&mut chip8, BUDGET), None);
+    assert!(!debugger.is_paused());
+}
+
+#[test]
+fn setters_ignore_out_of_range() {
+    let mut chip8: Chip8 = machine();
+    let before: Vec<u8> = chip8.save_state();
+
+    chip8.set_register(16, 1);
+    chip8.set_keypad(16, true);
+    chip8.set_keypad(usize::MAX, true);
+    assert!(chip8.save_state() == before);
+
+    chip8.set_register(0xF, 1);
+    chip8.set_keypad(0xF, true);
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert!(chip8.save_state() != before);
+}
+
+#[test]
+fn opcode_at_and_spinning() {
+    let mut chip8: Chip8 = machine();
+
+    assert_eq!(chip8.opcode_at(0x204), 0x2210);
+    assert_eq!(chip8.opcode_at(0x0FFF), 0);                 // half past the end of memory
+    assert!(!chip8.is_spinning());
+
+    chip8.set_pc(0x20A);
+    assert!(chip8.is_spinning());
+}
+
+// With nothing to stop on the frame runs on the machine's core, anything set later still stops it
+#[test]
+fn frames_on_every_core() {
+    for core in [Core::Interpreter, Core::Cached, Core::Recompiler] {
+        let mut chip8: Chip8 = machine();
+        chip8.set_core(core);
+        let mut debugger: Debugger = Debugger::new();
+
+        assert!(debugger.run_frame(&mut chip8).is_ok());
+        assert_eq!((chip8.pc(), chip8.memory()[0x300]), (0x20A, 5), "{:?}", core);
+
+        debugger.add_breakpoint(0x20A);
+        assert_eq!(debugger.run_frame(&mut chip8), Err(Stop::Breakpoint(0x20A)), "{:?}", core);
+    }
+
+    let mut chip8: Chip8 = Chip8::with_seed(0);
+    chip8.load_rom(&[0x60, 0x01, 0xE0, 0xFF]).unwrap();
+    chip8.set_core(Core::Recompiler);
+    let mut debugger: Debugger = Debugger::new();
+
+    assert!(matches!(debugger.run_frame(&mut chip8), Err(Stop::Error(_))));
+    assert!(debugger.is_paused());
+}
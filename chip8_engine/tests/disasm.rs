@@ -0,0 +1,27 @@
+// Instruction::decode and encode against each other over the whole opcode space, and the listing of the one
+// instruction that is two words long
+
+use chip8_engine::*;
+
+#[test]
+fn every_opcode_round_trips() {
+    for op in 0..=u16::MAX {
+        let instruction: Instruction = Instruction::decode(op);
+
+        match instruction {
+            Instruction::Unknown(unknown) => assert_eq!(unknown, op),
+            _ => assert_eq!(instruction.encode(), op, "{:04X} decodes to {:?}", op, instruction),
+        }
+    }
+}
+
+#[test]
+fn long_load_shows_its_address() {
+    let rom: [u8; 6] = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0];
+
+    assert_eq!(disassemble(&rom, 0x200, Syntax::Cowgod), "0200: F000 1234  LD I, 0x1234\n0204: 00E0       CLS\n");
+    assert_eq!(disassemble(&rom, 0x200, Syntax::Octo), "0200: F000 1234  i := long 0x1234\n0204: 00E0       clear\n");
+
+    // Cut off before its address, there is nothing to show but the opcode
+    assert_eq!(disassemble(&rom[..2], 0x200, Syntax::Cowgod), "0200: F000       LD I, nnnn\n");
+}
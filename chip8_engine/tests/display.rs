@@ -0,0 +1,101 @@
+// Drawing, scrolling and the XO-CHIP bitplanes, checked against the golden snapshots in tests/snapshots
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+// Every hex digit of the font in a 4x4 grid: V0 = digit, V1/V2 = position
+fn font_grid(font_op: u16, height: u16, step_x: u16, step_y: u16) -> Vec<u16> {
+    (0..16)
+        .flat_map(|digit: u16| [
+            0x6000 | digit,
+            font_op,
+            0x6100 | ((digit % 4) * step_x + 2),
+            0x6200 | ((digit / 4) * step_y + 1),
+            0xD120 | height,
+        ])
+        .collect()
+}
+
+#[test]
+fn font() {
+    let chip8: Chip8 = run_program(Quirks::default(), &font_grid(0xF029, 5, 8, 7));
+    assert_snapshot("font", &chip8);
+}
+
+#[test]
+fn big_font_in_hires() {
+    let ops: Vec<u16> = [vec![0x00FF], font_grid(0xF030, 10, 16, 12)].concat();
+    let chip8: Chip8 = run_program(Quirks::SCHIP, &ops);
+
+    assert_eq!(chip8.resolution(), (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT));
+    assert_snapshot("big_font", &chip8);
+}
+
+#[test]
+fn collision_sets_vf() {
+    let chip8: Chip8 = run_program(Quirks::default(), &[0xA000, 0xD005, 0x8AF0, 0xD005]);
+
+    assert_eq!(chip8.registers()[0xA], 0);
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert!(screen_text(&chip8).chars().all(|pixel| pixel != '#'));
+}
+
+#[test]
+fn sixteen_by_sixteen_sprite() {
+    // Dxy0 in hi-res draws 16x16 from 32 bytes, here the first 32 bytes of the font
+    let chip8: Chip8 = run_program(Quirks::SCHIP, &[0x00FF, 0xA000, 0x6038, 0x6118, 0xD010]);
+    assert_snapshot("sprite_16x16", &chip8);
+}
+
+#[test]
+fn scrolling() {
+    // Down by 3, right by 4, then left by 4 twice
+    let ops: Vec<u16> = [font_grid(0xF029, 5, 8, 7), vec![0x00C3, 0x00FB, 0x00FC, 0x00FC]].concat();
+    let chip8: Chip8 = run_program(Quirks::SCHIP, &ops);
+
+    assert_snapshot("scrolled", &chip8);
+}
+
+#[test]
+fn xo_chip_bitplanes() {
+    // Plane 1 gets 0, plane 2 gets 1 (the next 5 bytes), both planes selected together get 2 and 3,
+    // then 00D2 scrolls plane 2 up by 2
+    let chip8: Chip8 = run_program(Quirks::XOCHIP, &[
+        0x6102, 0x6202,
+        0xF101, 0xA000, 0xD125,
+        0x6110, 0xF201, 0xA005, 0xD125,
+        0x6120, 0xF301, 0xA00A, 0xD125,
+        0xF201, 0x00D2,
+    ]);
+
+    assert_snapshot("bitplanes", &chip8);
+}
+
+#[test]
+fn dirty_rows_follow_drawing() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0xA000, 0x6004, 0xD005])).unwrap();
+    chip8.take_dirty_rows();
+
+    // Only the 5 rows the sprite covers, and nothing once the program just spins
+    assert_eq!(chip8.run_frame().unwrap().dirty_rows, 0b11111 << 4);
+    assert_eq!(chip8.run_frame().unwrap().dirty_rows, 0);
+}
+
+#[test]
+fn pixels_outside_the_screen_are_off() {
+    // A font 0 at 100,40 in hi-res
+    let mut chip8: Chip8 = run_program(Quirks::SCHIP, &[0x00FF, 0x6064, 0x6128, 0xA000, 0xD015]);
+    assert_eq!(chip8.pixel(100, 40), 1);
+
+    assert_eq!(chip8.pixel(HIRES_SCREEN_WIDTH, 40), 0);
+    assert_eq!(chip8.pixel(100, HIRES_SCREEN_HEIGHT), 0);
+    assert_eq!(chip8.pixel(usize::MAX, usize::MAX), 0);
+
+    // Back in lo-res the same coordinates are past the edge
+    chip8.execute(0x00FE).unwrap();
+    assert_eq!(chip8.pixel(100, 40), 0);
+    assert_eq!(chip8.pixel(SCREEN_WIDTH, 0), 0);
+}
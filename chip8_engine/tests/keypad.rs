@@ -0,0 +1,77 @@
+// Ex9E, ExA1 and Fx0A against scripted key presses, like the keypad test ROM
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+fn run_with_keys(ops: &[u16], keys: &[KeyPress]) -> Chip8 {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(ops)).unwrap();
+    run(&mut chip8, MAX_FRAMES, keys).unwrap();
+
+    chip8
+}
+
+#[test]
+fn skip_if_key_down() {
+    let ops: [u16; 3] = [0x6005, 0xE09E, 0x6101];
+
+    let chip8: Chip8 = run_with_keys(&ops, &[KeyPress { frame: 0, key: 5, hold: 1 }]);
+    assert_eq!(chip8.registers()[1], 0);
+
+    let chip8: Chip8 = run_with_keys(&ops, &[KeyPress { frame: 0, key: 6, hold: 1 }]);
+    assert_eq!(chip8.registers()[1], 1);
+}
+
+#[test]
+fn skip_if_key_up() {
+    let ops: [u16; 3] = [0x6005, 0xE0A1, 0x6101];
+
+    let chip8: Chip8 = run_with_keys(&ops, &[]);
+    assert_eq!(chip8.registers()[1], 0);
+
+    let chip8: Chip8 = run_with_keys(&ops, &[KeyPress { frame: 0, key: 5, hold: 1 }]);
+    assert_eq!(chip8.registers()[1], 1);
+}
+
+#[test]
+fn wait_for_key() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0xF30A, 0x6401])).unwrap();
+    let keys: [KeyPress; 1] = [KeyPress { frame: 20, key: 0xB, hold: 1 }];
+
+    // Nothing happens until the key goes down...
+    run(&mut chip8, 20, &keys).unwrap();
+    assert_eq!(chip8.pc(), START_ADDRESS);
+    assert_eq!(chip8.registers()[4], 0);
+
+    // ...and then the key lands in V3
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0xF30A, 0x6401])).unwrap();
+    let frames: usize = run(&mut chip8, MAX_FRAMES, &keys).unwrap();
+
+    assert_eq!(frames, 21);
+    assert_eq!(&chip8.registers()[3..5], &[0xB, 1]);
+}
+
+#[test]
+fn invalid_key_is_an_error() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0x6020, 0xE09E])).unwrap();
+
+    assert_eq!(run(&mut chip8, 1, &[]), Err(Chip8Error::InvalidKey));
+}
+
+#[test]
+fn scripted_presses() {
+    let presses: [KeyPress; 3] = [
+        KeyPress { frame: 2, key: 0x7, hold: 2 },
+        KeyPress { frame: 3, key: 0x7, hold: 2 },              // overlaps, so 7 stays down one frame longer
+        KeyPress { frame: 0, key: 0x10, hold: u64::MAX },      // no such key
+    ];
+    let down: Vec<bool> = (0..6).map(|frame: u64| scripted_keypad(&presses, frame)[0x7]).collect();
+
+    assert_eq!(down, [false, false, true, true, true, false]);
+    assert_eq!(scripted_keypad(&presses, 3).iter().filter(|down: &&bool| **down).count(), 1);
+}
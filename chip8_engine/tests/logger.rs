@@ -0,0 +1,46 @@
+// The diagnostics hook
+
+mod common;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use chip8_engine::*;
+use common::*;
+
+// Run the program and return what was logged
+fn logged(core: Core, quirks: Quirks, ops: &[u16]) -> (Vec<String>, Chip8) {
+    let lines: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+    let sink: Rc<RefCell<Vec<String>>> = lines.clone();
+
+    let mut chip8: Chip8 = machine(quirks);
+    chip8.set_core(core);
+    chip8.load_rom(&program(ops)).unwrap();
+    chip8.set_logger(move |args: fmt::Arguments| sink.borrow_mut().push(args.to_string()));
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    (lines.take(), chip8)
+}
+
+#[test]
+fn overflows_are_logged() {
+    // V3 = 0xF0, then V3 += 0x20 wraps, V3 += 1 does not
+    let ops: [u16; 3] = [0x63F0, 0x7320, 0x7301];
+
+    for core in [Core::Interpreter, Core::Cached, Core::Recompiler] {
+        let (lines, _) = logged(core, Quirks::default(), &ops);
+        assert_eq!(lines, ["OVERFLOW: opcode 7xnn, x=3, nn=32"], "{:?}", core);
+    }
+}
+
+#[test]
+fn index_overflow_is_logged() {
+    let (lines, _) = logged(Core::Interpreter, Quirks::default(), &[0x6010, 0xA000, 0xF01E]);
+    assert!(lines.is_empty());
+
+    // I = 0xFFF0 only fits with XO-CHIP's F000 nnnn
+    let (lines, chip8) = logged(Core::Interpreter, Quirks::XOCHIP, &[0x6010, 0xF000, 0xFFF0, 0xF01E]);
+    assert_eq!(lines, ["OVERFLOW: opcode Fx1E, x=0"]);
+    assert_eq!(chip8.index_register(), 0);
+}
@@ -0,0 +1,279 @@
+// Every opcode on its own, along the lines of the corax+ and flags test ROMs but checking the registers
+// directly instead of reading a result off the screen
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+fn run_default(ops: &[u16]) -> Chip8 {
+    run_program(Quirks::default(), ops)
+}
+
+#[test]
+fn load_and_add_immediate() {
+    let chip8: Chip8 = run_default(&[0x6A12, 0x7A34, 0x6BFF, 0x7B02, 0x6F07, 0x7FFF]);
+
+    assert_eq!(chip8.registers()[0xA], 0x46);
+    assert_eq!(chip8.registers()[0xB], 0x01);       // 7xnn wraps around...
+    assert_eq!(chip8.registers()[0xF], 0x06);       // ...and never touches VF
+}
+
+#[test]
+fn register_logic() {
+    let chip8: Chip8 = run_default(&[
+        0x60F0, 0x613C,
+        0x8200, 0x8211,     // V2 = V0 | V1
+        0x8300, 0x8312,     // V3 = V0 & V1
+        0x8400, 0x8413,     // V4 = V0 ^ V1
+    ]);
+
+    assert_eq!(&chip8.registers()[2..5], &[0xFC, 0x30, 0xCC]);
+}
+
+#[test]
+fn add_and_subtract_set_carry_and_borrow() {
+    let chip8: Chip8 = run_default(&[
+        0x60FF, 0x6102, 0x8014, 0x8AF0,     // 0xFF + 2 carries
+        0x6220, 0x6310, 0x8234, 0x8BF0,     // 0x20 + 0x10 does not
+        0x6410, 0x6520, 0x8455, 0x8CF0,     // 0x10 - 0x20 borrows, VF = 0
+        0x6620, 0x6710, 0x8675, 0x8DF0,     // 0x20 - 0x10 does not, VF = 1
+        0x6810, 0x6920, 0x8897, 0x8EF0,     // 8xy7: 0x20 - 0x10, VF = 1
+    ]);
+    let v: &[u8; 16] = chip8.registers();
+
+    assert_eq!((v[0x0], v[0xA]), (0x01, 1));
+    assert_eq!((v[0x2], v[0xB]), (0x30, 0));
+    assert_eq!((v[0x4], v[0xC]), (0xF0, 0));
+    assert_eq!((v[0x6], v[0xD]), (0x10, 1));
+    assert_eq!((v[0x8], v[0xE]), (0x10, 1));
+}
+
+#[test]
+fn shifts_set_the_shifted_out_bit() {
+    let chip8: Chip8 = run_default(&[
+        0x6005, 0x8006, 0x8AF0,             // 5 >> 1, VF = 1
+        0x6181, 0x811E, 0x8BF0,             // 0x81 << 1, VF = 1
+        0x6240, 0x822E, 0x8CF0,             // 0x40 << 1, VF = 0
+    ]);
+    let v: &[u8; 16] = chip8.registers();
+
+    assert_eq!((v[0x0], v[0xA]), (0x02, 1));
+    assert_eq!((v[0x1], v[0xB]), (0x02, 1));
+    assert_eq!((v[0x2], v[0xC]), (0x80, 0));
+}
+
+// The flags test: with VF as the destination the flag wins over the result
+#[test]
+fn vf_as_destination_holds_the_flag() {
+    let chip8: Chip8 = run_default(&[0x6F03, 0x8FF6]);
+    assert_eq!(chip8.registers()[0xF], 1);
+}
+
+// 8xy4/8xy5/8xy7 used to write VF before the result, so VF as the destination ended up with the result.
+// The recompiler has its own 8xy4, so every core is checked
+#[test]
+fn vf_as_destination_holds_the_carry_and_borrow() {
+    let cases: [(&[u16], u8); 4] = [
+        (&[0x6FFF, 0x6101, 0x8F14], 1),     // 0xFF + 1 = 0x00, carries
+        (&[0x6F01, 0x6101, 0x8F14], 0),     // 0x01 + 1 = 0x02, no carry
+        (&[0x6F01, 0x6102, 0x8F15], 0),     // 0x01 - 2 = 0xFF, borrows
+        (&[0x6F01, 0x6102, 0x8F17], 1),     // 2 - 0x01 = 0x01, no borrow
+    ];
+
+    for core in [Core::Interpreter, Core::Cached, Core::Recompiler] {
+        for (ops, flag) in cases {
+            let mut chip8: Chip8 = machine(Quirks::default());
+            chip8.set_core(core);
+            chip8.load_rom(&program(ops)).unwrap();
+            run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+            assert_eq!(chip8.registers()[0xF], flag, "{:?}, {:04X?}", core, ops);
+        }
+    }
+}
+
+#[test]
+fn skips() {
+    // Every skip that should happen jumps over a 6xnn that would set V1 - V4
+    let chip8: Chip8 = run_default(&[
+        0x6010, 0x6A10,
+        0x3010, 0x6101,     // 3xnn: equal
+        0x4011, 0x6201,     // 4xnn: not equal
+        0x50A0, 0x6301,     // 5xy0: equal registers
+        0x9010, 0x6401,     // 9xy0: V0 != V1 (V1 is still 0)
+        0x3011, 0x6501,     // not skipped
+    ]);
+
+    assert_eq!(&chip8.registers()[1..6], &[0, 0, 0, 0, 1]);
+}
+
+#[test]
+fn call_and_return() {
+    // 0x200 call 0x208, 0x202 V1 = 1, 0x204 jump to the end, 0x208 V0 = 1, return
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&[0x22, 0x08, 0x61, 0x01, 0x12, 0x06, 0x12, 0x06, 0x60, 0x01, 0x00, 0xEE]).unwrap();
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    assert_eq!(&chip8.registers()[..2], &[1, 1]);
+    assert!(chip8.stack().is_empty());
+}
+
+#[test]
+fn stack_errors() {
+    // 2200: call itself until the stack is full
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&[0x22, 0x00]).unwrap();
+    assert_eq!(run(&mut chip8, MAX_FRAMES, &[]), Err(Chip8Error::StackOverflow));
+
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&[0x00, 0xEE]).unwrap();
+    assert_eq!(run(&mut chip8, MAX_FRAMES, &[]), Err(Chip8Error::StackUnderflow));
+}
+
+#[test]
+fn jump_with_offset() {
+    // B206 lands on 0x206 + V0 = 0x208, skipping the V1 load at 0x206
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&[0x60, 0x02, 0xB2, 0x06, 0x12, 0x04, 0x61, 0x01, 0x62, 0x01, 0x12, 0x0A]).unwrap();
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    assert_eq!(&chip8.registers()[1..3], &[0, 1]);
+}
+
+#[test]
+fn index_register() {
+    let chip8: Chip8 = run_default(&[0xA123, 0x6010, 0xF01E]);
+    assert_eq!(chip8.index_register(), 0x133);
+
+    // Fx29 points at the 5 byte font character
+    let chip8: Chip8 = run_default(&[0x600A, 0xF029]);
+    assert_eq!(chip8.index_register(), 50);
+}
+
+#[test]
+fn bcd_store_and_load() {
+    let chip8: Chip8 = run_default(&[
+        0x60FE, 0xA300, 0xF033,             // 254 -> 2 5 4 at 0x300
+        0x61AA, 0x62BB, 0xA310, 0xF255,     // V0 - V2 to 0x310
+        0xA300, 0xF265,                     // and the BCD digits back into V0 - V2
+    ]);
+
+    assert_eq!(&chip8.memory()[0x300..0x303], &[2, 5, 4]);
+    assert_eq!(&chip8.memory()[0x310..0x313], &[0xFE, 0xAA, 0xBB]);
+    assert_eq!(&chip8.registers()[..3], &[2, 5, 4]);
+}
+
+#[test]
+fn timers_count_down_at_60_hz() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0x601E, 0xF015, 0xF018])).unwrap();
+
+    run(&mut chip8, 1, &[]).unwrap();
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (29, 29));
+    assert!(chip8.sound_active());
+
+    for _ in 0..29 {
+        chip8.run_frame().unwrap();
+    }
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+    assert!(!chip8.sound_active());
+}
+
+#[test]
+fn run_frame_with_hands_out_a_frame_of_instructions() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0x6010, 0xF015, 0x7101, 0x1204])).unwrap();
+    chip8.set_speed(700);
+
+    // 700 per second is 11 or 12 a frame, the timers count down after each frame
+    let mut budgets: Vec<usize> = Vec::new();
+    for _ in 0..3 {
+        chip8.run_frame_with(|chip8: &mut Chip8, instructions: usize| {
+            budgets.push(instructions);
+            (0..instructions).try_for_each(|_| chip8.tick())
+        }).unwrap();
+    }
+    assert_eq!(budgets, [11, 12, 12]);
+    assert_eq!(chip8.delay_timer(), 0x10 - 3);
+
+    // Stopped early, the frame does not count
+    let stopped: Result<FrameResult, &str> = chip8.run_frame_with(|chip8: &mut Chip8, _| {
+        chip8.tick().unwrap();
+        Err("stop")
+    });
+    assert_eq!(stopped, Err("stop"));
+    assert_eq!((chip8.delay_timer(), chip8.cycles()), (0x10 - 3, 36));
+}
+
+#[test]
+fn read_delay_timer() {
+    // Wait in a loop until the delay timer runs out, counting in V1
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0x600A, 0xF015, 0xF007, 0x7101, 0x3000, 0x1204])).unwrap();
+    let frames: usize = run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    assert_eq!(frames, 11);     // 10 frames to run out, the 11th sees 0 and ends
+    assert_eq!(chip8.registers()[0], 0);
+}
+
+#[test]
+fn random_is_masked() {
+    let chip8: Chip8 = run_default(&[0xC000, 0xC10F, 0xC10F, 0xC10F, 0xC10F]);
+
+    assert_eq!(chip8.registers()[0], 0);
+    assert!(chip8.registers()[1] <= 0x0F);
+}
+
+// V0 after each of `count` C0FF
+fn randoms(chip8: &mut Chip8, count: usize) -> Vec<u8> {
+    (0..count)
+        .map(|_| {
+            chip8.execute(0xC0FF).unwrap();
+            chip8.registers()[0]
+        })
+        .collect()
+}
+
+#[test]
+fn random_follows_the_seed() {
+    for seed in [0, 1, 0xC8] {
+        let sequence: Vec<u8> = randoms(&mut Chip8::with_seed(seed), 32);
+
+        assert_eq!(randoms(&mut Chip8::with_seed(seed), 32), sequence, "seed {}", seed);
+        assert_ne!(randoms(&mut Chip8::with_seed(seed + 1), 32), sequence, "seed {}", seed);
+    }
+}
+
+#[test]
+fn random_resumes_from_a_save_state() {
+    let mut chip8: Chip8 = Chip8::with_seed(42);
+    randoms(&mut chip8, 10);
+    let state: Vec<u8> = chip8.save_state();
+    assert_eq!(u16::from_le_bytes([state[4], state[5]]), 2);
+
+    let expected: Vec<u8> = randoms(&mut chip8, 32);
+
+    let mut loaded: Chip8 = Chip8::with_seed(7);
+    loaded.load_state(&state).unwrap();
+    assert_eq!(randoms(&mut loaded, 32), expected);
+}
+
+#[test]
+fn unknown_opcode_is_an_error() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&[0x60, 0x01, 0xE0, 0x00]).unwrap();
+
+    assert_eq!(run(&mut chip8, 1, &[]), Err(Chip8Error::UnknownOpcode { op: 0xE000, pc: 0x202 }));
+}
+
+#[test]
+fn exit_halts() {
+    let mut chip8: Chip8 = machine(Quirks::SCHIP);
+    chip8.load_rom(&[0x60, 0x01, 0x00, 0xFD, 0x61, 0x01]).unwrap();
+    run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+    assert!(chip8.is_halted());
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.registers()[1], 0);
+}
@@ -0,0 +1,95 @@
+// The profiler: counts per address and opcode class, subroutine cycles and the reports
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+// main calls A twice, A calls B, then main spins
+//   0x200 call A, 0x202 call A, 0x204 jump to itself
+//   0x208 A: V0 = 1, call B, return
+//   0x210 B: V1 += 1, return
+const CALLS: [u8; 20] = [
+    0x22, 0x08, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00,
+    0x60, 0x01, 0x22, 0x10, 0x00, 0xEE, 0x00, 0x00,
+    0x71, 0x01, 0x00, 0xEE,
+];
+
+fn profiled(rom: &[u8], ticks: usize) -> Profiler {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(rom).unwrap();
+    chip8.set_profiler(Profiler::new());
+
+    for _ in 0..ticks {
+        chip8.tick().unwrap();
+    }
+
+    chip8.take_profiler().unwrap()
+}
+
+#[test]
+fn counts_addresses_and_classes() {
+    // 2 calls of A at 5 instructions each, between the 2 calls and 3 turns of the spin
+    let profiler: Profiler = profiled(&CALLS, 15);
+
+    assert_eq!(profiler.cycles(), 15);
+    assert_eq!(profiler.address(0x204), Some(AddressProfile { opcode: 0x1204, count: 3 }));
+    assert_eq!(profiler.address(0x210), Some(AddressProfile { opcode: 0x7101, count: 2 }));
+    assert_eq!(profiler.address(0x206), None);
+
+    assert_eq!((profiler.class("2nnn"), profiler.class("00EE"), profiler.class("1nnn")), (4, 4, 3));
+    assert_eq!(profiler.classes()[0], ("00EE", 4));      // ties are sorted by name
+}
+
+#[test]
+fn subroutine_cycles() {
+    let profiler: Profiler = profiled(&CALLS, 15);
+
+    assert_eq!(profiler.subroutine(None), Some(SubroutineProfile { calls: 0, inclusive: 15, exclusive: 5 }));
+    assert_eq!(profiler.subroutine(Some(0x208)), Some(SubroutineProfile { calls: 2, inclusive: 10, exclusive: 6 }));
+    assert_eq!(profiler.subroutine(Some(0x210)), Some(SubroutineProfile { calls: 2, inclusive: 4, exclusive: 4 }));
+}
+
+#[test]
+fn running_subroutine_counts_up_to_now() {
+    // main calls 0x204, which spins
+    let profiler: Profiler = profiled(&[0x22, 0x04, 0x00, 0x00, 0x12, 0x04], 4);
+
+    assert_eq!(profiler.subroutine(Some(0x204)), Some(SubroutineProfile { calls: 1, inclusive: 3, exclusive: 3 }));
+    assert_eq!(profiler.subroutines()[0].0, None);
+}
+
+#[test]
+fn recursion_is_counted_once() {
+    //   0x200 V0 = 3, call 0x206, 0x204 jump to itself
+    //   0x206 skip if V0 == 0, call 0x20C, return
+    //   0x20C V0 -= 1, jump to 0x206
+    // 0x20C calls itself through 0x206 three levels deep: 5 + 5 + 4 instructions, counted once
+    let rom: [u8; 16] = [
+        0x60, 0x03, 0x22, 0x06, 0x12, 0x04,
+        0x30, 0x00, 0x22, 0x0C, 0x00, 0xEE,
+        0x70, 0xFF, 0x12, 0x06,
+    ];
+    let profiler: Profiler = profiled(&rom, 20);
+
+    assert_eq!(profiler.subroutine(Some(0x20C)), Some(SubroutineProfile { calls: 3, inclusive: 14, exclusive: 14 }));
+    assert_eq!(profiler.subroutine(Some(0x206)), Some(SubroutineProfile { calls: 1, inclusive: 17, exclusive: 3 }));
+}
+
+#[test]
+fn reports() {
+    let profiler: Profiler = profiled(&CALLS, 15);
+
+    assert_eq!(
+        profiler.report(ProfileFormat::CollapsedStacks),
+        "main 5\nmain;0x0208 6\nmain;0x0208;0x0210 4\n"
+    );
+
+    let json: String = profiler.report(ProfileFormat::Json);
+    assert!(json.starts_with("{\"cycles\":15,\"addresses\":[{\"address\":516,\"opcode\":4612,\"count\":3},"));
+    assert!(json.contains("{\"name\":\"0x0208\",\"address\":520,\"calls\":2,\"inclusive\":10,\"exclusive\":6}"));
+
+    let text: String = profiler.report(ProfileFormat::Text);
+    assert!(text.starts_with("15 instructions\n"));
+    assert!(text.contains("  0204             3  20.00%  1204  JP 0x204\n"));
+}
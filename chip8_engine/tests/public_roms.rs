@@ -0,0 +1,121 @@
+// The public test ROMs: the Timendus suite (https://github.com/Timendus/chip8-test-suite) and BonCoder's
+// opcode test. They are not part of this repository, so these tests are ignored by default and run with
+// `cargo test --test public_roms -- --ignored` once the ROMs are in tests/roms (see the README there). Then a
+// missing ROM fails, and so does a missing snapshot, with the display it got so it can be checked and recorded.
+// The harness test always runs, on a small ROM of our own.
+//
+// The Timendus ROMs with a menu read their choice from 0x1FF when it is set before they start, that is how
+// the quirks and keypad tests are pointed at a platform or a test here.
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+const MENU_ADDRESS: usize = 0x1FF;
+
+// Draws the menu choice it finds at 0x1FF, waits for a key and draws that too. Stands in for a public ROM so
+// the harness itself is checked on every run
+const HARNESS_ROM: [u8; 22] = [
+    0xA1, 0xFF,     // 0200  LD I, 0x1FF
+    0xF0, 0x65,     // 0202  LD V0, [I]
+    0xF0, 0x29,     // 0204  LD F, V0
+    0x61, 0x08,     // 0206  LD V1, 8
+    0x62, 0x08,     // 0208  LD V2, 8
+    0xD1, 0x25,     // 020A  DRW V1, V2, 5
+    0xF3, 0x0A,     // 020C  LD V3, K
+    0xF3, 0x29,     // 020E  LD F, V3
+    0x61, 0x10,     // 0210  LD V1, 16
+    0xD1, 0x25,     // 0212  DRW V1, V2, 5
+    0x12, 0x14,     // 0214  JP 0x214
+];
+
+fn check(file: &str, snapshot: &str, quirks: Quirks, menu: Option<u8>, keys: &[KeyPress]) {
+    check_rom(&public_rom(file), snapshot, quirks, menu, keys);
+}
+
+fn check_rom(rom: &[u8], snapshot: &str, quirks: Quirks, menu: Option<u8>, keys: &[KeyPress]) {
+    let mut chip8: Chip8 = machine(quirks);
+    chip8.load_rom(rom).unwrap();
+    if let Some(choice) = menu {
+        chip8.set_memory(MENU_ADDRESS, choice).unwrap();
+    }
+
+    run(&mut chip8, MAX_FRAMES, keys).unwrap();
+    assert_snapshot(snapshot, &chip8);
+}
+
+// A 2 from the menu, then the 7 pressed on frame 10
+#[test]
+fn harness() {
+    check_rom(&HARNESS_ROM, "public_rom_harness", Quirks::default(), Some(2), &[KeyPress { frame: 10, key: 7, hold: 2 }]);
+}
+
+#[test]
+#[should_panic(expected = "does not match the display")]
+fn harness_mismatch() {
+    check_rom(&HARNESS_ROM, "public_rom_harness", Quirks::default(), Some(3), &[KeyPress { frame: 10, key: 7, hold: 2 }]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn chip8_logo() {
+    check("1-chip8-logo.ch8", "timendus_chip8_logo", Quirks::default(), None, &[]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn ibm_logo() {
+    check("2-ibm-logo.ch8", "timendus_ibm_logo", Quirks::default(), None, &[]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn corax_plus() {
+    check("3-corax+.ch8", "timendus_corax_plus", Quirks::default(), None, &[]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn flags() {
+    check("4-flags.ch8", "timendus_flags", Quirks::default(), None, &[]);
+}
+
+// One snapshot per preset, the ROM prints which quirks it saw
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn quirks_vip() {
+    check("5-quirks.ch8", "timendus_quirks_vip", Quirks::VIP, Some(1), &[]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn quirks_chip48() {
+    // The ROM has no CHIP-48 entry, its SUPER-CHIP expectations are the closest
+    check("5-quirks.ch8", "timendus_quirks_chip48", Quirks::CHIP48, Some(2), &[]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn quirks_schip() {
+    check("5-quirks.ch8", "timendus_quirks_schip", Quirks::SCHIP, Some(2), &[]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn quirks_xochip() {
+    check("5-quirks.ch8", "timendus_quirks_xochip", Quirks::XOCHIP, Some(3), &[]);
+}
+
+// Fx0A test, with 7 pressed a second in
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn keypad_wait() {
+    check("6-keypad.ch8", "timendus_keypad_wait", Quirks::default(), Some(3), &[KeyPress { frame: 60, key: 7, hold: 5 }]);
+}
+
+#[test]
+#[ignore = "needs the public test ROMs, see tests/roms/README.md"]
+fn boncoder_opcodes() {
+    check("BC_test.ch8", "boncoder_opcodes", Quirks::default(), None, &[]);
+}
@@ -0,0 +1,137 @@
+// Each quirk probed on its own under every preset, the same behaviours the quirks test ROM reports on.
+// The expected result is read off the preset itself, so a preset that changes has to change here too
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+const PRESETS: [(&str, Quirks); 5] = [
+    ("default", Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: LoadStoreIncrement::None,
+        jump_uses_vx: false,
+        logic_resets_vf: false,
+        clip_sprites: false,
+        display_wait: false,
+    }),
+    ("vip", Quirks::VIP),
+    ("chip48", Quirks::CHIP48),
+    ("schip", Quirks::SCHIP),
+    ("xochip", Quirks::XOCHIP),
+];
+
+#[test]
+fn default_preset_is_every_quirk_off() {
+    assert_eq!(PRESETS[0].1, Quirks::default());
+}
+
+#[test]
+fn presets_are_distinct() {
+    for (i, (name, quirks)) in PRESETS.iter().enumerate() {
+        for (other_name, other) in &PRESETS[i + 1..] {
+            assert_ne!(quirks, other, "{} and {}", name, other_name);
+        }
+    }
+}
+
+#[test]
+fn logic_resets_vf() {
+    for (name, quirks) in PRESETS {
+        for op in [0x8011, 0x8012, 0x8013] {
+            let chip8: Chip8 = run_program(quirks, &[0x6F05, op]);
+            let expected: u8 = if quirks.logic_resets_vf {0} else {5};
+
+            assert_eq!(chip8.registers()[0xF], expected, "{} {:04X}", name, op);
+        }
+    }
+}
+
+#[test]
+fn load_store_increments_i() {
+    for (name, quirks) in PRESETS {
+        for op in [0xF255, 0xF265] {
+            let chip8: Chip8 = run_program(quirks, &[0xA300, op]);
+            let expected: u16 = match quirks.load_store_increments_i {
+                LoadStoreIncrement::None => 0x300,
+                LoadStoreIncrement::X => 0x302,
+                LoadStoreIncrement::XPlusOne => 0x303,
+            };
+
+            assert_eq!(chip8.index_register(), expected, "{} {:04X}", name, op);
+        }
+    }
+}
+
+#[test]
+fn shift_uses_vy() {
+    for (name, quirks) in PRESETS {
+        let chip8: Chip8 = run_program(quirks, &[0x60FF, 0x6104, 0x8016]);
+        let expected: u8 = if quirks.shift_uses_vy {0x02} else {0x7F};
+        assert_eq!(chip8.registers()[0], expected, "{} 8xy6", name);
+
+        let chip8: Chip8 = run_program(quirks, &[0x60FF, 0x6104, 0x801E]);
+        let expected: u8 = if quirks.shift_uses_vy {0x08} else {0xFE};
+        assert_eq!(chip8.registers()[0], expected, "{} 8xyE", name);
+    }
+}
+
+#[test]
+fn jump_uses_vx() {
+    // B208 lands on 0x208 (+ V0 = 0) and sets VA, or on 0x20C (+ V2 = 4) and sets VB
+    let rom: [u8; 16] = [
+        0x62, 0x04, 0x60, 0x00, 0xB2, 0x08, 0x12, 0x06,
+        0x6A, 0x01, 0x12, 0x0A, 0x6B, 0x01, 0x12, 0x0E,
+    ];
+
+    for (name, quirks) in PRESETS {
+        let mut chip8: Chip8 = machine(quirks);
+        chip8.load_rom(&rom).unwrap();
+        run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+        let expected: [u8; 2] = if quirks.jump_uses_vx {[0, 1]} else {[1, 0]};
+        assert_eq!(&chip8.registers()[0xA..0xC], &expected, "{}", name);
+    }
+}
+
+#[test]
+fn clip_sprites() {
+    // A 2 row 0xFF sprite at (60, 31) spills past the right and the bottom edge
+    let ops: [u16; 7] = [0x60FF, 0x61FF, 0xA300, 0xF155, 0xA300, 0x603C, 0x611F];
+
+    for (name, quirks) in PRESETS {
+        let chip8: Chip8 = run_program(quirks, &[&ops[..], &[0xD012]].concat());
+        let wrapped: bool = !quirks.clip_sprites;
+
+        assert_eq!(chip8.pixel(63, 31), 1, "{}", name);
+        assert_eq!(chip8.pixel(0, 31) == 1, wrapped, "{} right edge", name);
+        assert_eq!(chip8.pixel(60, 0) == 1, wrapped, "{} bottom edge", name);
+        assert_eq!(chip8.pixel(0, 0) == 1, wrapped, "{} corner", name);
+    }
+}
+
+#[test]
+fn display_wait() {
+    // Three sprites: without the quirk they are all drawn in the first frame, with it one per vblank
+    let ops: [u16; 4] = [0xA000, 0xD005, 0xD005, 0xD005];
+
+    for (name, quirks) in PRESETS {
+        let mut chip8: Chip8 = machine(quirks);
+        chip8.load_rom(&program(&ops)).unwrap();
+        let frames: usize = run(&mut chip8, MAX_FRAMES, &[]).unwrap();
+
+        let expected: usize = if quirks.display_wait {4} else {1};
+        assert_eq!(frames, expected, "{}", name);
+    }
+}
+
+#[test]
+fn presets_survive_a_save_state() {
+    for (name, quirks) in PRESETS {
+        let state: Vec<u8> = machine(quirks).save_state();
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_state(&state).unwrap();
+
+        assert_eq!(chip8.quirks(), quirks, "{}", name);
+    }
+}
@@ -0,0 +1,144 @@
+// Rewind history: stepping back has to land on exactly the state each snapshot was taken from
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+// Every frame counts V0 up, draws its digit, rolls a random number and waits for the next frame
+const COUNTER: [u16; 11] = [0x7001, 0xF029, 0x00E0, 0xD125, 0xC3FF, 0x6401, 0xF415, 0xF407, 0x3400, 0x120E, 0x1200];
+
+fn counter() -> Chip8 {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&COUNTER)).unwrap();
+
+    chip8
+}
+
+// Run and record `frames` frames, the save state after each of them
+fn record(chip8: &mut Chip8, rewind: &mut Rewind, frames: usize) -> Vec<Vec<u8>> {
+    (0..frames)
+        .map(|_| {
+            chip8.run_frame().unwrap();
+            rewind.record(chip8);
+            chip8.save_state()
+        })
+        .collect()
+}
+
+fn assert_state(chip8: &Chip8, expected: &[u8], step: usize) {
+    assert!(chip8.save_state() == expected, "step {} did not restore the snapshot", step);
+}
+
+#[test]
+fn steps_back_through_every_snapshot() {
+    let mut chip8: Chip8 = counter();
+    let mut rewind: Rewind = Rewind::new(1, 100);
+    let states: Vec<Vec<u8>> = record(&mut chip8, &mut rewind, 20);
+    assert_eq!(rewind.available_frames(), 19);
+
+    // The newest snapshot is where the machine already is, so the first step goes to the one before
+    for (step, expected) in states[..19].iter().rev().enumerate() {
+        assert!(rewind.step_back(&mut chip8));
+        assert_state(&chip8, expected, step);
+    }
+
+    assert!(!rewind.step_back(&mut chip8));
+    assert_state(&chip8, &states[0], 19);
+}
+
+#[test]
+fn empty_history() {
+    let mut chip8: Chip8 = counter();
+    let before: Vec<u8> = chip8.save_state();
+    let mut rewind: Rewind = Rewind::new(2, 10);
+
+    assert_eq!(rewind.available_frames(), 0);
+    assert!(!rewind.step_back(&mut chip8));
+    assert!(!rewind.rewind(&mut chip8, 10));
+    assert_state(&chip8, &before, 0);
+
+    // And once more after clear()
+    record(&mut chip8, &mut rewind, 5);
+    rewind.clear();
+    let before: Vec<u8> = chip8.save_state();
+    assert!(!rewind.step_back(&mut chip8));
+    assert_state(&chip8, &before, 0);
+}
+
+#[test]
+fn interval() {
+    // Snapshots after frames 0, 4 and 8, then 3 frames past the last one
+    let mut chip8: Chip8 = counter();
+    let mut rewind: Rewind = Rewind::new(4, 100);
+    let states: Vec<Vec<u8>> = record(&mut chip8, &mut rewind, 12);
+    assert_eq!(rewind.available_frames(), 2 * 4 + 3);
+
+    for (step, frame) in [8, 4, 0].into_iter().enumerate() {
+        assert!(rewind.step_back(&mut chip8));
+        assert_state(&chip8, &states[frame], step);
+    }
+
+    assert!(!rewind.step_back(&mut chip8));
+}
+
+#[test]
+fn rewind_carries_frames_over() {
+    // Snapshots after frames 0, 4 and 8, the machine is 3 frames past the last one
+    let mut chip8: Chip8 = counter();
+    let mut rewind: Rewind = Rewind::new(4, 100);
+    let states: Vec<Vec<u8>> = record(&mut chip8, &mut rewind, 12);
+
+    assert!(rewind.rewind(&mut chip8, 2));
+    assert_state(&chip8, &states[11], 0);
+    assert!(rewind.rewind(&mut chip8, 1));
+    assert_state(&chip8, &states[8], 1);
+    assert!(rewind.rewind(&mut chip8, 5));
+    assert_state(&chip8, &states[4], 2);
+    assert!(!rewind.rewind(&mut chip8, 3));       // 1 frame was left over from the last call, that was the oldest
+    assert_state(&chip8, &states[0], 3);
+
+    assert!(!rewind.rewind(&mut chip8, 10));
+    assert_state(&chip8, &states[0], 4);
+}
+
+#[test]
+fn capacity_drops_the_oldest_snapshots() {
+    let mut chip8: Chip8 = counter();
+    let mut rewind: Rewind = Rewind::new(1, 5);
+    let states: Vec<Vec<u8>> = record(&mut chip8, &mut rewind, 20);
+    assert_eq!(rewind.available_frames(), 4);
+
+    for (step, expected) in states[15..19].iter().rev().enumerate() {
+        assert!(rewind.step_back(&mut chip8));
+        assert_state(&chip8, expected, step);
+    }
+
+    assert!(!rewind.step_back(&mut chip8));
+    assert_state(&chip8, &states[15], 4);
+}
+
+#[test]
+fn large_and_resized_differences() {
+    let mut chip8: Chip8 = counter();
+    let mut rewind: Rewind = Rewind::new(1, 10);
+    rewind.record(&chip8);
+    let lores: Vec<u8> = chip8.save_state();
+
+    // A run of changed bytes longer than one varint byte can count
+    for addr in 0x400..0x600 {
+        chip8.set_memory(addr, addr as u8 | 1).unwrap();
+    }
+    rewind.record(&chip8);
+    let changed: Vec<u8> = chip8.save_state();
+
+    // XO-CHIP mode changes the size of the state, that difference is kept whole
+    chip8.set_xo_chip(true);
+    rewind.record(&chip8);
+
+    assert!(rewind.step_back(&mut chip8));
+    assert_state(&chip8, &changed, 0);
+    assert!(rewind.step_back(&mut chip8));
+    assert_state(&chip8, &lores, 1);
+    assert!(!rewind.step_back(&mut chip8));
+}
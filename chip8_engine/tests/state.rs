@@ -0,0 +1,158 @@
+// Save states: a round trip through save_state() and load_state() in every display mode, and a bad state of
+// each kind turned away without touching the machine
+
+mod common;
+
+use chip8_engine::*;
+use common::*;
+
+const STACK_POINTER_OFFSET: usize = 60;
+const MEMORY_OFFSET: usize = 2159;       // after the memory length, see the format in state.rs
+
+// Load into a fresh machine and check it comes back out byte for byte
+fn round_trip(chip8: &Chip8) -> Chip8 {
+    let state: Vec<u8> = chip8.save_state();
+    let mut loaded: Chip8 = Chip8::new();
+    loaded.load_state(&state).unwrap();
+
+    assert!(loaded.save_state() == state, "the loaded state saves differently");
+    assert_eq!(screen_text(&loaded), screen_text(chip8));
+    assert_eq!(loaded.resolution(), chip8.resolution());
+    assert_eq!(loaded.pc(), chip8.pc());
+    assert_eq!(loaded.registers(), chip8.registers());
+    assert_eq!(loaded.stack(), chip8.stack());
+
+    loaded
+}
+
+// New CRC after a field was changed on purpose
+fn reseal(state: &mut Vec<u8>) {
+    state.truncate(state.len() - 4);
+
+    let crc: u32 = crc32(state);
+    state.extend_from_slice(&crc.to_le_bytes());
+}
+
+// A lo-res machine with a sprite on screen and a return address on the stack
+fn saved_machine() -> Chip8 {
+    run_program(Quirks::default(), &[0x6A12, 0x6B34, 0xA000, 0x6105, 0x6203, 0xD125, 0x220E])
+}
+
+fn assert_rejected(state: &[u8], expected: StateError) {
+    let mut chip8: Chip8 = Chip8::new();
+    let before: Vec<u8> = chip8.save_state();
+
+    assert_eq!(chip8.load_state(state), Err(expected));
+    assert!(chip8.save_state() == before, "{:?} changed the machine", expected);
+}
+
+// The standard check values, the CRC has to match what zip and PNG readers compute
+#[test]
+fn crc32_check_values() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+}
+
+#[test]
+fn lores_round_trip() {
+    let chip8: Chip8 = saved_machine();
+    assert_eq!(chip8.stack(), [0x20E]);
+
+    let loaded: Chip8 = round_trip(&chip8);
+    assert_eq!(loaded.pixel(5, 3), 1);
+}
+
+#[test]
+fn hires_round_trip() {
+    // The font's 0 near the bottom right corner, out of reach of a 64x32 layout
+    let chip8: Chip8 = run_program(Quirks::SCHIP, &[0x00FF, 0xA000, 0x6178, 0x623A, 0xD125]);
+    assert_eq!(chip8.resolution(), (128, 64));
+
+    let loaded: Chip8 = round_trip(&chip8);
+    assert_eq!(loaded.pixel(120, 58), 1);
+}
+
+#[test]
+fn xo_chip_round_trip() {
+    // Registers stored past 4 KiB, the font's 0 in the second plane, an audio pattern and a pitch
+    let chip8: Chip8 = run_program(Quirks::XOCHIP, &[
+        0xF000, 0x1400, 0x6A77, 0xFA55,
+        0xF201, 0xA000, 0xD015,
+        0xF002, 0x6B40, 0xFB3A,
+    ]);
+
+    let loaded: Chip8 = round_trip(&chip8);
+    assert!(loaded.is_xo_chip());
+    assert_eq!(loaded.memory_size(), 65536);
+    assert_eq!(loaded.memory()[0x140A], 0x77);
+    assert_eq!(loaded.pixel(0, 0), 2);
+    assert_eq!(loaded.audio_pattern(), chip8.audio_pattern());
+    assert_eq!(loaded.pitch(), 0x40);
+}
+
+// Memory lives on the heap and only grows to 64kB when XO-CHIP mode needs it, so a Chip8 can be built on a
+// microcontroller's stack
+#[test]
+fn memory_grows_for_xo_chip() {
+    assert!(size_of::<Chip8>() < 4 * 1024, "Chip8 is {} bytes", size_of::<Chip8>());
+
+    let mut chip8: Chip8 = Chip8::new();
+    assert_eq!(chip8.memory().len(), 0x1000);
+
+    chip8.set_xo_chip(true);
+    chip8.set_memory(0xFFFF, 7).unwrap();
+    assert_eq!(chip8.memory().len(), 0x10000);
+
+    // Turning XO-CHIP mode off and on again keeps the upper memory, like it always did
+    chip8.set_xo_chip(false);
+    assert_eq!(chip8.memory().len(), 0x1000);
+    assert_eq!(chip8.set_memory(0xFFFF, 8), Err(Chip8Error::MemoryOutOfBounds { addr: 0xFFFF }));
+    chip8.set_xo_chip(true);
+    assert_eq!(chip8.memory()[0xFFFF], 7);
+}
+
+#[test]
+fn truncated() {
+    let state: Vec<u8> = saved_machine().save_state();
+    assert_rejected(&state[..4], StateError::Truncated);
+
+    // Short by part of the memory, with a checksum that matches what is left
+    let mut state: Vec<u8> = state[..state.len() - 100].to_vec();
+    reseal(&mut state);
+    assert_rejected(&state, StateError::Truncated);
+}
+
+#[test]
+fn bad_magic() {
+    let mut state: Vec<u8> = saved_machine().save_state();
+    state[0] = b'X';
+
+    assert_rejected(&state, StateError::BadMagic);
+}
+
+#[test]
+fn future_version() {
+    let mut state: Vec<u8> = saved_machine().save_state();
+    state[4..6].copy_from_slice(&3u16.to_le_bytes());
+    reseal(&mut state);
+
+    assert_rejected(&state, StateError::UnsupportedVersion(3));
+}
+
+#[test]
+fn checksum_mismatch() {
+    let mut state: Vec<u8> = saved_machine().save_state();
+    state[MEMORY_OFFSET + 0x200] ^= 0xFF;
+
+    assert_rejected(&state, StateError::ChecksumMismatch);
+}
+
+#[test]
+fn oversized_stack_pointer() {
+    let mut state: Vec<u8> = saved_machine().save_state();
+    state[STACK_POINTER_OFFSET] = 17;
+    reseal(&mut state);
+
+    assert_rejected(&state, StateError::Corrupt);
+}
@@ -0,0 +1,116 @@
+// The execution tracer: what an entry records, the sinks, and both output formats
+
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use chip8_engine::*;
+use common::*;
+
+fn traced(ops: &[u16], capacity: usize) -> (Chip8, Rc<RefCell<TraceBuffer>>) {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(ops)).unwrap();
+
+    let buffer: Rc<RefCell<TraceBuffer>> = Rc::new(RefCell::new(TraceBuffer::new(capacity)));
+    chip8.set_tracer(buffer.clone());
+
+    (chip8, buffer)
+}
+
+#[test]
+fn records_every_instruction() {
+    let (mut chip8, buffer) = traced(&[0x6A12, 0xA300, 0x2208, 0x1206, 0x00EE], 100);
+    for _ in 0..5 {
+        chip8.tick().unwrap();
+    }
+
+    let buffer = buffer.borrow();
+    let entries: Vec<&TraceEntry> = buffer.entries().collect();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(chip8.cycles(), 5);
+
+    let load: &TraceEntry = entries[0];
+    assert_eq!((load.cycle, load.pc, load.opcode), (0, 0x200, 0x6A12));
+    assert_eq!(load.instruction, Instruction::LoadByte(0xA, 0x12));
+    assert_eq!((load.before.v[0xA], load.after.v[0xA]), (0, 0x12));
+
+    assert_eq!((entries[1].before.i, entries[1].after.i), (0, 0x300));
+
+    // The call at 0x204 pushes, the return at 0x208 pops
+    assert_eq!((entries[2].before.sp, entries[2].after.sp), (0, 1));
+    assert_eq!((entries[3].pc, entries[3].before.sp, entries[3].after.sp), (0x208, 1, 0));
+    assert_eq!(entries[4].cycle, 4);
+}
+
+#[test]
+fn buffer_keeps_the_newest_entries() {
+    // A jump to itself, 10 times
+    let (mut chip8, buffer) = traced(&[], 3);
+    for _ in 0..10 {
+        chip8.tick().unwrap();
+    }
+
+    let cycles: Vec<u64> = buffer.borrow().entries().map(|entry: &TraceEntry| entry.cycle).collect();
+    assert_eq!(cycles, [7, 8, 9]);
+}
+
+#[test]
+fn toggled_at_runtime() {
+    let mut chip8: Chip8 = machine(Quirks::default());
+    chip8.load_rom(&program(&[0x6001, 0x6002, 0x6003])).unwrap();
+    chip8.tick().unwrap();
+
+    // Only the middle instruction runs with the callback set
+    let seen: Rc<RefCell<Vec<u16>>> = Rc::new(RefCell::new(Vec::new()));
+    let sink: Rc<RefCell<Vec<u16>>> = seen.clone();
+    chip8.set_tracer(move |entry: &TraceEntry| sink.borrow_mut().push(entry.pc));
+    assert!(chip8.is_tracing());
+
+    chip8.tick().unwrap();
+    assert!(chip8.take_tracer().is_some());
+    chip8.tick().unwrap();
+
+    assert_eq!(*seen.borrow(), [0x202]);
+    assert!(!chip8.is_tracing());
+}
+
+#[test]
+fn failed_instruction_is_not_recorded() {
+    let (mut chip8, buffer) = traced(&[0x00EE], 10);
+
+    assert_eq!(chip8.tick(), Err(Chip8Error::StackUnderflow));
+    assert!(buffer.borrow().is_empty());
+    assert_eq!(chip8.cycles(), 0);
+}
+
+#[test]
+#[cfg(feature = "std")]         // TraceWriter
+fn text_and_json_lines() {
+    let (mut chip8, buffer) = traced(&[0x6A12], 1);
+    chip8.tick().unwrap();
+
+    let entry: TraceEntry = *buffer.borrow().entries().next().unwrap();
+    let zeros: &str = "00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00";
+    let after: &str = "00 00 00 00 00 00 00 00 00 00 12 00 00 00 00 00";
+    assert_eq!(
+        entry.format(TraceFormat::Text),
+        format!("         0 0200 6A12 LD VA, 0x12          V={} I=0000 SP=0 | V={} I=0000 SP=0", zeros, after)
+    );
+
+    // The writer adds the newline
+    let mut writer: TraceWriter<Vec<u8>> = TraceWriter::new(Vec::new(), TraceFormat::JsonLines);
+    writer.record(&entry);
+
+    let json: String = String::from_utf8(writer.into_inner()).unwrap();
+    let zeros: &str = "0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0";
+    let after: &str = "0,0,0,0,0,0,0,0,0,0,18,0,0,0,0,0";
+    assert_eq!(
+        json,
+        format!(
+            "{{\"cycle\":0,\"pc\":512,\"opcode\":27154,\"mnemonic\":\"LD VA, 0x12\",\
+            \"before\":{{\"v\":[{}],\"i\":0,\"sp\":0}},\"after\":{{\"v\":[{}],\"i\":0,\"sp\":0}}}}\n",
+            zeros, after
+        )
+    );
+}
@@ -0,0 +1,59 @@
+// The two clocks a frontend usually needs. SystemClock measures real time itself, ManualClock is told how much
+// passed: by a browser's requestAnimationFrame timestamps, a hardware timer, or a test
+
+use crate::*;
+
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+// std::time::Instant, not for wasm32-unknown-unknown where it panics (use ManualClock with the browser's time)
+#[cfg(feature = "std")]
+pub struct SystemClock {
+    last: Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { last: Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn elapsed(&mut self) -> Duration {
+        let now: Instant = Instant::now();
+        let elapsed: Duration = now - self.last;
+        self.last = now;
+
+        elapsed
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ManualClock {
+    pending: Duration,          // time advanced since the last elapsed()
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.pending += elapsed;
+    }
+}
+
+impl Clock for ManualClock {
+    fn elapsed(&mut self) -> Duration {
+        core::mem::take(&mut self.pending)
+    }
+}
@@ -0,0 +1,74 @@
+// The hex keypad on the left side of a QWERTY keyboard, the layout every CHIP-8 emulator uses:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   ->   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+
+use crate::*;
+
+// (keyboard key, CHIP-8 key)
+pub const KEYPAD_LAYOUT: [(char, usize); KEYS] = [
+    ('1', 0x1), ('2', 0x2), ('3', 0x3), ('4', 0xC),
+    ('Q', 0x4), ('W', 0x5), ('E', 0x6), ('R', 0xD),
+    ('A', 0x7), ('S', 0x8), ('D', 0x9), ('F', 0xE),
+    ('Z', 0xA), ('X', 0x0), ('C', 0xB), ('V', 0xF),
+];
+
+// CHIP-8 key for a key name as the platform reports it ("q", "Q", "1"), any case. None for everything else,
+// including longer names like "F5" or "Backspace"
+pub fn keypad_key(name: &str) -> Option<usize> {
+    let mut chars = name.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+
+    KEYPAD_LAYOUT.iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(&c))
+        .map(|(_, chip8_key)| *chip8_key)
+}
+
+// Key state fed from the platform's key events
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keypad {
+    keys: [bool; KEYS],
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Key event by name, see keypad_key(). Returns false when the key is not on the keypad
+    pub fn set_key(&mut self, name: &str, down: bool) -> bool {
+        match keypad_key(name) {
+            Some(key) => {
+                self.set(key, down);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Key event for a CHIP-8 key, for frontends with a layout of their own
+    pub fn set(&mut self, key: usize, down: bool) {
+        if let Some(state) = self.keys.get_mut(key) {
+            *state = down;
+        }
+    }
+
+    pub fn is_down(&self, key: usize) -> bool {
+        self.keys.get(key).copied().unwrap_or(false)
+    }
+
+    // Everything up, e.g. when the window loses focus and the key up events go elsewhere
+    pub fn release_all(&mut self) {
+        self.keys = [false; KEYS];
+    }
+}
+
+impl Input for Keypad {
+    fn keypad(&mut self) -> [bool; KEYS] {
+        self.keys
+    }
+}
@@ -0,0 +1,82 @@
+// What every frontend has to provide, and the loop that drives the engine through it.
+//
+// A frontend implements four small traits: Display (show the pixels), Input (which of the 16 keys are down),
+// Audio (buzzer on or off) and Clock (how much real time passed), then hands them to a Runner and calls
+// Runner::frame() once per vsync / animation frame / timer interrupt. The Runner does the rest: keys in,
+// emulation of the time that passed under the debugger, optional hold-to-rewind, buzzer and redraw of the rows
+// that changed. Everything a platform does not need to do itself is here, like the QWERTY key map (Keypad)
+// and the clocks.
+//
+// no_std like the engine, the default std feature only adds SystemClock
+
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+mod clock;
+mod keypad;
+mod runner;
+
+pub use clock::ManualClock;
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+pub use keypad::{keypad_key, Keypad, KEYPAD_LAYOUT};
+pub use runner::Runner;
+
+use chip8_engine::*;
+use core::time::Duration;
+
+pub const KEYS: usize = 16;
+
+// RGB for the 2-bit XO-CHIP colour indices: off, first plane, second plane, both planes
+pub const PALETTE: [(u8, u8, u8); 4] = [(0, 0, 0), (255, 255, 255), (170, 170, 170), (85, 85, 85)];
+
+pub trait Display {
+    // Called once per frame. dirty_rows has bit n set for every row n that changed since the last call (see
+    // Chip8::take_dirty_rows()), the pixels come from chip8.resolution() and chip8.pixel()
+    fn draw(&mut self, chip8: &Chip8, dirty_rows: u64);
+}
+
+pub trait Input {
+    // Which keys are held down right now, index 0x0 - 0xF
+    fn keypad(&mut self) -> [bool; KEYS];
+}
+
+pub trait Audio {
+    // Called once per frame, true while the buzzer should sound
+    fn set_playing(&mut self, playing: bool);
+
+    // XO-CHIP audio pattern and pitch, called once per frame in XO-CHIP mode. A plain beep is fine too
+    fn set_pattern(&mut self, _pattern: &[u8; 16], _pitch: u8) {}
+}
+
+pub trait Clock {
+    // Real time since the previous call
+    fn elapsed(&mut self) -> Duration;
+}
+
+// Nothing to show, e.g. a headless run that only looks at the screen at the end
+impl Display for () {
+    fn draw(&mut self, _chip8: &Chip8, _dirty_rows: u64) {}
+}
+
+// No sound device (or it failed to open), the game runs silently
+impl<A: Audio> Audio for Option<A> {
+    fn set_playing(&mut self, playing: bool) {
+        if let Some(audio) = self {
+            audio.set_playing(playing);
+        }
+    }
+
+    fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        if let Some(audio) = self {
+            audio.set_pattern(pattern, pitch);
+        }
+    }
+}
+
+// A frontend without sound at all
+impl Audio for () {
+    fn set_playing(&mut self, _playing: bool) {}
+}
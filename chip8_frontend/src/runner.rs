@@ -0,0 +1,179 @@
+// The render loop shared by every frontend. frame() is run() followed by present(), a frontend that has to
+// draw at a different point of its loop (the browser draws when JavaScript asks) calls the two itself.
+//
+// Emulation always goes through a Debugger, so breakpoints, stepping and pausing work in every frontend. A
+// Runner given a Rewind records a snapshot history while running and goes back through it while rewinding.
+
+use crate::*;
+
+pub struct Runner<D: Display, I: Input, A: Audio, C: Clock> {
+    chip8: Chip8,
+    debugger: Debugger,
+    rewind: Option<Rewind>,         // hold-to-rewind history, None when the frontend has no rewind
+    rewinding: bool,
+    dirty_rows: u64,                // rows changed since the last present()
+
+    display: D,
+    input: I,
+    audio: A,
+    clock: C,
+}
+
+impl<D: Display, I: Input, A: Audio, C: Clock> Runner<D, I, A, C> {
+    pub fn new(chip8: Chip8, display: D, input: I, audio: A, clock: C) -> Self {
+        Self {
+            chip8,
+            debugger: Debugger::new(),
+            rewind: None,
+            rewinding: false,
+            dirty_rows: 0,
+
+            display,
+            input,
+            audio,
+            clock,
+        }
+    }
+
+    // One pass of the loop. Returns why the debugger stopped (breakpoint, watchpoint, step) if it did, an
+    // emulation error stops the game and comes back as Err
+    pub fn frame(&mut self) -> Result<Option<Stop>, Chip8Error> {
+        let stop: Option<Stop> = self.run()?;
+        self.present();
+
+        Ok(stop)
+    }
+
+    // Keys in, the time since the last call emulated (or rewound), buzzer updated
+    pub fn run(&mut self) -> Result<Option<Stop>, Chip8Error> {
+        for (key, down) in self.input.keypad().into_iter().enumerate() {
+            self.chip8.set_keypad(key, down);
+        }
+
+        let elapsed: Duration = self.clock.elapsed();
+        let mut stop: Option<Stop> = None;
+
+        match &mut self.rewind {
+            // Back by the frames that passed, out of the same time backlog as running
+            Some(rewind) if self.rewinding => {
+                let frames: usize = self.chip8.frames_due(elapsed);
+                rewind.rewind(&mut self.chip8, frames);
+            }
+            // Frame by frame, so every frame can be recorded
+            _ => {
+                for _ in 0..self.chip8.frames_due(elapsed) {
+                    match self.debugger.run_frame(&mut self.chip8) {
+                        Ok(frame) => {
+                            self.dirty_rows |= frame.dirty_rows;
+
+                            // No frame ran while the debugger has the game paused, nothing to record
+                            if let Some(rewind) = &mut self.rewind
+                                && frame.frames > 0 {
+                                rewind.record(&self.chip8);
+                            }
+                        }
+                        Err(Stop::Error(err)) => {
+                            self.audio.set_playing(false);
+                            return Err(err);
+                        }
+                        Err(other) => {
+                            stop = Some(other);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.audio.set_playing(self.chip8.sound_active() && !self.is_rewinding() && !self.debugger.is_paused());
+        if self.chip8.is_xo_chip() {
+            self.audio.set_pattern(self.chip8.audio_pattern(), self.chip8.pitch());
+        }
+
+        Ok(stop)
+    }
+
+    // Redraw what changed, including anything drawn outside run(): rewinding, loading a state, a breakpoint
+    pub fn present(&mut self) {
+        let dirty_rows: u64 = self.dirty_rows | self.chip8.take_dirty_rows();
+        self.dirty_rows = 0;
+
+        self.display.draw(&self.chip8, dirty_rows);
+    }
+
+    // Something to redraw on the next present()
+    pub fn screen_changed(&self) -> bool {
+        (self.dirty_rows | self.chip8.dirty_rows()) != 0
+    }
+
+    // Keep a rewind history from now on
+    pub fn set_rewind(&mut self, rewind: Rewind) {
+        self.rewind = Some(rewind);
+    }
+
+    pub fn rewind(&self) -> Option<&Rewind> {
+        self.rewind.as_ref()
+    }
+
+    // While set, run() goes back by the frames that passed instead of running. Does nothing without set_rewind()
+    pub fn set_rewinding(&mut self, rewinding: bool) {
+        self.rewinding = rewinding;
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding && self.rewind.is_some()
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    // The debugger commands that need to look at the machine
+    pub fn step_over(&mut self) {
+        self.debugger.step_over(&self.chip8);
+    }
+
+    pub fn step_out(&mut self) {
+        self.debugger.step_out(&self.chip8);
+    }
+
+    pub fn display(&self) -> &D {
+        &self.display
+    }
+
+    pub fn display_mut(&mut self) -> &mut D {
+        &mut self.display
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn audio(&self) -> &A {
+        &self.audio
+    }
+
+    pub fn audio_mut(&mut self) -> &mut A {
+        &mut self.audio
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+}
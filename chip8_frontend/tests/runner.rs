@@ -0,0 +1,207 @@
+// The Runner driving a machine through test doubles of the platform traits
+
+use std::time::Duration;
+
+use chip8_engine::*;
+use chip8_frontend::*;
+
+const FRAME: Duration = Duration::from_micros(16_667);     // a little over 1/60 s, so every call runs a frame
+
+// Every draw call's dirty rows
+#[derive(Default)]
+struct Frames(Vec<u64>);
+
+impl Display for Frames {
+    fn draw(&mut self, _chip8: &Chip8, dirty_rows: u64) {
+        self.0.push(dirty_rows);
+    }
+}
+
+// Whether the buzzer is on
+#[derive(Default)]
+struct Buzzer(bool);
+
+impl Audio for Buzzer {
+    fn set_playing(&mut self, playing: bool) {
+        self.0 = playing;
+    }
+}
+
+type TestRunner = Runner<Frames, Keypad, Buzzer, ManualClock>;
+
+fn runner(ops: &[u16]) -> TestRunner {
+    let rom: Vec<u8> = ops.iter().flat_map(|op: &u16| op.to_be_bytes()).collect();
+    let mut chip8: Chip8 = Chip8::with_seed(0);
+    chip8.load_rom(&rom).unwrap();
+
+    Runner::new(chip8, Frames::default(), Keypad::new(), Buzzer::default(), ManualClock::new())
+}
+
+// One frame of emulated time
+fn frame(runner: &mut TestRunner) -> Result<Option<Stop>, Chip8Error> {
+    runner.clock_mut().advance(FRAME);
+    runner.frame()
+}
+
+#[test]
+fn key_names() {
+    assert_eq!(keypad_key("1"), Some(0x1));
+    assert_eq!((keypad_key("q"), keypad_key("Q")), (Some(0x4), Some(0x4)));
+    assert_eq!(keypad_key("x"), Some(0x0));
+    assert_eq!(keypad_key("v"), Some(0xF));
+    assert_eq!(keypad_key("F5"), None);
+    assert_eq!(keypad_key(""), None);
+
+    let mut keypad: Keypad = Keypad::new();
+    assert!(keypad.set_key("r", true));
+    assert!(!keypad.set_key("Backspace", true));
+    assert!(keypad.is_down(0xD));
+    assert_eq!(keypad.keypad().iter().filter(|down: &&bool| **down).count(), 1);
+}
+
+#[test]
+fn keys_reach_the_machine() {
+    // Wait for a key into V5, then spin
+    let mut runner: TestRunner = runner(&[0xF50A, 0x1202]);
+
+    frame(&mut runner).unwrap();
+    assert_eq!(runner.chip8().pc(), 0x200);
+
+    runner.input_mut().set_key("e", true);
+    frame(&mut runner).unwrap();
+    runner.input_mut().set_key("e", false);
+    frame(&mut runner).unwrap();
+
+    assert_eq!(runner.chip8().registers()[5], 0x6);
+    assert_eq!(runner.chip8().pc(), 0x202);
+}
+
+#[test]
+fn draws_the_rows_that_changed() {
+    // Draw the 5 rows of the font's 0 at (0, 8) once, then spin
+    let mut runner: TestRunner = runner(&[0x6108, 0xA000, 0xD015, 0x1206]);
+
+    frame(&mut runner).unwrap();
+    frame(&mut runner).unwrap();
+
+    // Everything is dirty after the reset, nothing changes after the first frame
+    assert_eq!(runner.display().0, [u64::MAX, 0]);
+    assert!(!runner.screen_changed());
+
+    // A clear outside of run(), like loading a state, shows up on the next draw
+    runner.chip8_mut().reset();
+    assert!(runner.screen_changed());
+    runner.present();
+    assert_ne!(runner.display().0[2], 0);
+}
+
+#[test]
+fn buzzer_follows_the_sound_timer() {
+    // Sound timer = 2 frames, then spin
+    let mut runner: TestRunner = runner(&[0x6002, 0xF018, 0x1204]);
+
+    frame(&mut runner).unwrap();
+    assert!(runner.audio().0);
+
+    frame(&mut runner).unwrap();
+    frame(&mut runner).unwrap();
+    assert!(!runner.audio().0);
+}
+
+#[test]
+fn stops_and_errors() {
+    let mut runner: TestRunner = runner(&[0x6001, 0x7001, 0xFFFF]);
+
+    runner.debugger_mut().add_breakpoint(0x202);
+    assert_eq!(frame(&mut runner), Ok(Some(Stop::Breakpoint(0x202))));
+    assert!(runner.debugger().is_paused());
+
+    // Paused, no time passes
+    frame(&mut runner).unwrap();
+    assert_eq!(runner.chip8().pc(), 0x202);
+
+    runner.debugger_mut().resume();
+    assert_eq!(frame(&mut runner), Err(Chip8Error::UnknownOpcode { op: 0xFFFF, pc: 0x204 }));
+    assert!(!runner.audio().0);
+}
+
+#[test]
+fn rewinding() {
+    // V0 counts frames: add 1, wait for the next frame through the delay timer, repeat
+    let mut runner: TestRunner = runner(&[0x7001, 0x6101, 0xF115, 0xF107, 0x3100, 0x1206, 0x1200]);
+    runner.set_rewind(Rewind::new(1, 100));
+
+    for _ in 0..10 {
+        frame(&mut runner).unwrap();
+    }
+    let counted: u8 = runner.chip8().registers()[0];
+
+    runner.set_rewinding(true);
+    assert!(runner.is_rewinding());
+    for _ in 0..4 {
+        frame(&mut runner).unwrap();
+    }
+    assert!(runner.chip8().registers()[0] < counted);
+
+    runner.set_rewinding(false);
+    frame(&mut runner).unwrap();
+    assert!(!runner.is_rewinding());
+}
+
+#[test]
+fn rewinding_follows_the_clock() {
+    let mut runner: TestRunner = runner(&[0x7001, 0x6101, 0xF115, 0xF107, 0x3100, 0x1206, 0x1200]);
+    runner.set_rewind(Rewind::new(1, 100));
+
+    for _ in 0..10 {
+        frame(&mut runner).unwrap();
+    }
+    let counted: u8 = runner.chip8().registers()[0];
+    runner.set_rewinding(true);
+
+    // Half a frame is not enough to go back, the next half is
+    let rewind_by = |runner: &mut TestRunner, elapsed: Duration| -> u8 {
+        runner.clock_mut().advance(elapsed);
+        runner.run().unwrap();
+        counted - runner.chip8().registers()[0]
+    };
+    assert_eq!(rewind_by(&mut runner, Duration::from_millis(8)), 0);
+    assert_eq!(rewind_by(&mut runner, Duration::from_millis(9)), 1);
+
+    // A slow frame goes back as many frames as it took
+    assert_eq!(rewind_by(&mut runner, 3 * FRAME), 4);
+}
+
+// Snapshots every 4 frames, host frames of uneven length: the history counts emulated frames and rewinding
+// moves by them, a snapshot only once enough frames have passed
+#[test]
+fn rewind_counts_emulated_frames() {
+    let mut runner: TestRunner = runner(&[0x7001, 0x6101, 0xF115, 0xF107, 0x3100, 0x1206, 0x1200]);
+    runner.set_rewind(Rewind::new(4, 100));
+
+    let pass = |runner: &mut TestRunner, millis: u64| -> u8 {
+        runner.clock_mut().advance(Duration::from_millis(millis));
+        runner.run().unwrap();
+        runner.chip8().registers()[0]
+    };
+
+    // 3 + 0 + 1 + 6 frames
+    for millis in [50, 8, 9, 100] {
+        pass(&mut runner, millis);
+    }
+    assert_eq!(runner.chip8().registers()[0], 10);
+    assert_eq!(runner.rewind().map(Rewind::available_frames), Some(9));     // snapshots after frames 1, 5 and 9
+
+    runner.set_rewinding(true);
+    assert_eq!(pass(&mut runner, 8), 10);       // not a frame yet
+    assert_eq!(pass(&mut runner, 9), 9);        // one frame, back to the newest snapshot
+    assert_eq!(pass(&mut runner, 50), 9);       // three frames, short of the next snapshot
+    assert_eq!(pass(&mut runner, 17), 5);       // the fourth reaches it
+    assert_eq!(pass(&mut runner, 100), 1);      // six frames, but only four are left
+    assert_eq!(runner.rewind().map(Rewind::available_frames), Some(0));
+
+    // Running again records from where the rewind stopped
+    runner.set_rewinding(false);
+    assert_eq!(pass(&mut runner, 50), 4);
+    assert_eq!(runner.rewind().map(Rewind::available_frames), Some(3));
+}
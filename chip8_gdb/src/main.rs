@@ -0,0 +1,71 @@
+use chip8_engine::*;
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+
+mod packet;
+mod server;
+
+use packet::Connection;
+use server::Server;
+
+const DEFAULT_PORT: u16 = 1234;
+
+fn main() {
+    // Command Line argument
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 2 && args.len() != 3 {
+        println!("Usage: cargo run path/to/game [port]");
+        return;
+    }
+
+    let port: u16 = match args.get(2).map(|port| port.parse()) {
+        None => DEFAULT_PORT,
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            println!("Invalid port: {}", args[2]);
+            return;
+        }
+    };
+
+    let rom: Vec<u8> = match fs::read(&args[1]) {
+        Ok(rom) => rom,
+        Err(err) => {
+            println!("Unable to read {}: {}", args[1], err);
+            return;
+        }
+    };
+
+    // Only listen on the local machine, the protocol has no authentication
+    let listener: TcpListener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            println!("Unable to listen on port {}: {}", port, err);
+            return;
+        }
+    };
+    println!("Waiting for GDB on 127.0.0.1:{} (target remote :{})", port, port);
+
+    // Every connection gets the ROM freshly loaded
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                println!("Connection failed: {}", err);
+                continue;
+            }
+        };
+
+        let mut chip8: Chip8 = Chip8::new();
+        if let Err(err) = chip8.load_rom(&rom) {
+            println!("Unable to load ROM: {}", err);
+            return;
+        }
+
+        let result = Connection::new(stream).and_then(|conn| Server::new(chip8, conn).serve());
+        match result {
+            Ok(()) => println!("Debugger detached"),
+            Err(err) => println!("Connection lost: {}", err),
+        }
+    }
+}
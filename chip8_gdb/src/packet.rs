@@ -0,0 +1,322 @@
+// Packet framing of the GDB Remote Serial Protocol: $<data>#<2 hex digit checksum>, acknowledged with + or -.
+// A lone 0x03 byte outside a packet asks the target to stop.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+const INTERRUPT: u8 = 0x03;
+
+pub enum Packet {
+    Data(String),
+    Interrupt,
+}
+
+// What a connection runs over, a TcpStream outside of the tests
+pub trait Transport: Read + Write + Sized {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Transport for TcpStream {
+    fn try_clone(&self) -> io::Result<Self> {
+        TcpStream::try_clone(self)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+pub struct Connection<S: Transport = TcpStream> {
+    reader: BufReader<S>,
+    writer: S,
+    no_ack: bool,           // QStartNoAckMode, acks are neither sent nor expected
+}
+
+impl<S: Transport> Connection<S> {
+    pub fn new(stream: S) -> io::Result<Self> {
+        let writer: S = stream.try_clone()?;
+
+        Ok(Self { reader: BufReader::new(stream), writer, no_ack: false })
+    }
+
+    pub fn set_no_ack(&mut self) {
+        self.no_ack = true;
+    }
+
+    // The next packet from the debugger, None once the connection is closed
+    pub fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            let Some(byte) = self.read_byte()? else {
+                return Ok(None);
+            };
+
+            match byte {
+                INTERRUPT => return Ok(Some(Packet::Interrupt)),
+                b'$' => {},
+                _ => continue,      // acks, and noise between packets
+            }
+
+            let mut data: Vec<u8> = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let (Some(high), Some(low)) = (self.read_byte()?, self.read_byte()?) else {
+                return Ok(None);
+            };
+            let expected: Option<u8> = std::str::from_utf8(&[high, low]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            if expected != Some(checksum(&data)) {
+                if !self.no_ack {
+                    self.writer.write_all(b"-")?;
+                }
+                continue;
+            }
+            if !self.no_ack {
+                self.writer.write_all(b"+")?;
+            }
+
+            return Ok(Some(Packet::Data(String::from_utf8_lossy(&unescape(&data)).into_owned())));
+        }
+    }
+
+    pub fn send(&mut self, data: &str) -> io::Result<()> {
+        // $, #, } and * are escaped as } followed by the byte XOR 0x20
+        let mut escaped: Vec<u8> = Vec::with_capacity(data.len());
+        for byte in data.bytes() {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                escaped.push(b'}');
+                escaped.push(byte ^ 0x20);
+            } else {
+                escaped.push(byte);
+            }
+        }
+
+        let mut packet: Vec<u8> = Vec::with_capacity(escaped.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(&escaped);
+        packet.extend_from_slice(format!("#{:02x}", checksum(&escaped)).as_bytes());
+
+        self.writer.write_all(&packet)?;
+        self.writer.flush()
+    }
+
+    // While the target runs, check without blocking whether the debugger sent an interrupt. A closed
+    // connection is an UnexpectedEof error, there is nobody left to run the program for
+    pub fn poll_interrupt(&mut self) -> io::Result<bool> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let buffered: io::Result<&[u8]> = self.reader.fill_buf();
+
+        let interrupted: bool = match buffered {
+            Ok([]) => {
+                self.reader.get_ref().set_nonblocking(false)?;
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "debugger disconnected"));
+            },
+            Ok(bytes) => bytes.first() == Some(&INTERRUPT),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => false,
+            Err(err) => {
+                self.reader.get_ref().set_nonblocking(false)?;
+                return Err(err);
+            },
+        };
+        if interrupted {
+            self.reader.consume(1);
+        }
+
+        self.reader.get_ref().set_nonblocking(false)?;
+        Ok(interrupted)
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte: Option<u8> = self.reader.fill_buf()?.first().copied();
+        if byte.is_some() {
+            self.reader.consume(1);
+        }
+
+        Ok(byte)
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+// The other way around from send(): } followed by a byte stands for that byte XOR 0x20
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => out.extend(bytes.next().map(|escaped| escaped ^ 0x20)),
+            _ => out.push(*byte),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    // Both ends of an in-memory connection: what the debugger sent, and what the stub wrote back
+    #[derive(Default)]
+    pub(crate) struct Pipe {
+        input: VecDeque<u8>,
+        pub(crate) output: Vec<u8>,
+        nonblocking: bool,
+        open: bool,         // more input may come, reading nothing would block instead of meaning closed
+    }
+
+    #[derive(Clone)]
+    pub(crate) struct MemoryStream(Rc<RefCell<Pipe>>);
+
+    impl Read for MemoryStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let mut pipe = self.0.borrow_mut();
+            if pipe.input.is_empty() && pipe.open && pipe.nonblocking {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+
+            let len: usize = buf.len().min(pipe.input.len());
+            for (slot, byte) in buf.iter_mut().zip(pipe.input.drain(..len)) {
+                *slot = byte;
+            }
+
+            Ok(len)
+        }
+    }
+
+    impl Write for MemoryStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Transport for MemoryStream {
+        fn try_clone(&self) -> io::Result<Self> {
+            Ok(self.clone())
+        }
+
+        fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+            self.0.borrow_mut().nonblocking = nonblocking;
+            Ok(())
+        }
+    }
+
+    pub(crate) fn connection(input: &[u8], open: bool) -> (Connection<MemoryStream>, Rc<RefCell<Pipe>>) {
+        let pipe: Rc<RefCell<Pipe>> = Rc::new(RefCell::new(Pipe { input: input.iter().copied().collect(), open, ..Pipe::default() }));
+
+        (Connection::new(MemoryStream(pipe.clone())).unwrap(), pipe)
+    }
+
+    // $<data>#<checksum>, the way GDB sends it
+    pub(crate) fn framed(data: &str) -> Vec<u8> {
+        format!("${}#{:02x}", data, checksum(data.as_bytes())).into_bytes()
+    }
+
+    // The data of every packet the stub sent, acks left out
+    pub(crate) fn sent(output: &[u8]) -> Vec<String> {
+        let (mut conn, _) = connection(output, false);
+
+        std::iter::from_fn(|| conn.read_packet().unwrap()).map(|packet| data(Some(packet))).collect()
+    }
+
+    fn data(packet: Option<Packet>) -> String {
+        match packet {
+            Some(Packet::Data(data)) => data,
+            Some(Packet::Interrupt) => panic!("interrupt instead of a packet"),
+            None => panic!("connection closed instead of a packet"),
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"?"), 0x3F);
+        assert_eq!(checksum(b"m200,4"), 0x5F);
+        assert_eq!(checksum(&[0xFF, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn reads_and_acks_a_packet() {
+        let (mut conn, pipe) = connection(b"+$m200,4#5f", false);
+
+        assert_eq!(data(conn.read_packet().unwrap()), "m200,4");
+        assert_eq!(pipe.borrow().output, b"+");
+        assert!(conn.read_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_checksum_is_nacked() {
+        let (mut conn, pipe) = connection(b"$g#00$g#67", false);
+
+        assert_eq!(data(conn.read_packet().unwrap()), "g");
+        assert_eq!(pipe.borrow().output, b"-+");
+    }
+
+    #[test]
+    fn no_ack_mode() {
+        let (mut conn, pipe) = connection(b"$g#00$?#3F", false);
+        conn.set_no_ack();
+
+        assert_eq!(data(conn.read_packet().unwrap()), "?");
+        assert!(pipe.borrow().output.is_empty());
+    }
+
+    #[test]
+    fn interrupt_and_closed() {
+        let (mut conn, _) = connection(b"\x03$g#6", false);
+
+        assert!(matches!(conn.read_packet().unwrap(), Some(Packet::Interrupt)));
+        assert!(conn.read_packet().unwrap().is_none());     // cut off in the checksum
+    }
+
+    #[test]
+    fn escapes() {
+        let (mut conn, pipe) = connection(b"", false);
+        conn.send("a$b#c}d*e").unwrap();
+
+        let escaped: &[u8] = b"a}\x04b}\x03c}]d}\x0ae";
+        let mut expected: Vec<u8> = [b"$", escaped, b"#"].concat();
+        expected.extend_from_slice(format!("{:02x}", checksum(escaped)).as_bytes());
+        assert_eq!(pipe.borrow().output, expected);
+
+        // What was sent reads back the same
+        let (mut conn, _) = connection(&expected, false);
+        assert_eq!(data(conn.read_packet().unwrap()), "a$b#c}d*e");
+    }
+
+    #[test]
+    fn poll_interrupt() {
+        let (mut conn, _) = connection(b"\x03", true);
+        assert!(conn.poll_interrupt().unwrap());
+        assert!(!conn.poll_interrupt().unwrap());
+
+        // A packet that is not an interrupt stays for read_packet()
+        let (mut conn, _) = connection(b"$?#3f", true);
+        assert!(!conn.poll_interrupt().unwrap());
+        assert_eq!(data(conn.read_packet().unwrap()), "?");
+    }
+
+    #[test]
+    fn poll_interrupt_after_disconnect() {
+        let (mut conn, _) = connection(b"", false);
+
+        assert_eq!(conn.poll_interrupt().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,433 @@
+// The commands of the remote protocol, mapped onto chip8_engine's Debugger. Only the packets GDB needs for
+// registers, memory, breakpoints, watchpoints, step and continue are implemented, every other one gets the
+// empty reply that tells GDB it is not supported.
+
+use std::io;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chip8_engine::*;
+
+use crate::packet::{Connection, Packet, Transport};
+
+const TARGET_XML: &str = include_str!("target.xml");
+
+const FRAME_TIME: Duration = Duration::from_micros(16_667);     // how often a running program checks for an interrupt
+
+// Register numbers, in the order of target.xml
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+const REG_COUNT: usize = 21;
+
+// Signals in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGABRT: u8 = 6;
+const SIGSEGV: u8 = 11;
+
+pub struct Server<S: Transport = TcpStream> {
+    chip8: Chip8,
+    debugger: Debugger,
+    conn: Connection<S>,
+}
+
+impl<S: Transport> Server<S> {
+    pub fn new(chip8: Chip8, conn: Connection<S>) -> Self {
+        // The program starts stopped on its first instruction, GDB decides when it runs
+        let mut debugger: Debugger = Debugger::new();
+        debugger.pause();
+
+        Self { chip8, debugger, conn }
+    }
+
+    // Serve one debugger session, returns once it detaches or disconnects
+    pub fn serve(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.conn.read_packet()? {
+            let Packet::Data(data) = packet else {
+                continue;       // already stopped
+            };
+
+            match data.as_str() {
+                "k" => return Ok(()),
+                "D" => return self.conn.send("OK"),
+                _ => {},
+            }
+
+            let reply: String = self.handle(&data)?;
+            self.conn.send(&reply)?;
+
+            // The OK to QStartNoAckMode is the last packet that gets acked
+            if data == "QStartNoAckMode" {
+                self.conn.set_no_ack();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, data: &str) -> io::Result<String> {
+        let reply: String = match data {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => self.read_registers(),
+            "s" | "vCont;s" | "vCont;s:1" => self.step(),
+            "c" | "vCont;c" | "vCont;c:1" => self.resume()?,
+            "vCont?" => "vCont;c;s".to_string(),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "QStartNoAckMode" => "OK".to_string(),
+            _ if data.starts_with("qSupported") => {
+                "PacketSize=4000;qXfer:features:read+;swbreak+;QStartNoAckMode+".to_string()
+            },
+            _ if data.starts_with("qXfer:features:read:target.xml:") => {
+                read_chunk(TARGET_XML, &data["qXfer:features:read:target.xml:".len()..])
+            },
+            _ if data.starts_with('G') => self.write_registers(&data[1..]),
+            _ if data.starts_with('p') => self.read_register(&data[1..]),
+            _ if data.starts_with('P') => self.write_register(&data[1..]),
+            _ if data.starts_with('m') => self.read_memory(&data[1..]),
+            _ if data.starts_with('M') => self.write_memory(&data[1..]),
+            _ if data.starts_with('Z') || data.starts_with('z') => self.breakpoint(data),
+            _ if data.starts_with('H') => "OK".to_string(),
+            _ => String::new(),
+        };
+
+        Ok(reply)
+    }
+
+    // REGISTERS
+
+    fn register(&self, reg: usize) -> Option<Vec<u8>> {
+        match reg {
+            0..=15 => Some(vec![self.chip8.registers()[reg]]),
+            REG_I => Some(self.chip8.index_register().to_le_bytes().to_vec()),
+            REG_PC => Some(self.chip8.pc().to_le_bytes().to_vec()),
+            REG_SP => Some(vec![self.chip8.stack().len() as u8]),
+            REG_DT => Some(vec![self.chip8.delay_timer()]),
+            REG_ST => Some(vec![self.chip8.sound_timer()]),
+            _ => None,
+        }
+    }
+
+    // The stack pointer is read-only, it only ever moves through 2nnn and 00EE. Returns false for an unknown register
+    fn set_register(&mut self, reg: usize, bytes: &[u8]) -> bool {
+        let word: u16 = u16::from_le_bytes([bytes[0], *bytes.get(1).unwrap_or(&0)]);
+
+        match reg {
+            0..=15 => self.chip8.set_register(reg, bytes[0]),
+            REG_I => self.chip8.set_index_register(word),
+            REG_PC => self.chip8.set_pc(word),
+            REG_SP => {},
+            REG_DT => self.chip8.set_delay_timer(bytes[0]),
+            REG_ST => self.chip8.set_sound_timer(bytes[0]),
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn read_registers(&self) -> String {
+        (0..REG_COUNT).filter_map(|reg| self.register(reg)).map(|bytes| to_hex(&bytes)).collect()
+    }
+
+    fn write_registers(&mut self, hex: &str) -> String {
+        let Some(bytes) = from_hex(hex) else {
+            return error(1);
+        };
+
+        let mut pos: usize = 0;
+        for reg in 0..REG_COUNT {
+            let size: usize = if reg == REG_I || reg == REG_PC {2} else {1};
+            let Some(val) = bytes.get(pos..pos + size) else {
+                return error(1);
+            };
+            self.set_register(reg, val);
+            pos += size;
+        }
+
+        "OK".to_string()
+    }
+
+    // p<reg>
+    fn read_register(&self, args: &str) -> String {
+        match usize::from_str_radix(args, 16).ok().and_then(|reg| self.register(reg)) {
+            Some(bytes) => to_hex(&bytes),
+            None => error(1),
+        }
+    }
+
+    // P<reg>=<value>
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((reg, val)) = args.split_once('=') else {
+            return error(1);
+        };
+
+        match (usize::from_str_radix(reg, 16), from_hex(val)) {
+            (Ok(reg), Some(bytes)) if !bytes.is_empty() && self.set_register(reg, &bytes) => "OK".to_string(),
+            _ => error(1),
+        }
+    }
+
+    // MEMORY
+
+    // m<addr>,<length>
+    fn read_memory(&self, args: &str) -> String {
+        let Some((addr, len)) = parse_range(args) else {
+            return error(1);
+        };
+
+        match self.chip8.memory().get(addr..addr.saturating_add(len)) {
+            Some(bytes) => to_hex(bytes),
+            None => error(14),     // EFAULT
+        }
+    }
+
+    // M<addr>,<length>:<bytes>
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, hex)) = args.split_once(':') else {
+            return error(1);
+        };
+        let (Some((addr, len)), Some(bytes)) = (parse_range(range), from_hex(hex)) else {
+            return error(1);
+        };
+        if bytes.len() != len || addr.saturating_add(len) > self.chip8.memory().len() {
+            return error(14);
+        }
+
+        for (offset, byte) in bytes.iter().enumerate() {
+            if self.chip8.set_memory(addr + offset, *byte).is_err() {
+                return error(14);
+            }
+        }
+
+        "OK".to_string()
+    }
+
+    // Z<type>,<addr>,<kind> inserts and z<type>,<addr>,<kind> removes. Type 0 and 1 are breakpoints
+    // (CHIP-8 has no breakpoint opcode, both are handled by the debugger), 2 to 4 are write, read and access watchpoints
+    fn breakpoint(&mut self, data: &str) -> String {
+        let insert: bool = data.starts_with('Z');
+        let mut fields = data[1..].split(',');
+
+        let (Some(kind), Some(addr)) = (fields.next(), fields.next().and_then(|addr| u16::from_str_radix(addr, 16).ok())) else {
+            return error(1);
+        };
+
+        let access: Access = match kind {
+            "0" | "1" => {
+                if insert {self.debugger.add_breakpoint(addr)} else {self.debugger.remove_breakpoint(addr)}
+                return "OK".to_string();
+            },
+            "2" => Access::Write,
+            "3" => Access::Read,
+            "4" => Access::ReadWrite,
+            _ => return String::new(),
+        };
+
+        if insert {self.debugger.watch_memory(addr, access)} else {self.debugger.unwatch_memory(addr)}
+        "OK".to_string()
+    }
+
+    // EXECUTION
+
+    fn step(&mut self) -> String {
+        self.debugger.step();
+
+        match self.debugger.run(&mut self.chip8, 1) {
+            Some(stop) => self.stop_reply(stop),
+            None => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    // Run in real time until something stops the program or GDB interrupts it. The scheduler decides how many
+    // instructions and timer ticks the time that went by is worth, at the speed set on the Chip8
+    fn resume(&mut self) -> io::Result<String> {
+        self.debugger.resume();
+        let mut last: Instant = Instant::now();
+
+        loop {
+            let now: Instant = Instant::now();
+            if let Err(stop) = self.debugger.run_for(&mut self.chip8, now - last) {
+                return Ok(self.stop_reply(stop));
+            }
+            last = now;
+
+            if self.chip8.is_halted() {
+                self.debugger.pause();
+                return Ok("W00".to_string());
+            }
+
+            if self.conn.poll_interrupt()? {
+                self.debugger.pause();
+                return Ok(format!("S{:02x}", SIGINT));
+            }
+
+            thread::sleep(FRAME_TIME);
+        }
+    }
+
+    fn stop_reply(&self, stop: Stop) -> String {
+        match stop {
+            Stop::Breakpoint(_) => format!("T{:02x}swbreak:;", SIGTRAP),
+            Stop::Watchpoint { addr, access: Access::Write } => format!("T{:02x}watch:{:x};", SIGTRAP, addr),
+            Stop::Watchpoint { addr, .. } => format!("T{:02x}rwatch:{:x};", SIGTRAP, addr),
+            Stop::Register { .. } | Stop::Step => format!("S{:02x}", SIGTRAP),
+            Stop::Error(Chip8Error::UnknownOpcode { .. }) => format!("S{:02x}", SIGILL),
+            Stop::Error(Chip8Error::MemoryOutOfBounds { .. }) => format!("S{:02x}", SIGSEGV),
+            Stop::Error(_) => format!("S{:02x}", SIGABRT),
+        }
+    }
+}
+
+// qXfer reads come as <offset>,<length>, the reply starts with m when there is more to read and l at the end
+fn read_chunk(document: &str, args: &str) -> String {
+    let Some((offset, len)) = parse_range(args) else {
+        return error(1);
+    };
+
+    let start: usize = offset.min(document.len());
+    let end: usize = offset.saturating_add(len).min(document.len());
+    let marker: char = if end < document.len() {'m'} else {'l'};
+
+    format!("{}{}", marker, &document[start..end])
+}
+
+// <addr>,<length> in hex
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (addr, len) = args.split_once(',')?;
+
+    Some((usize::from_str_radix(addr, 16).ok()?, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn error(code: u8) -> String {
+    format!("E{:02x}", code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::tests::{connection, framed, sent, MemoryStream};
+
+    const PROGRAM: &[u8] = &[
+        0x60, 0x05,     // 200: V0 = 5
+        0xA2, 0x0E,     // 202: I = 0x20E
+        0xF0, 0x55,     // 204: write V0 to 0x20E
+        0xF0, 0x65,     // 206: read it back
+        0x61, 0x07,     // 208: V1 = 7
+        0x00, 0xFD,     // 20A: exit
+        0x00, 0x00,
+        0x00,           // 20E: data
+    ];
+
+    // Serve one session of the raw bytes GDB sends, returns the replies and the server to look at afterwards
+    fn serve(input: &[u8]) -> (Vec<String>, Server<MemoryStream>) {
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_rom(PROGRAM).unwrap();
+
+        // Left open so a running program sees no input rather than a disconnect
+        let (conn, pipe) = connection(input, true);
+        let mut server: Server<MemoryStream> = Server::new(chip8, conn);
+        server.serve().unwrap();
+
+        let replies: Vec<String> = sent(&pipe.borrow().output);
+        (replies, server)
+    }
+
+    fn session(packets: &[&str]) -> (Vec<String>, Server<MemoryStream>) {
+        serve(&packets.iter().flat_map(|packet| framed(packet)).collect::<Vec<u8>>())
+    }
+
+    #[test]
+    fn registers() {
+        let (replies, server) = session(&[
+            "g", "p11", "P0=2a", "p0", "P10=3412", "p10", "P12=05", "p15", "P15=00", "Gzz",
+            "G00112233445566778899aabbccddeeff0c02fe0300a0b0",
+        ]);
+
+        // V0 to VF, then I and PC little-endian, SP, DT and ST
+        let start: String = "00".repeat(16) + "0000" + "0002" + "000000";
+        assert_eq!(replies, [
+            start.as_str(), "0002", "OK", "2a", "OK", "3412",
+            "OK",                   // SP is read-only, the write is ignored
+            "E01", "E01", "E01",    // register 21 does not exist, nor does a G without hex
+            "OK",
+        ]);
+
+        let chip8: &Chip8 = &server.chip8;
+        assert_eq!(chip8.registers()[..3], [0x00, 0x11, 0x22]);
+        assert_eq!(chip8.registers()[15], 0xFF);
+        assert_eq!((chip8.index_register(), chip8.pc(), chip8.stack().len()), (0x020C, 0x03FE, 0));
+        assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0xA0, 0xB0));
+    }
+
+    #[test]
+    fn memory() {
+        let (replies, _) = session(&["m200,4", "M20e,2:abcd", "m20e,2", "mfff,2", "M20e,2:ab", "Mfff,2:abcd", "m200"]);
+
+        assert_eq!(replies, ["6005a20e", "OK", "abcd", "E0e", "E0e", "E0e", "E01"]);
+    }
+
+    #[test]
+    fn breakpoints_and_steps() {
+        let (replies, server) = session(&["?", "Z0,208,2", "c", "p11", "s", "p11", "z0,208,2", "c"]);
+
+        assert_eq!(replies, ["S05", "OK", "T05swbreak:;", "0802", "S05", "0a02", "OK", "W00"]);
+        assert!(server.chip8.is_halted());
+        assert_eq!(server.chip8.registers()[1], 7);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let (replies, _) = session(&["Z2,20e,1", "c", "p11", "z2,20e,1", "Z3,20e,1", "c", "p11", "z3,20e,1", "Z4,20e,1", "c"]);
+
+        assert_eq!(replies, [
+            "OK", "T05watch:20e;", "0602",
+            "OK", "OK", "T05rwatch:20e;", "0802",
+            "OK", "OK", "W00",      // nothing touches 0x20E anymore
+        ]);
+    }
+
+    #[test]
+    fn error_stops() {
+        // 0x5001 is no instruction at all
+        let (replies, server) = session(&["M208,2:5001", "c"]);
+
+        assert_eq!(replies, ["OK", "S04"]);
+        assert!(server.debugger.is_paused());
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut input: Vec<u8> = [framed("M200,2:1200"), framed("c")].concat();
+        input.push(0x03);
+        let (replies, server) = serve(&input);
+
+        assert_eq!(replies, ["OK", "S02"]);
+        assert_eq!(server.chip8.pc(), 0x200);
+    }
+
+    #[test]
+    fn detach() {
+        let (replies, _) = session(&["D", "g"]);
+
+        assert_eq!(replies, ["OK"]);
+    }
+}
@@ -0,0 +1,361 @@
+use chip8_engine::*;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::process;
+
+mod record;
+
+use record::{Difference, Record};
+
+const DEFAULT_CONTEXT: usize = 5;       // records shown before the divergence
+
+const USAGE: &str = "Usage:
+  cargo run -- ours.trace reference.trace [--context N]
+      compare two traces and report the first instruction where they diverge
+  cargo run -- --lockstep path/to/game reference.trace [options]
+      run the game and stop at the first instruction that does not match the reference
+      --quirks PROFILE  vip, chip48, schip or xochip
+      --speed N         instructions per second, the timers tick every speed / 60 instructions (default 600)
+      --seed N          random number seed (default 0)
+      --keep-random     do not copy the reference's Cxnn results into our registers
+  --context N       records shown before the divergence (default 5)
+
+Traces are JSON lines or text from the tracer (headless --trace, F11 on desktop), or a register dump
+with one 'PC:0200 OP:6005 V0:00 ... VF:00 I:0000 SP:0' line per instruction.
+Exits with 0 when the traces agree, 1 on a divergence and 2 on usage or IO errors";
+
+struct Options {
+    lockstep: bool,
+    files: Vec<String>,
+    context: usize,
+    quirks: Quirks,
+    speed: u32,
+    seed: u64,
+    sync_random: bool,
+}
+
+// Our side of the comparison. Lockstep only keeps the records the report can show, the ones from `first` on
+struct Ours {
+    first: usize,
+    records: Vec<Record>,
+    total: usize,                   // instructions compared
+}
+
+impl Ours {
+    fn get(&self, cycle: usize) -> Option<&Record> {
+        cycle.checked_sub(self.first).and_then(|idx: usize| self.records.get(idx))
+    }
+}
+
+// Where two traces first disagree
+struct Divergence {
+    cycle: usize,
+    differences: Vec<Difference>,
+    note: Option<String>,           // why there is no record to compare, e.g. our machine stopped on an error
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options: Options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let reference: Vec<Record> = read_trace(&options.files[1]);
+    let (ours, divergence): (Ours, Option<Divergence>) = if options.lockstep {
+        let rom: Vec<u8> = fs::read(&options.files[0]).unwrap_or_else(|err| {
+            println!("Unable to read {}: {}", options.files[0], err);
+            process::exit(2);
+        });
+        lockstep(&options, &rom, &reference)
+    } else {
+        let records: Vec<Record> = read_trace(&options.files[0]);
+        let divergence: Option<Divergence> = diff(&records, &reference);
+        (Ours { first: 0, total: records.len(), records }, divergence)
+    };
+
+    match divergence {
+        Some(divergence) => {
+            report(&divergence, &ours, &reference, options.context);
+            process::exit(1);
+        }
+        None if ours.total == reference.len() => println!("No divergence in {} instructions", ours.total),
+        None => println!(
+            "No divergence in the first {} instructions (ours has {}, the reference {})",
+            ours.total.min(reference.len()), ours.total, reference.len()
+        ),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options: Options = Options {
+        lockstep: false,
+        files: Vec::new(),
+        context: DEFAULT_CONTEXT,
+        quirks: Quirks::default(),
+        speed: DEFAULT_SPEED,
+        seed: 0,
+        sync_random: true,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lockstep" => options.lockstep = true,
+            "--keep-random" => options.sync_random = false,
+            "--context" | "--quirks" | "--speed" | "--seed" => {
+                let value: &String = args.next().ok_or(format!("Missing value for {}", arg))?;
+                let number = || value.parse::<u64>().map_err(|_| format!("Invalid number for {}: {}", arg, value));
+
+                match arg.as_str() {
+                    "--context" => options.context = number()? as usize,
+                    "--speed" => options.speed = number()?.min(u32::MAX as u64) as u32,
+                    "--seed" => options.seed = number()?,
+                    _ => options.quirks = match value.as_str() {
+                        "vip" =>        Quirks::VIP,
+                        "chip48" =>     Quirks::CHIP48,
+                        "schip" =>      Quirks::SCHIP,
+                        "xochip" =>     Quirks::XOCHIP,
+                        other => return Err(format!("Unknown quirk profile: {}", other)),
+                    },
+                }
+            }
+            other if other.starts_with("--") => return Err(format!("Unknown option: {}", other)),
+            file => options.files.push(file.to_string()),
+        }
+    }
+
+    if options.files.len() != 2 {
+        return Err("Expected two files".to_string());
+    }
+
+    Ok(options)
+}
+
+fn read_trace(path: &str) -> Vec<Record> {
+    let text: String = fs::read_to_string(path).unwrap_or_else(|err| {
+        println!("Unable to read {}: {}", path, err);
+        process::exit(2);
+    });
+
+    record::parse(&text).unwrap_or_else(|err| {
+        println!("{}:{}", path, err);
+        process::exit(2);
+    })
+}
+
+fn diff(ours: &[Record], reference: &[Record]) -> Option<Divergence> {
+    ours.iter()
+        .zip(reference)
+        .map(|(ours, reference)| ours.compare(reference))
+        .position(|differences: Vec<Difference>| !differences.is_empty())
+        .map(|cycle: usize| Divergence { cycle, differences: ours[cycle].compare(&reference[cycle]), note: None })
+}
+
+// Run the ROM one instruction at a time, comparing the state before every instruction with the reference.
+// The engine's scheduler decides how many instructions a frame gets and counts the timers down after it, the
+// same as run_frame(). Random numbers cannot match another emulator's, so unless told otherwise the result of
+// every Cxnn is taken from the reference's next record.
+fn lockstep(options: &Options, rom: &[u8], reference: &[Record]) -> (Ours, Option<Divergence>) {
+    let mut chip8: Chip8 = Chip8::with_quirks(options.quirks);
+    chip8.set_xo_chip(options.quirks == Quirks::XOCHIP);
+    chip8.set_seed(options.seed);
+    chip8.set_speed(options.speed);
+    if let Err(err) = chip8.load_rom(rom) {
+        println!("Unable to load ROM: {}", err);
+        process::exit(2);
+    }
+
+    let mut window: VecDeque<Record> = VecDeque::new();
+    let mut cycle: usize = 0;
+
+    // Err(None) once the reference runs out
+    let mut run = |chip8: &mut Chip8, instructions: usize| -> Result<(), Option<Divergence>> {
+        for _ in 0..instructions {
+            let Some(expected) = reference.get(cycle) else {
+                return Err(None);
+            };
+
+            let record: Record = Record::from_chip8(chip8);
+            window.push_back(record);
+            if window.len() > options.context + 1 {
+                window.pop_front();
+            }
+
+            let differences: Vec<Difference> = record.compare(expected);
+            if !differences.is_empty() {
+                return Err(Some(Divergence { cycle, differences, note: None }));
+            }
+
+            if chip8.is_halted() {
+                let note: String = "ours halted (00FD), the reference keeps going".to_string();
+                return Err(Some(Divergence { cycle, differences, note: Some(note) }));
+            }
+
+            if let Err(err) = chip8.tick() {
+                let note: String = format!("ours stopped: {}", err);
+                return Err(Some(Divergence { cycle, differences, note: Some(note) }));
+            }
+
+            if let Some(Instruction::Random(x, _)) = record.opcode().map(Instruction::decode)
+                && options.sync_random
+                && let Some(value) = reference.get(cycle + 1).and_then(|next: &Record| next.register(x as usize)) {
+                chip8.set_register(x as usize, value);
+            }
+
+            cycle += 1;
+        }
+
+        Ok(())
+    };
+
+    let divergence: Option<Divergence> = loop {
+        if let Err(divergence) = chip8.run_frame_with(&mut run) {
+            break divergence;
+        }
+    };
+
+    let total: usize = divergence.as_ref().map_or(reference.len(), |divergence: &Divergence| divergence.cycle + 1);
+    (Ours { first: total - window.len(), records: window.into(), total }, divergence)
+}
+
+fn report(divergence: &Divergence, ours: &Ours, reference: &[Record], context: usize) {
+    let cycle: usize = divergence.cycle;
+    let start: usize = cycle.saturating_sub(context);
+
+    println!("Diverged at instruction {}", cycle);
+    for difference in &divergence.differences {
+        println!("  {}", difference);
+    }
+    if let Some(note) = &divergence.note {
+        println!("  {}", note);
+    }
+
+    println!();
+    for idx in start..=cycle {
+        let marker: &str = if idx == cycle {">"} else {" "};
+        if let Some(record) = ours.get(idx) {
+            println!("{} {:>8} ours       {}", marker, idx, record);
+        }
+        if let Some(record) = reference.get(idx) {
+            println!("{} {:>8} reference  {}    (line {})", marker, idx, record, record.line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use record::tests::{CYCLES, RANDOM_CYCLE, ROM, trace};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn options(args: &[&str]) -> Options {
+        let args: Vec<String> = args.iter().map(|arg: &&str| arg.to_string()).collect();
+        parse_args(&args).unwrap()
+    }
+
+    // Our trace as a register dump, with `field` of record `cycle` replaced
+    fn injected(cycle: usize, field: &str, value: &str) -> Vec<Record> {
+        let (_, states) = trace(0, TraceFormat::JsonLines);
+
+        let mut text: String = String::new();
+        for (idx, record) in states.iter().enumerate() {
+            for token in record.to_string().split(' ') {
+                match token.split_once(':') {
+                    Some((name, _)) if idx == cycle && name == field => text.push_str(&format!("{}:{} ", name, value)),
+                    _ => text.push_str(&format!("{} ", token)),
+                }
+            }
+            text.push('\n');
+        }
+
+        record::parse(&text).unwrap()
+    }
+
+    fn fields(divergence: &Divergence) -> Vec<&'static str> {
+        divergence.differences.iter().map(|difference: &Difference| difference.field).collect()
+    }
+
+    #[test]
+    fn diff_finds_the_first_divergence() {
+        let ours: Vec<Record> = record::parse(&trace(0, TraceFormat::JsonLines).0).unwrap();
+        let theirs: Vec<Record> = record::parse(&trace(0, TraceFormat::Text).0).unwrap();
+        assert!(diff(&ours, &theirs).is_none());
+
+        let divergence: Divergence = diff(&ours, &injected(4, "V0", "0D")).unwrap();
+        assert_eq!(divergence.cycle, 4);
+        assert_eq!(divergence.differences, [Difference { field: "V0", ours: "0C".to_string(), reference: "0D".to_string() }]);
+
+        // Only the first one counts
+        let mut reference: Vec<Record> = injected(6, "SP", "2");
+        reference[8] = injected(8, "I", "0000")[8];
+        assert_eq!(diff(&ours, &reference).unwrap().cycle, 6);
+    }
+
+    #[test]
+    fn lockstep_agrees_with_our_own_trace() {
+        let reference: Vec<Record> = record::parse(&trace(0, TraceFormat::JsonLines).0).unwrap();
+        let (ours, divergence) = lockstep(&options(&["--lockstep", "rom", "reference"]), &ROM, &reference);
+
+        assert!(divergence.is_none());
+        assert_eq!(ours.total, CYCLES);
+    }
+
+    #[test]
+    fn lockstep_stops_on_a_mismatch() {
+        let reference: Vec<Record> = injected(5, "I", "02F1");
+        let (ours, divergence) = lockstep(&options(&["--lockstep", "--context", "2", "rom", "reference"]), &ROM, &reference);
+        let divergence: Divergence = divergence.unwrap();
+
+        assert_eq!((divergence.cycle, fields(&divergence)), (5, vec!["I"]));
+        assert_eq!(ours.total, 6);                              // nothing ran past the mismatch
+        assert_eq!((ours.first, ours.records.len()), (3, 3));   // the context and the diverging record
+        assert_eq!(ours.get(5).and_then(Record::opcode), Some(0x7201));
+    }
+
+    #[test]
+    fn lockstep_takes_random_numbers_from_the_reference() {
+        let (_, ours) = trace(0, TraceFormat::JsonLines);
+        let (text, theirs) = trace(1, TraceFormat::JsonLines);
+        assert_ne!(ours[RANDOM_CYCLE + 1].register(3), theirs[RANDOM_CYCLE + 1].register(3));
+
+        let reference: Vec<Record> = record::parse(&text).unwrap();
+        let (_, divergence) = lockstep(&options(&["--lockstep", "rom", "reference"]), &ROM, &reference);
+        assert!(divergence.is_none());
+
+        let (_, divergence) = lockstep(&options(&["--lockstep", "--keep-random", "rom", "reference"]), &ROM, &reference);
+        let divergence: Divergence = divergence.unwrap();
+        assert_eq!((divergence.cycle, fields(&divergence)), (RANDOM_CYCLE + 1, vec!["V3"]));
+    }
+
+    #[test]
+    fn lockstep_counts_the_timers_down_like_run_frame() {
+        // DT = 0x20, then read it into V1 over and over
+        let rom: [u8; 8] = [0x60, 0x20, 0xF0, 0x15, 0xF1, 0x07, 0x12, 0x04];
+
+        // Traced frame by frame at 700 instructions per second, 11 or 12 to a frame
+        let buffer: Rc<RefCell<TraceBuffer>> = Rc::new(RefCell::new(TraceBuffer::new(1000)));
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.load_rom(&rom).unwrap();
+        chip8.set_speed(700);
+        chip8.set_tracer(buffer.clone());
+        for _ in 0..10 {
+            chip8.run_frame().unwrap();
+        }
+        let reference: Vec<Record> = record::parse(&buffer.borrow().dump(TraceFormat::JsonLines)).unwrap();
+
+        let (ours, divergence) = lockstep(&options(&["--lockstep", "--speed", "700", "rom", "reference"]), &rom, &reference);
+        assert!(divergence.is_none());
+        assert_eq!(ours.total, reference.len());
+
+        // At 600 the first frame ends an instruction early and V1 sees the timer tick too soon
+        let (_, divergence) = lockstep(&options(&["--lockstep", "rom", "reference"]), &rom, &reference);
+        let divergence: Divergence = divergence.unwrap();
+        assert_eq!((divergence.cycle, fields(&divergence)), (11, vec!["V1"]));
+    }
+}
@@ -0,0 +1,314 @@
+// One instruction of a trace: the machine state right before it runs. Traces come in three formats, told
+// apart by their first line:
+//
+//   JSON lines from the engine's tracer         {"cycle":0,"pc":512,"opcode":24581,...,"before":{...}}
+//   text from the engine's tracer               0 0200 6005 LD V0, 0x05   V=00 00 ... I=0000 SP=0 | ...
+//   a register dump, the usual reference format PC:0200 OP:6005 V0:00 V1:00 ... VF:00 I:0000 SP:0
+//
+// In a register dump fields are NAME:VALUE or NAME=VALUE in hex, separated by spaces or commas, in any order
+// and any case. Fields it does not have are not compared, unknown fields (DT, ST, ...) are ignored.
+
+use chip8_engine::*;
+use serde_json::Value;
+use std::fmt;
+
+const V_REGISTERS: usize = 16;
+
+// PC, OP, V0 - VF, I, SP
+const FIELDS: usize = 20;
+const PC: usize = 0;
+const OPCODE: usize = 1;
+const V0: usize = 2;
+const I: usize = 18;
+const SP: usize = 19;
+
+const NAMES: [&str; FIELDS] = [
+    "PC", "OP",
+    "V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF",
+    "I", "SP",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub line: usize,                    // in the file it came from, 0 when it did not come from a file
+    values: [Option<u16>; FIELDS],
+}
+
+// A field both records have, with different values, both already in hex
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    pub field: &'static str,
+    pub ours: String,
+    pub reference: String,
+}
+
+impl Record {
+    fn empty() -> Self {
+        Record { line: 0, values: [None; FIELDS] }
+    }
+
+    // The state of a machine about to execute its next instruction
+    pub fn from_chip8(chip8: &Chip8) -> Self {
+        let pc: u16 = chip8.pc();
+
+        let mut record: Record = Record::empty();
+        record.values[PC] = Some(pc);
+        record.values[OPCODE] = Some(chip8.opcode_at(pc));
+        for (reg, value) in chip8.registers().iter().enumerate() {
+            record.values[V0 + reg] = Some(*value as u16);
+        }
+        record.values[I] = Some(chip8.index_register());
+        record.values[SP] = Some(chip8.stack().len() as u16);
+
+        record
+    }
+
+    pub fn opcode(&self) -> Option<u16> {
+        self.values[OPCODE]
+    }
+
+    pub fn register(&self, reg: usize) -> Option<u8> {
+        self.values[V0 + reg].map(|value: u16| value as u8)
+    }
+
+    // Every field that is in both and differs, an empty list when the records agree
+    pub fn compare(&self, reference: &Record) -> Vec<Difference> {
+        (0..FIELDS)
+            .filter_map(|field: usize| match (self.values[field], reference.values[field]) {
+                (Some(ours), Some(theirs)) if ours != theirs => Some(Difference {
+                    field: NAMES[field],
+                    ours: hex(field, ours),
+                    reference: hex(field, theirs),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// The fields it has, as a register dump
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = (0..FIELDS)
+            .filter_map(|field: usize| self.values[field].map(|value: u16| format!("{}:{}", NAMES[field], hex(field, value))))
+            .collect();
+
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is {}, reference has {}", self.field, self.ours, self.reference)
+    }
+}
+
+// Registers as 2 digits, addresses and opcodes as 4, SP as 1
+fn hex(field: usize, value: u16) -> String {
+    match field {
+        PC | OPCODE | I => format!("{:04X}", value),
+        SP => format!("{:X}", value),
+        _ => format!("{:02X}", value),
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<Record>, String> {
+    let first: Option<&str> = text.lines().map(str::trim).find(|line: &&str| !line.is_empty());
+    let parse_line: fn(&str) -> Result<Record, String> = match first {
+        Some(line) if line.starts_with('{') => parse_json,
+        Some(line) if line.contains(" | ") && line.contains("V=") => parse_text,
+        _ => parse_dump,
+    };
+
+    let mut records: Vec<Record> = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut record: Record = parse_line(line).map_err(|err| format!("line {}: {}", idx + 1, err))?;
+        record.line = idx + 1;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+fn parse_json(line: &str) -> Result<Record, String> {
+    let entry: Value = serde_json::from_str(line).map_err(|err| err.to_string())?;
+    let number = |value: &Value, name: &str| -> Result<u16, String> {
+        value.get(name).and_then(Value::as_u64).map(|value: u64| value as u16).ok_or(format!("missing {}", name))
+    };
+
+    let mut record: Record = Record::empty();
+    record.values[PC] = Some(number(&entry, "pc")?);
+    record.values[OPCODE] = Some(number(&entry, "opcode")?);
+
+    let before: &Value = entry.get("before").ok_or("missing before")?;
+    let v: &Vec<Value> = before.get("v").and_then(Value::as_array).ok_or("missing before.v")?;
+    if v.len() != V_REGISTERS {
+        return Err(format!("expected {} registers, got {}", V_REGISTERS, v.len()));
+    }
+    for (reg, value) in v.iter().enumerate() {
+        record.values[V0 + reg] = Some(value.as_u64().ok_or("invalid register")? as u16);
+    }
+    record.values[I] = Some(number(before, "i")?);
+    record.values[SP] = Some(number(before, "sp")?);
+
+    Ok(record)
+}
+
+// Only the state before the instruction, left of the |
+fn parse_text(line: &str) -> Result<Record, String> {
+    let before: &str = line.split(" | ").next().unwrap_or("");
+    let tokens: Vec<&str> = before.split_whitespace().collect();
+    if tokens.len() < 3 {
+        return Err("expected a cycle, a PC and an opcode".to_string());
+    }
+
+    let mut record: Record = Record::empty();
+    record.values[PC] = Some(parse_hex(tokens[1])?);
+    record.values[OPCODE] = Some(parse_hex(tokens[2])?);
+
+    let v: usize = tokens.iter().position(|token: &&str| token.starts_with("V=")).ok_or("missing V=")?;
+    let registers: Vec<&str> = tokens[v..].iter().take(V_REGISTERS).copied().collect();
+    if registers.len() != V_REGISTERS {
+        return Err("expected 16 registers after V=".to_string());
+    }
+    for (reg, value) in registers.iter().enumerate() {
+        record.values[V0 + reg] = Some(parse_hex(value.trim_start_matches("V="))?);
+    }
+
+    for token in &tokens[v + V_REGISTERS..] {
+        if let Some(value) = token.strip_prefix("I=") {
+            record.values[I] = Some(parse_hex(value)?);
+        } else if let Some(value) = token.strip_prefix("SP=") {
+            record.values[SP] = Some(parse_hex(value)?);
+        }
+    }
+
+    Ok(record)
+}
+
+fn parse_dump(line: &str) -> Result<Record, String> {
+    let mut record: Record = Record::empty();
+
+    for token in line.split(|c: char| c.is_whitespace() || c == ',').filter(|token: &&str| !token.is_empty()) {
+        let Some((name, value)) = token.split_once([':', '=']) else {
+            return Err(format!("expected NAME:VALUE, got '{}'", token));
+        };
+
+        let name: String = name.to_ascii_uppercase();
+        let field: Option<usize> = match name.as_str() {
+            "OPCODE" => Some(OPCODE),
+            name => NAMES.iter().position(|known: &&str| *known == name),
+        };
+        if let Some(field) = field {
+            record.values[field] = Some(parse_hex(value)?);
+        }
+    }
+
+    if record.values == [None; FIELDS] {
+        return Err("no known fields".to_string());
+    }
+
+    Ok(record)
+}
+
+fn parse_hex(text: &str) -> Result<u16, String> {
+    let digits: &str = text.trim_start_matches("0x").trim_start_matches("0X");
+
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid hex value '{}'", text))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Loads, an add, a call and return, a random number, then a jump to itself
+    pub const ROM: [u8; 20] = [
+        0x60, 0x05,         // 0200  LD V0, 0x05
+        0x61, 0x07,         // 0202  LD V1, 0x07
+        0x80, 0x14,         // 0204  ADD V0, V1
+        0xA2, 0xF0,         // 0206  LD I, 0x2F0
+        0x22, 0x0E,         // 0208  CALL 0x20E
+        0xC3, 0xFF,         // 020A  RND V3, 0xFF
+        0x12, 0x0C,         // 020C  JP 0x20C
+        0x72, 0x01,         // 020E  ADD V2, 0x01
+        0x00, 0xEE,         // 0210  RET
+        0x00, 0x00,
+    ];
+    pub const CYCLES: usize = 10;
+    pub const RANDOM_CYCLE: usize = 7;         // the RND, V3 is only different in the record after it
+
+    // What our own tracer writes for ROM, and the state before every instruction
+    pub fn trace(seed: u64, format: TraceFormat) -> (String, Vec<Record>) {
+        let buffer: Rc<RefCell<TraceBuffer>> = Rc::new(RefCell::new(TraceBuffer::new(CYCLES)));
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.set_seed(seed);
+        chip8.load_rom(&ROM).unwrap();
+        chip8.set_tracer(buffer.clone());
+
+        let mut states: Vec<Record> = Vec::new();
+        for _ in 0..CYCLES {
+            states.push(Record::from_chip8(&chip8));
+            chip8.tick().unwrap();
+        }
+
+        let text: String = buffer.borrow().dump(format);
+        (text, states)
+    }
+
+    fn without_lines(records: Vec<Record>) -> Vec<Record> {
+        records.into_iter().map(|record: Record| Record { line: 0, ..record }).collect()
+    }
+
+    #[test]
+    fn parses_our_json_lines() {
+        let (text, states) = trace(0, TraceFormat::JsonLines);
+        let records: Vec<Record> = parse(&text).unwrap();
+
+        assert_eq!(records.iter().map(|record: &Record| record.line).collect::<Vec<usize>>(), (1..=CYCLES).collect::<Vec<usize>>());
+        assert_eq!(without_lines(records), states);
+    }
+
+    #[test]
+    fn parses_our_text() {
+        let (text, states) = trace(0, TraceFormat::Text);
+        let records: Vec<Record> = parse(&text).unwrap();
+
+        assert_eq!(without_lines(records), states);
+        assert_eq!(states[6].to_string(), "PC:0210 OP:00EE V0:0C V1:07 V2:01 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 VB:00 VC:00 VD:00 VE:00 VF:00 I:02F0 SP:1");
+    }
+
+    #[test]
+    fn parses_a_register_dump() {
+        let text: &str = "\
+            # from another emulator\n\
+            PC:0200 OP:6005 V0:00 V1:00 VF:00 I:0000 SP:0\n\
+            \n\
+            pc=0x0202, opcode=6107, v0=05, dt=3C, i=0000\n";
+        let records: Vec<Record> = parse(text).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].line, records[1].line), (2, 4));
+        assert_eq!((records[1].opcode(), records[1].register(0), records[1].register(1)), (Some(0x6107), Some(0x05), None));
+        assert_eq!(records[1].to_string(), "PC:0202 OP:6107 V0:05 I:0000");
+
+        // Fields the dump does not have are not compared
+        let (_, states) = trace(0, TraceFormat::Text);
+        assert!(states[0].compare(&records[0]).is_empty());
+        assert!(states[1].compare(&records[1]).is_empty());
+        assert_eq!(states[2].compare(&records[1]).iter().map(|difference: &Difference| difference.field).collect::<Vec<&str>>(), ["PC", "OP"]);
+    }
+
+    #[test]
+    fn reports_the_line_that_does_not_parse() {
+        assert_eq!(parse("PC:0200 OP:6005\nPC:0202 OP:zz").unwrap_err(), "line 2: invalid hex value 'zz'");
+        assert_eq!(parse("PC:0200\nDT:3C").unwrap_err(), "line 2: no known fields");
+        assert!(parse("{\"pc\":512}").unwrap_err().starts_with("line 1: missing opcode"));
+    }
+}
@@ -1,29 +1,52 @@
 use chip8_engine::*;
+use chip8_frontend::{Audio, Display, Keypad, Runner, SystemClock, PALETTE};
+use std::cell::RefCell;
+use std::fs;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufWriter, Read};
 use std::env;
+use std::rc::Rc;
 
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
 
 const SCALE: u32 = 15;
 const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
 const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
 
-const TICKS_PER_FRAME: usize = 10;
+// Rewind history: a snapshot every 2 frames, 600 snapshots is 20 seconds at 60 FPS
+const REWIND_INTERVAL: usize = 2;
+const REWIND_CAPACITY: usize = 600;
+
+const AUDIO_SAMPLE_RATE: i32 = 44100;
+
+type SdlRunner<'a> = Runner<SdlDisplay<'a>, Keypad, Option<Speaker>, SystemClock>;
 
 fn main() {
     // Command Line argument
     let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: cargo run path/to/game");
+    if args.len() != 2 && args.len() != 3 {
+        println!("Usage: cargo run path/to/game [vip|chip48|schip|xochip]");
         return;
     }
 
+    let quirks: Quirks = match args.get(2).map(|s| s.as_str()) {
+        None =>             Quirks::default(),
+        Some("vip") =>      Quirks::VIP,
+        Some("chip48") =>   Quirks::CHIP48,
+        Some("schip") =>    Quirks::SCHIP,
+        Some("xochip") =>   Quirks::XOCHIP,
+        Some(other) => {
+            println!("Unknown quirk profile: {}", other);
+            return;
+        }
+    };
+
     // Initialize SDL2 Window
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
@@ -40,15 +63,52 @@ fn main() {
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
+    // The display lives in a texture at its native resolution, only rows that changed are written to it
+    // and the GPU scales it up to the window every frame
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, HIRES_SCREEN_WIDTH as u32, HIRES_SCREEN_HEIGHT as u32)
+                .unwrap();
+
+    // Buzzer, keeps playing silence until the sound timer runs. A missing audio device is not fatal
+    let audio: Option<Speaker> = match open_audio(&sdl_context) {
+        Ok(device) => Some(Speaker(device)),
+        Err(err) => {
+            println!("Sound disabled: {}", err);
+            None
+        }
+    };
+
     // Instance of Chip8
-    let mut chip8: Chip8 = Chip8::new(false);
+    let mut chip8: Chip8 = Chip8::with_quirks(quirks);
+    chip8.set_xo_chip(quirks == Quirks::XOCHIP);
 
     // Get and Load ROM to Chip8
     let mut rom: File = File::open(&args[1]).expect("Unable to open file");
     let mut buffer:Vec<u8>  = Vec::new();
 
     rom.read_to_end(&mut buffer).unwrap();
-    chip8.load_rom(&buffer);
+    if let Err(err) = chip8.load_rom(&buffer) {
+        println!("Unable to load ROM: {}", err);
+        return;
+    }
+
+    // Quick-save slot, next to the ROM
+    let state_path: String = format!("{}.state", &args[1]);
+
+    // F11 starts and stops an instruction trace, next to the ROM
+    let trace_path: String = format!("{}.trace", &args[1]);
+    let mut trace: Option<Rc<RefCell<TraceWriter<BufWriter<File>>>>> = None;
+
+    // F12 starts profiling, pressing it again writes the report next to the ROM
+    let profile_path: String = format!("{}.profile", &args[1]);
+
+    // The engine works out how much to run from the time between iterations, whatever the refresh rate.
+    // F6 pauses and continues, F7 steps, F8 steps over a call and F10 steps out of the current one
+    let mut runner: SdlRunner = Runner::new(chip8, SdlDisplay { canvas, texture }, Keypad::new(), audio, SystemClock::new());
+
+    // Holding backspace rewinds the game instead of running it
+    runner.set_rewind(Rewind::new(REWIND_INTERVAL, REWIND_CAPACITY));
 
     // Gameloop
     'gameloop: loop{
@@ -57,70 +117,170 @@ fn main() {
                 Event::Quit {..}=> { 
                     break 'gameloop; 
                 },
-                Event::KeyDown{keycode: Some(key), ..} => {
-                    if let Some(k) = key2btn(key) {
-                        chip8.set_keypad(k, true);
+                Event::KeyDown{keycode: Some(Keycode::F5), repeat: false, ..} => {
+                    match fs::write(&state_path, runner.chip8().save_state()) {
+                        Ok(()) => println!("Saved state to {}", state_path),
+                        Err(err) => println!("Unable to save state: {}", err),
                     }
                 },
-                Event::KeyUp{keycode: Some(key), ..} => {
-                    if let Some(k) = key2btn(key) {
-                        chip8.set_keypad(k, false);
+                Event::KeyDown{keycode: Some(Keycode::F9), repeat: false, ..} => {
+                    match fs::read(&state_path) {
+                        Ok(state) => match runner.chip8_mut().load_state(&state) {
+                            Ok(()) => println!("Loaded state from {}", state_path),
+                            Err(err) => println!("Unable to load state: {}", err),
+                        },
+                        Err(err) => println!("Unable to read {}: {}", state_path, err),
+                    }
+                },
+                Event::KeyDown{keycode: Some(Keycode::F6), repeat: false, ..} => {
+                    if runner.debugger().is_paused() {
+                        runner.debugger_mut().resume();
+                    } else {
+                        runner.debugger_mut().pause();
+                        print_debug_state(runner.chip8(), "paused");
+                    }
+                },
+                Event::KeyDown{keycode: Some(Keycode::F7), ..} => {
+                    runner.debugger_mut().step();
+                },
+                Event::KeyDown{keycode: Some(Keycode::F8), ..} => {
+                    runner.step_over();
+                },
+                Event::KeyDown{keycode: Some(Keycode::F10), ..} => {
+                    runner.step_out();
+                },
+                Event::KeyDown{keycode: Some(Keycode::F11), repeat: false, ..} => {
+                    match trace.take() {
+                        Some(writer) => {
+                            runner.chip8_mut().take_tracer();
+                            match writer.borrow_mut().flush() {
+                                Ok(()) => println!("Trace written to {}", trace_path),
+                                Err(err) => println!("Unable to write {}: {}", trace_path, err),
+                            }
+                        },
+                        None => match File::create(&trace_path) {
+                            Ok(file) => {
+                                let writer = Rc::new(RefCell::new(TraceWriter::new(BufWriter::new(file), TraceFormat::Text)));
+                                runner.chip8_mut().set_tracer(writer.clone());
+                                trace = Some(writer);
+                                println!("Tracing to {}", trace_path);
+                            },
+                            Err(err) => println!("Unable to create {}: {}", trace_path, err),
+                        },
                     }
                 },
+                Event::KeyDown{keycode: Some(Keycode::F12), repeat: false, ..} => {
+                    match runner.chip8_mut().take_profiler() {
+                        Some(profiler) => match fs::write(&profile_path, profiler.report(ProfileFormat::Text)) {
+                            Ok(()) => println!("Profile written to {}", profile_path),
+                            Err(err) => println!("Unable to write {}: {}", profile_path, err),
+                        },
+                        None => {
+                            runner.chip8_mut().set_profiler(Profiler::new());
+                            println!("Profiling");
+                        },
+                    }
+                },
+                Event::KeyDown{keycode: Some(Keycode::Backspace), ..} => {
+                    runner.set_rewinding(true);
+                },
+                Event::KeyUp{keycode: Some(Keycode::Backspace), ..} => {
+                    runner.set_rewinding(false);
+                },
+                Event::KeyDown{keycode: Some(key), ..} => {
+                    runner.input_mut().set_key(&key.name(), true);
+                },
+                Event::KeyUp{keycode: Some(key), ..} => {
+                    runner.input_mut().set_key(&key.name(), false);
+                },
                 _ => ()                
             }
         }
 
-        for _ in 0..TICKS_PER_FRAME{
-            chip8.tick();
+        match runner.frame() {
+            Err(err) => {
+                println!("Emulation stopped: {}", err);
+                break 'gameloop;
+            },
+            Ok(Some(stop)) => print_debug_state(runner.chip8(), &stop.to_string()),
+            Ok(None) => (),
         }
-        chip8.timers();
-        draw_screen(&chip8, &mut canvas);
     }
 }
 
-fn draw_screen(chip8: &Chip8, canvas: &mut Canvas<Window>){
-    // Clear canvas as black
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+// SDL calls this from its audio thread whenever it needs more samples
+struct Buzzer(Beeper);
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
 
-    let screen_buf = chip8.get_display();
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.fill(out);
+    }
+}
 
-    // Set draw color to white and check each pixel if it should be drawn
-    canvas.set_draw_color((255, 255, 255));
-    for (i, pixel) in screen_buf.iter().enumerate(){
-        if *pixel == 1{
-            // Convert our 1D array's index into a 2D (x,y) position
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
+// The Beeper behind SDL's audio device
+struct Speaker(AudioDevice<Buzzer>);
 
-            // Draw a rectangle at (x,y), scaled up by our SCALE value
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
-        }
+impl Audio for Speaker {
+    fn set_playing(&mut self, playing: bool) {
+        self.0.lock().0.set_active(playing);
     }
 
-    canvas.present();
+    fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        self.0.lock().0.set_pattern(pattern, pitch);
+    }
 }
 
-fn key2btn(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 =>    Some(0x1),
-        Keycode::Num2 =>    Some(0x2),
-        Keycode::Num3 =>    Some(0x3),
-        Keycode::Num4 =>    Some(0xC),
-        Keycode::Q =>       Some(0x4),
-        Keycode::W =>       Some(0x5),
-        Keycode::E =>       Some(0x6),
-        Keycode::R =>       Some(0xD),
-        Keycode::A =>       Some(0x7),
-        Keycode::S =>       Some(0x8),
-        Keycode::D =>       Some(0x9),
-        Keycode::F =>       Some(0xE),
-        Keycode::Z =>       Some(0xA),
-        Keycode::X =>       Some(0x0),
-        Keycode::C =>       Some(0xB),
-        Keycode::V =>       Some(0xF),
-        _ =>                None,
+fn open_audio(sdl_context: &sdl2::Sdl) -> Result<AudioDevice<Buzzer>, String> {
+    let audio_subsystem = sdl_context.audio()?;
+    let spec = AudioSpecDesired {
+        freq: Some(AUDIO_SAMPLE_RATE),
+        channels: Some(1),
+        samples: None,
+    };
+
+    let device = audio_subsystem.open_playback(None, &spec, |spec| Buzzer(Beeper::new(spec.freq as u32)))?;
+    device.resume();
+
+    Ok(device)
+}
+
+struct SdlDisplay<'a> {
+    canvas: Canvas<Window>,
+    texture: Texture<'a>,           // the display at its native resolution
+}
+
+impl Display for SdlDisplay<'_> {
+    fn draw(&mut self, chip8: &Chip8, dirty_rows: u64){
+        let (width, height) = chip8.resolution();
+
+        // Update the rows that changed, in the colour of their bitplanes
+        let mut row_buf: [u8; HIRES_SCREEN_WIDTH * 3] = [0; HIRES_SCREEN_WIDTH * 3];
+        for y in (0..height).filter(|y| dirty_rows & (1 << y) != 0){
+            for x in 0..width{
+                let (r, g, b) = PALETTE[chip8.pixel(x, y) as usize];
+                row_buf[x * 3..x * 3 + 3].copy_from_slice(&[r, g, b]);
+            }
+
+            let rect = Rect::new(0, y as i32, width as u32, 1);
+            self.texture.update(rect, &row_buf[..width * 3], width * 3).unwrap();
+        }
+
+        // The window is sized for lo-res, hi-res is simply scaled down to fit
+        let screen = Rect::new(0, 0, width as u32, height as u32);
+        self.canvas.copy(&self.texture, screen, None).unwrap();
+        self.canvas.present();
     }
-}
\ No newline at end of file
+}
+
+fn print_debug_state(chip8: &Chip8, reason: &str) {
+    let pc: u16 = chip8.pc();
+    let op: u16 = chip8.opcode_at(pc);
+
+    println!("{}: PC={:04X} {:04X} {}", reason, pc, op, Instruction::decode(op));
+
+    let registers: Vec<String> = chip8.registers().iter().enumerate().map(|(i, v)| format!("V{:X}={:02X}", i, v)).collect();
+    println!("  {} I={:04X}", registers.join(" "), chip8.index_register());
+    println!("  DT={:02X} ST={:02X} stack={:04X?}", chip8.delay_timer(), chip8.sound_timer(), chip8.stack());
+}
@@ -0,0 +1,210 @@
+// Writers for the final framebuffer. The format is picked from the file extension:
+//
+//   .png   indexed colour, one pixel per CHIP-8 pixel, the same palette as the desktop frontend
+//   .pbm   plain (P1) portable bitmap, any lit pixel is black whatever bitplane it is on. Lines are wrapped at
+//          PBM_PIXELS_PER_LINE pixels, the format allows no more than 70 characters
+//   other  text, one character per pixel, see TEXT_PIXELS
+//
+// The PNG is written by hand with uncompressed deflate blocks, a framebuffer is at most 8 kB anyway.
+
+use chip8_engine::*;
+use chip8_frontend::PALETTE;
+use std::path::Path;
+
+const TEXT_PIXELS: [char; 4] = ['.', '#', '+', '@'];
+const PBM_PIXELS_PER_LINE: usize = 32;     // 63 characters with the spaces
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const MAX_STORED_BLOCK: usize = 65535;
+
+pub fn encode(chip8: &Chip8, path: &Path) -> Vec<u8> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("png") => png(chip8),
+        Some("pbm") => pbm(chip8),
+        _ => text(chip8).into_bytes(),
+    }
+}
+
+pub fn text(chip8: &Chip8) -> String {
+    let (width, height) = chip8.resolution();
+    let mut out: String = String::with_capacity((width + 1) * height);
+
+    for y in 0..height {
+        out.extend((0..width).map(|x| TEXT_PIXELS[chip8.pixel(x, y) as usize]));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn pbm(chip8: &Chip8) -> Vec<u8> {
+    let (width, height) = chip8.resolution();
+    let mut out: String = format!("P1\n{} {}\n", width, height);
+
+    // A lo-res row fits on two lines, a hi-res one on four
+    for y in 0..height {
+        let row: Vec<&str> = (0..width).map(|x| if chip8.pixel(x, y) != 0 {"1"} else {"0"}).collect();
+        for line in row.chunks(PBM_PIXELS_PER_LINE) {
+            out.push_str(&line.join(" "));
+            out.push('\n');
+        }
+    }
+
+    out.into_bytes()
+}
+
+fn png(chip8: &Chip8) -> Vec<u8> {
+    let (width, height) = chip8.resolution();
+
+    // Every scanline starts with its filter type, 0 is none
+    let mut pixels: Vec<u8> = Vec::with_capacity((width + 1) * height);
+    for y in 0..height {
+        pixels.push(0);
+        pixels.extend((0..width).map(|x| chip8.pixel(x, y)));
+    }
+
+    let mut header: Vec<u8> = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);     // 8-bit palette indices, no compression method/filter/interlace options
+
+    let palette: Vec<u8> = PALETTE.iter().flat_map(|(r, g, b)| [*r, *g, *b]).collect();
+
+    let mut out: Vec<u8> = PNG_SIGNATURE.to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"PLTE", &palette);
+    chunk(&mut out, b"IDAT", &zlib_stored(&pixels));
+    chunk(&mut out, b"IEND", &[]);
+
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start: usize = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc: u32 = crc32(&out[start..]);
+
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// A zlib stream of stored (uncompressed) deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out: Vec<u8> = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if data.is_empty() {vec![data]} else {data.chunks(MAX_STORED_BLOCK).collect()};
+
+    for (idx, block) in blocks.iter().enumerate() {
+        let last: bool = idx == blocks.len() - 1;
+        let len: u16 = block.len() as u16;
+
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b): (u32, u32) = (1, 0);
+
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The font's 0 in the top left corner: F0 90 90 90 F0
+    fn machine() -> Chip8 {
+        let mut chip8: Chip8 = Chip8::new();
+        chip8.execute(0xA000).unwrap();
+        chip8.execute(0xD005).unwrap();
+
+        chip8
+    }
+
+    fn be_u32(bytes: &[u8]) -> u32 {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn png_decodes() {
+        let png: Vec<u8> = png(&machine());
+        assert_eq!(png[..8], PNG_SIGNATURE);
+
+        // Every chunk with its CRC over the type and data
+        let mut chunks: Vec<([u8; 4], &[u8])> = Vec::new();
+        let mut pos: usize = 8;
+        while pos < png.len() {
+            let len: usize = be_u32(&png[pos..]) as usize;
+            let body: &[u8] = &png[pos + 4..pos + 8 + len];
+            assert_eq!(be_u32(&png[pos + 8 + len..]), crc32(body), "chunk at {}", pos);
+
+            chunks.push((body[..4].try_into().unwrap(), &body[4..]));
+            pos += 12 + len;
+        }
+        assert_eq!(pos, png.len());
+
+        let kinds: Vec<&[u8; 4]> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+
+        let header: &[u8] = chunks[0].1;
+        assert_eq!((be_u32(header), be_u32(&header[4..])), (64, 32));
+        assert_eq!(header[8..], [8, 3, 0, 0, 0]);
+        assert_eq!(chunks[1].1.len(), 4 * 3);
+
+        // A single stored block: zlib header, final block flag, length and its complement, then the scanlines
+        let idat: &[u8] = chunks[2].1;
+        let len: usize = u16::from_le_bytes([idat[3], idat[4]]) as usize;
+        assert_eq!((idat[0], idat[1], idat[2]), (0x78, 0x01, 1));
+        assert_eq!(u16::from_le_bytes([idat[5], idat[6]]), !(len as u16));
+        assert_eq!(len, (64 + 1) * 32);
+
+        let pixels: &[u8] = &idat[7..7 + len];
+        assert_eq!(be_u32(&idat[7 + len..]), adler32(pixels));
+        assert_eq!(pixels[..6], [0, 1, 1, 1, 1, 0]);            // filter byte, then F0
+        assert_eq!(pixels[65..71], [0, 1, 0, 0, 1, 0]);         // the next row, 90
+    }
+
+    #[test]
+    fn pbm() {
+        let pbm: String = String::from_utf8(super::pbm(&machine())).unwrap();
+        let lines: Vec<&str> = pbm.lines().collect();
+
+        assert_eq!(lines[..2], ["P1", "64 32"]);
+        assert_eq!(lines.len(), 2 + 32 * 2);
+        assert!(lines[2].starts_with("1 1 1 1 0 "));
+        assert!(lines[4].starts_with("1 0 0 1 0 "));
+        assert_eq!(lines[2].split(' ').count(), 32);
+
+        // Hi-res rows take four lines, none of them over 70 characters
+        let mut chip8: Chip8 = machine();
+        chip8.execute(0x00FF).unwrap();
+        let pbm: String = String::from_utf8(super::pbm(&chip8)).unwrap();
+        assert_eq!(pbm.lines().count(), 2 + 64 * 4);
+        assert!(pbm.lines().all(|line| line.len() <= 70));
+    }
+
+    #[test]
+    fn text_dump() {
+        let text: String = text(&machine());
+
+        assert!(text.starts_with("####....."));
+        assert_eq!(text.lines().nth(1).map(|line| &line[..5]), Some("#..#."));
+    }
+}
@@ -0,0 +1,351 @@
+use chip8_engine::*;
+use chip8_frontend::{ManualClock, Runner};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+mod image;
+mod script;
+
+use script::ScriptedKeys;
+
+// No screen and no sound, the keys come from the script and the clock moves a frame at a time
+type HeadlessRunner = Runner<(), ScriptedKeys, (), ManualClock>;
+
+const DEFAULT_FRAMES: u64 = 600;        // 10 seconds of emulated time
+const DEFAULT_SEED: u64 = 0;            // fixed, so two runs of the same ROM give the same result
+
+const USAGE: &str = "Usage: cargo run path/to/game [options]
+  --frames N        frames to run at 60 per second, stops earlier on a halt (default 600)
+  --quirks PROFILE  vip, chip48, schip or xochip
+  --speed N         instructions per second (default 600)
+  --core CORE       interpreter, cached or recompiler (default interpreter)
+  --seed N          random number seed (default 0)
+  --key F:K[:H]     press key K (hex) on frame F and hold it for H frames, can be repeated
+  --keys FILE       key presses from a script, one 'frame key [hold]' per line
+  --screen FILE     write the final framebuffer as .png, .pbm or a text dump (any other extension)
+  --trace FILE      write every executed instruction to FILE, as JSON lines for .jsonl, text otherwise
+  --profile FILE    write a profile of the run to FILE: .json, collapsed stacks for .folded, text otherwise
+  --summary FILE    write the JSON summary to FILE instead of stdout";
+
+struct Options {
+    rom: String,
+    frames: u64,
+    quirks: (&'static str, Quirks),
+    speed: u32,
+    core: (&'static str, Core),
+    seed: u64,
+    keys: Vec<KeyPress>,
+    screen: Option<String>,
+    trace: Option<String>,
+    profile: Option<String>,
+    summary: Option<String>,
+}
+
+// Why the run ended
+enum Outcome {
+    Frames,                 // ran every frame it was asked to
+    Halted,                 // 00FD
+    Spinning,               // stuck on a jump to itself, the usual way for a CHIP-8 program to end
+    Error(Chip8Error),
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Frames => "frames",
+            Outcome::Halted => "halted",
+            Outcome::Spinning => "spinning",
+            Outcome::Error(_) => "error",
+        }
+    }
+}
+
+// Runs a ROM without a window, for CI and batch jobs. Prints (or writes) a JSON summary of the final state
+// and exits with 1 when the emulation stopped on an error. Stdout only ever carries the summary, errors go to
+// stderr with exit code 2
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options: Options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let rom: Vec<u8> = match fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", options.rom, err);
+            process::exit(2);
+        }
+    };
+
+    let (_, quirks) = options.quirks;
+    let mut chip8: Chip8 = Chip8::with_quirks(quirks);
+    chip8.set_xo_chip(quirks == Quirks::XOCHIP);
+    chip8.set_speed(options.speed);
+    let (_, core) = options.core;
+    chip8.set_core(core);
+    chip8.set_seed(options.seed);
+
+    if let Err(err) = chip8.load_rom(&rom) {
+        eprintln!("Unable to load ROM: {}", err);
+        process::exit(2);
+    }
+
+    let tracer: Option<Rc<RefCell<TraceWriter<BufWriter<File>>>>> = options.trace.as_ref().map(|path: &String| {
+        match File::create(path) {
+            Ok(file) => Rc::new(RefCell::new(TraceWriter::new(BufWriter::new(file), trace_format(Path::new(path))))),
+            Err(err) => {
+                eprintln!("Unable to create {}: {}", path, err);
+                process::exit(2);
+            }
+        }
+    });
+    if let Some(tracer) = &tracer {
+        chip8.set_tracer(tracer.clone());
+    }
+    if options.profile.is_some() {
+        chip8.set_profiler(Profiler::new());
+    }
+
+    // EXECUTE
+    let mut runner: HeadlessRunner = Runner::new(chip8, (), ScriptedKeys::new(options.keys.clone()), (), ManualClock::new());
+    let start: Instant = Instant::now();
+    let (frames, outcome) = run(&mut runner, options.frames);
+    let wall_time: f64 = start.elapsed().as_secs_f64();
+    let chip8: &Chip8 = runner.chip8();
+
+    // OUTPUT
+    if let (Some(path), Some(tracer)) = (&options.trace, &tracer)
+        && let Err(err) = tracer.borrow_mut().flush() {
+        eprintln!("Unable to write {}: {}", path, err);
+        process::exit(2);
+    }
+
+    if let (Some(path), Some(profiler)) = (&options.profile, chip8.profiler())
+        && let Err(err) = fs::write(path, profiler.report(profile_format(Path::new(path)))) {
+        eprintln!("Unable to write {}: {}", path, err);
+        process::exit(2);
+    }
+
+    if let Some(path) = &options.screen
+        && let Err(err) = fs::write(path, image::encode(chip8, Path::new(path))) {
+        eprintln!("Unable to write {}: {}", path, err);
+        process::exit(2);
+    }
+
+    let summary: String = serde_json::to_string_pretty(&summary(&options, chip8, frames, &outcome, wall_time)).unwrap();
+    match &options.summary {
+        Some(path) => {
+            if let Err(err) = fs::write(path, summary + "\n") {
+                eprintln!("Unable to write {}: {}", path, err);
+                process::exit(2);
+            }
+        }
+        None => println!("{}", summary),
+    }
+
+    if let Outcome::Error(_) = outcome {
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options: Options = Options {
+        rom: String::new(),
+        frames: DEFAULT_FRAMES,
+        quirks: ("default", Quirks::default()),
+        speed: DEFAULT_SPEED,
+        core: ("interpreter", Core::Interpreter),
+        seed: DEFAULT_SEED,
+        keys: Vec::new(),
+        screen: None,
+        trace: None,
+        profile: None,
+        summary: None,
+    };
+
+    let mut rom: Option<String> = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if rom.replace(arg.clone()).is_some() {
+                return Err(format!("Unexpected argument: {}", arg));
+            }
+            continue;
+        }
+
+        let value: &String = args.next().ok_or(format!("Missing value for {}", arg))?;
+        let number = || value.parse::<u64>().map_err(|_| format!("Invalid number for {}: {}", arg, value));
+
+        match arg.as_str() {
+            "--frames" => options.frames = number()?,
+            "--speed" => options.speed = number()?.min(u32::MAX as u64) as u32,
+            "--seed" => options.seed = number()?,
+            "--quirks" => options.quirks = match value.as_str() {
+                "vip" =>        ("vip", Quirks::VIP),
+                "chip48" =>     ("chip48", Quirks::CHIP48),
+                "schip" =>      ("schip", Quirks::SCHIP),
+                "xochip" =>     ("xochip", Quirks::XOCHIP),
+                other => return Err(format!("Unknown quirk profile: {}", other)),
+            },
+            "--core" => options.core = match value.as_str() {
+                "interpreter" =>    ("interpreter", Core::Interpreter),
+                "cached" =>         ("cached", Core::Cached),
+                "recompiler" =>     ("recompiler", Core::Recompiler),
+                other => return Err(format!("Unknown core: {}", other)),
+            },
+            "--key" => options.keys.push(script::parse_entry(value)?),
+            "--keys" => {
+                let text: String = fs::read_to_string(value).map_err(|err| format!("Unable to read {}: {}", value, err))?;
+                options.keys.extend(script::parse(&text).map_err(|err| format!("{}:{}", value, err))?);
+            }
+            "--screen" => options.screen = Some(value.clone()),
+            "--trace" => options.trace = Some(value.clone()),
+            "--profile" => options.profile = Some(value.clone()),
+            "--summary" => options.summary = Some(value.clone()),
+            other => return Err(format!("Unknown option: {}", other)),
+        }
+    }
+
+    options.rom = rom.ok_or("No ROM given".to_string())?;
+    Ok(options)
+}
+
+fn trace_format(path: &Path) -> TraceFormat {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jsonl") => TraceFormat::JsonLines,
+        _ => TraceFormat::Text,
+    }
+}
+
+fn profile_format(path: &Path) -> ProfileFormat {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => ProfileFormat::Json,
+        Some("folded") => ProfileFormat::CollapsedStacks,
+        _ => ProfileFormat::Text,
+    }
+}
+
+// Run up to `frames` frames, one Runner pass each. Returns the frames that ran and why it stopped
+fn run(runner: &mut HeadlessRunner, frames: u64) -> (u64, Outcome) {
+    for frame in 0..frames {
+        runner.input_mut().set_frame(frame);
+        runner.clock_mut().advance(frame_start(frame + 1) - frame_start(frame));
+
+        if let Err(err) = runner.run() {
+            return (frame, Outcome::Error(err));
+        }
+
+        if runner.chip8().is_halted() {
+            return (frame + 1, Outcome::Halted);
+        }
+        if runner.chip8().is_spinning() {
+            return (frame + 1, Outcome::Spinning);
+        }
+    }
+
+    (frames, Outcome::Frames)
+}
+
+// Emulated time at the start of `frame`, rounded up to whole nanoseconds so every pass of run() is due exactly one frame
+fn frame_start(frame: u64) -> Duration {
+    let hz: u64 = TIMER_HZ as u64;
+
+    Duration::new(frame / hz, ((frame % hz) * 1_000_000_000).div_ceil(hz) as u32)
+}
+
+fn summary(options: &Options, chip8: &Chip8, frames: u64, outcome: &Outcome, wall_time: f64) -> Value {
+    let (width, height) = chip8.resolution();
+    let (quirks, _) = options.quirks;
+    let (core, _) = options.core;
+    let error: Option<String> = match outcome {
+        Outcome::Error(err) => Some(err.to_string()),
+        _ => None,
+    };
+
+    json!({
+        "rom": options.rom,
+        "quirks": quirks,
+        "core": core,
+        "seed": options.seed,
+        "outcome": outcome.name(),
+        "error": error,
+        "timing": {
+            "frames": frames,
+            "cycles": chip8.cycles(),
+            "speed": chip8.speed(),
+            "emulated_seconds": frames as f64 / TIMER_HZ as f64,
+            "wall_seconds": wall_time,
+        },
+        "registers": {
+            "pc": chip8.pc(),
+            "i": chip8.index_register(),
+            "v": chip8.registers().to_vec(),
+            "stack": chip8.stack().to_vec(),
+            "delay_timer": chip8.delay_timer(),
+            "sound_timer": chip8.sound_timer(),
+        },
+        "display": {
+            "width": width,
+            "height": height,
+            "crc32": format!("{:08x}", crc32(&chip8.get_color_display())),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use script::parse;
+
+    fn runner(rom: &[u8], keys: &str) -> HeadlessRunner {
+        let mut chip8: Chip8 = Chip8::with_seed(DEFAULT_SEED);
+        chip8.load_rom(rom).unwrap();
+
+        Runner::new(chip8, (), ScriptedKeys::new(parse(keys).unwrap()), (), ManualClock::new())
+    }
+
+    #[test]
+    fn every_pass_runs_one_frame() {
+        let mut chip8: Chip8 = Chip8::new();
+
+        for frame in 0..3 * TIMER_HZ as u64 {
+            assert_eq!(chip8.frames_due(frame_start(frame + 1) - frame_start(frame)), 1, "frame {}", frame);
+        }
+        assert_eq!(frame_start(TIMER_HZ as u64), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn outcomes() {
+        // V0 counts, forever
+        let mut looping: HeadlessRunner = runner(&[0x70, 0x01, 0x12, 0x00], "");
+        assert!(matches!(run(&mut looping, 5), (5, Outcome::Frames)));
+        assert_eq!(looping.chip8().cycles(), 5 * (DEFAULT_SPEED / TIMER_HZ) as u64);
+
+        let mut spinning: HeadlessRunner = runner(&[0x60, 0x01, 0x12, 0x02], "");
+        assert!(matches!(run(&mut spinning, 5), (1, Outcome::Spinning)));
+
+        let mut error: HeadlessRunner = runner(&[0xE0, 0xFF], "");
+        assert!(matches!(run(&mut error, 5), (0, Outcome::Error(_))));
+    }
+
+    // Fx0A waits until the script presses B on frame 20
+    #[test]
+    fn scripted_keys() {
+        let mut runner: HeadlessRunner = runner(&[0xF3, 0x0A, 0x64, 0x01, 0x12, 0x04], "20 b");
+
+        assert!(matches!(run(&mut runner, 600), (21, Outcome::Spinning)));
+        assert_eq!(&runner.chip8().registers()[3..5], &[0xB, 1]);
+    }
+}
@@ -0,0 +1,121 @@
+// Scripted key presses. A script has one press per line: the frame it starts on, the key (hex 0 - F) and
+// optionally how many frames it is held for. # starts a comment
+//
+//   120 5          press 5 on frame 120, held for DEFAULT_HOLD_FRAMES
+//   300 a 30       hold A for half a second
+//
+// On the command line the same entry is written with colons, --key 300:a:30
+
+use chip8_engine::*;
+use chip8_frontend::{Input, KEYS};
+
+const DEFAULT_HOLD_FRAMES: u64 = 3;     // long enough for games that poll the keypad instead of waiting with Fx0A
+
+// The script as the Runner's keyboard, pressing the keys of the frame set with set_frame()
+pub struct ScriptedKeys {
+    presses: Vec<KeyPress>,
+    frame: u64,
+}
+
+impl ScriptedKeys {
+    pub fn new(presses: Vec<KeyPress>) -> Self {
+        Self { presses, frame: 0 }
+    }
+
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+}
+
+impl Input for ScriptedKeys {
+    fn keypad(&mut self) -> [bool; KEYS] {
+        scripted_keypad(&self.presses, self.frame)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Vec<KeyPress>, String> {
+    let mut presses: Vec<KeyPress> = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line: &str = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        presses.push(parse_fields(&fields).map_err(|err| format!("line {}: {}", idx + 1, err))?);
+    }
+
+    Ok(presses)
+}
+
+// A single press written as frame:key[:hold]
+pub fn parse_entry(entry: &str) -> Result<KeyPress, String> {
+    let fields: Vec<&str> = entry.split(':').collect();
+
+    parse_fields(&fields).map_err(|err| format!("{}: {}", entry, err))
+}
+
+fn parse_fields(fields: &[&str]) -> Result<KeyPress, String> {
+    if fields.len() != 2 && fields.len() != 3 {
+        return Err("expected a frame, a key and optionally a hold time".to_string());
+    }
+
+    let frame: u64 = fields[0].parse().map_err(|_| format!("invalid frame '{}'", fields[0]))?;
+    let key: usize = match usize::from_str_radix(fields[1], 16) {
+        Ok(key) if key < KEYS => key,
+        _ => return Err(format!("invalid key '{}', keys are 0 - F", fields[1])),
+    };
+    let hold: u64 = match fields.get(2) {
+        Some(hold) => hold.parse().map_err(|_| format!("invalid hold time '{}'", hold))?,
+        None => DEFAULT_HOLD_FRAMES,
+    };
+
+    Ok(KeyPress { frame, key, hold })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script() {
+        let text: &str = "# title screen\n\n120 5\n300 a 30   # jump\n  0 F 1\n";
+
+        assert_eq!(parse(text), Ok(vec![
+            KeyPress { frame: 120, key: 0x5, hold: DEFAULT_HOLD_FRAMES },
+            KeyPress { frame: 300, key: 0xA, hold: 30 },
+            KeyPress { frame: 0, key: 0xF, hold: 1 },
+        ]));
+        assert_eq!(parse(""), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn script_errors_name_the_line() {
+        assert_eq!(parse("1 2\n3 10\n"), Err("line 2: invalid key '10', keys are 0 - F".to_string()));
+        assert_eq!(parse("x 2"), Err("line 1: invalid frame 'x'".to_string()));
+        assert_eq!(parse("1 2 3 4"), Err("line 1: expected a frame, a key and optionally a hold time".to_string()));
+    }
+
+    #[test]
+    fn entry() {
+        assert_eq!(parse_entry("300:a:30"), Ok(KeyPress { frame: 300, key: 0xA, hold: 30 }));
+        assert_eq!(parse_entry("5:0"), Ok(KeyPress { frame: 5, key: 0x0, hold: DEFAULT_HOLD_FRAMES }));
+        assert_eq!(parse_entry("5:0:x"), Err("5:0:x: invalid hold time 'x'".to_string()));
+        assert!(parse_entry("5").is_err());
+    }
+
+    #[test]
+    fn presses_are_held() {
+        let mut keys: ScriptedKeys = ScriptedKeys::new(vec![KeyPress { frame: 2, key: 0x7, hold: 2 }]);
+
+        // Down on frames 2 and 3 only
+        let down: Vec<bool> = (0..5)
+            .map(|frame: u64| {
+                keys.set_frame(frame);
+                keys.keypad()[0x7]
+            })
+            .collect();
+        assert_eq!(down, [false, false, true, true, false]);
+    }
+}
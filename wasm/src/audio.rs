@@ -0,0 +1,103 @@
+// Buzzer for the browser: a square wave oscillator behind a gain node, faded in and out with the sound timer.
+//
+// Browsers only let a page start audio from a user gesture, so the AudioContext is created (or resumed) by
+// unlock() which index.js calls on the first key press or click. Until then the game runs silently.
+
+use chip8_frontend as frontend;
+use wasm_bindgen::prelude::*;
+use web_sys::{AudioContext, AudioContextState, GainNode, OscillatorNode, OscillatorType};
+
+const FREQUENCY: f32 = 440.0;
+const DEFAULT_VOLUME: f32 = 0.25;
+const FADE_SECONDS: f64 = 0.005;        // time constant of the fade, avoids clicks at start and stop
+
+struct Nodes {
+    ctx: AudioContext,
+    gain: GainNode,
+    _oscillator: OscillatorNode,        // runs for the life of the page, the gain node switches it on and off
+}
+
+pub struct Audio {
+    nodes: Option<Nodes>,               // None until the first user gesture
+    volume: f32,
+    muted: bool,
+    playing: bool,
+}
+
+impl Audio {
+    pub fn new() -> Self {
+        Self { nodes: None, volume: DEFAULT_VOLUME, muted: false, playing: false }
+    }
+
+    // Must be called from a user gesture handler
+    pub fn unlock(&mut self) -> Result<(), JsValue> {
+        if let Some(nodes) = &self.nodes {
+            if nodes.ctx.state() == AudioContextState::Suspended {
+                let _ = nodes.ctx.resume()?;
+            }
+            return Ok(());
+        }
+
+        let ctx: AudioContext = AudioContext::new()?;
+
+        let gain: GainNode = ctx.create_gain()?;
+        gain.gain().set_value(0.0);
+        gain.connect_with_audio_node(&ctx.destination())?;
+
+        let oscillator: OscillatorNode = ctx.create_oscillator()?;
+        oscillator.set_type(OscillatorType::Square);
+        oscillator.frequency().set_value(FREQUENCY);
+        oscillator.connect_with_audio_node(&gain)?;
+        oscillator.start()?;
+
+        self.nodes = Some(Nodes { ctx, gain, _oscillator: oscillator });
+        self.playing = false;
+
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.nodes.as_ref().is_some_and(|nodes| nodes.ctx.state() == AudioContextState::Running)
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.apply();
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.apply();
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    fn apply(&self) {
+        let Some(nodes) = &self.nodes else {
+            return;
+        };
+
+        let target: f32 = match self.playing && !self.muted {
+            true => self.volume,
+            false => 0.0,
+        };
+        let _ = nodes.gain.gain().set_target_at_time(target, nodes.ctx.current_time(), FADE_SECONDS);
+    }
+}
+
+// The Runner calls this every frame with whether the sound timer is running. XO-CHIP patterns play as the
+// plain square wave
+impl frontend::Audio for Audio {
+    fn set_playing(&mut self, playing: bool) {
+        if playing != self.playing {
+            self.playing = playing;
+            self.apply();
+        }
+    }
+}
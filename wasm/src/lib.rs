@@ -1,22 +1,27 @@
 use chip8_engine::*;
+use chip8_frontend::{Display, Keypad, ManualClock, Runner, PALETTE};
 use wasm_bindgen::prelude::*;
 
+mod audio;
+use audio::Audio;
+
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent};
 use wasm_bindgen::JsCast;
 use js_sys::Uint8Array;
+use std::time::Duration;
+
+// The clock is told the time by run_for(), std::time::Instant does not work in the browser
+type WebRunner = Runner<CanvasDisplay, Keypad, Audio, ManualClock>;
 
 #[wasm_bindgen]
 pub struct Chip8EngineWasm {
-    chip8: Chip8,
-    ctx: CanvasRenderingContext2d,  // For JS Canvas object
+    runner: WebRunner,              // the WebAudio buzzer in it is silent until unlock_audio()
 }
 
 #[wasm_bindgen]
 impl Chip8EngineWasm {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Result<Chip8EngineWasm, JsValue> {
-        let chip8 = Chip8::new();
-
         let doc: web_sys::Document = web_sys::window().unwrap().document().unwrap();
         let canvas: web_sys::Element = doc.get_element_by_id("canvas").unwrap();
         let canvas: HtmlCanvasElement = canvas.dyn_into()
@@ -29,75 +34,250 @@ impl Chip8EngineWasm {
                         .dyn_into::<CanvasRenderingContext2d>()
                         .unwrap();
 
-        Ok (Chip8EngineWasm { chip8, ctx })
+        let display = CanvasDisplay { ctx, scale: 1, palette: PALETTE.map(|(r, g, b)| format!("#{:02x}{:02x}{:02x}", r, g, b)) };
+
+        Ok (Chip8EngineWasm { runner: Runner::new(Chip8::new(), display, Keypad::new(), Audio::new(), ManualClock::new()) })
+    }
+
+    #[wasm_bindgen]
+    pub fn tick(&mut self) -> Result<(), JsValue>{
+        self.runner.chip8_mut().tick().map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    // Emulates `elapsed_ms` of real time under the debugger, returns why it stopped early (undefined if it did not).
+    // screen_changed() and sound_active() tell what happened, draw_screen() shows it
+    #[wasm_bindgen]
+    pub fn run_for(&mut self, elapsed_ms: f64) -> Result<Option<String>, JsValue>{
+        let elapsed: Duration = Duration::from_secs_f64(elapsed_ms.max(0.0) / 1000.0);
+        self.runner.clock_mut().advance(elapsed);
+
+        match self.runner.run() {
+            Ok(stop) => Ok(stop.map(|stop| stop.to_string())),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
+
+    // Browsers only allow audio to start from a user gesture, call this from a key or click handler
+    #[wasm_bindgen]
+    pub fn unlock_audio(&mut self) -> Result<(), JsValue>{
+        self.runner.audio_mut().unlock()
+    }
+
+    #[wasm_bindgen]
+    pub fn audio_unlocked(&self) -> bool{
+        self.runner.audio().is_unlocked()
+    }
+
+    // 0.0 - 1.0
+    #[wasm_bindgen]
+    pub fn set_volume(&mut self, volume: f32){
+        self.runner.audio_mut().set_volume(volume);
+    }
+
+    #[wasm_bindgen]
+    pub fn volume(&self) -> f32{
+        self.runner.audio().volume()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_muted(&mut self, muted: bool){
+        self.runner.audio_mut().set_muted(muted);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_muted(&self) -> bool{
+        self.runner.audio().is_muted()
+    }
+
+    #[wasm_bindgen]
+    pub fn screen_changed(&self) -> bool{
+        self.runner.screen_changed()
+    }
+
+    #[wasm_bindgen]
+    pub fn sound_active(&self) -> bool{
+        self.runner.chip8().sound_active()
+    }
+
+    // Instructions per second
+    #[wasm_bindgen]
+    pub fn set_speed(&mut self, instructions_per_second: u32){
+        self.runner.chip8_mut().set_speed(instructions_per_second);
+    }
+
+    #[wasm_bindgen]
+    pub fn speed(&self) -> u32{
+        self.runner.chip8().speed()
+    }
+
+    #[wasm_bindgen]
+    pub fn is_paused(&self) -> bool{
+        self.runner.debugger().is_paused()
+    }
+
+    #[wasm_bindgen]
+    pub fn pause(&mut self){
+        self.runner.debugger_mut().pause();
     }
 
     #[wasm_bindgen]
-    pub fn tick(&mut self){
-        self.chip8.tick();
+    pub fn resume(&mut self){
+        self.runner.debugger_mut().resume();
+    }
+
+    #[wasm_bindgen]
+    pub fn step(&mut self){
+        self.runner.debugger_mut().step();
+    }
+
+    #[wasm_bindgen]
+    pub fn step_over(&mut self){
+        self.runner.step_over();
+    }
+
+    #[wasm_bindgen]
+    pub fn step_out(&mut self){
+        self.runner.step_out();
+    }
+
+    #[wasm_bindgen]
+    pub fn add_breakpoint(&mut self, addr: u16){
+        self.runner.debugger_mut().add_breakpoint(addr);
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_breakpoint(&mut self, addr: u16){
+        self.runner.debugger_mut().remove_breakpoint(addr);
+    }
+
+    // access is "r", "w" or "rw"
+    #[wasm_bindgen]
+    pub fn watch_memory(&mut self, addr: u16, access: &str) -> Result<(), JsValue>{
+        let access = match access {
+            "r" => Access::Read,
+            "w" => Access::Write,
+            "rw" => Access::ReadWrite,
+            _ => return Err(JsValue::from_str("access must be r, w or rw")),
+        };
+        self.runner.debugger_mut().watch_memory(addr, access);
+
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn unwatch_memory(&mut self, addr: u16){
+        self.runner.debugger_mut().unwatch_memory(addr);
+    }
+
+    #[wasm_bindgen]
+    pub fn watch_register(&mut self, reg: u8){
+        self.runner.debugger_mut().watch_register(reg);
+    }
+
+    #[wasm_bindgen]
+    pub fn unwatch_register(&mut self, reg: u8){
+        self.runner.debugger_mut().unwatch_register(reg);
+    }
+
+    #[wasm_bindgen]
+    pub fn pc(&self) -> u16{
+        self.runner.chip8().pc()
+    }
+
+    #[wasm_bindgen]
+    pub fn registers(&self) -> Vec<u8>{
+        self.runner.chip8().registers().to_vec()
+    }
+
+    #[wasm_bindgen]
+    pub fn index_register(&self) -> u16{
+        self.runner.chip8().index_register()
+    }
+
+    #[wasm_bindgen]
+    pub fn stack(&self) -> Vec<u16>{
+        self.runner.chip8().stack().to_vec()
     }
 
     #[wasm_bindgen]
     pub fn timers(&mut self){
-        self.chip8.timers();
+        self.runner.chip8_mut().timers();
     }
 
     #[wasm_bindgen]
     pub fn reset(&mut self){
-        self.chip8.reset();
+        self.runner.chip8_mut().reset();
     }
 
     #[wasm_bindgen]
-    pub fn keypress(&mut self, evt: KeyboardEvent, pressed: bool) {
-        let key = evt.key();
-        if let Some(k) = key2btn(&key){
-            self.chip8.set_keypad(k, pressed);
-        }
+    pub fn save_state(&self) -> Vec<u8>{
+        self.runner.chip8().save_state()
     }
 
     #[wasm_bindgen]
-    pub fn load_rom(&mut self, rom: Uint8Array){
-        self.chip8.load_rom(&rom.to_vec());
+    pub fn load_state(&mut self, state: Uint8Array) -> Result<(), JsValue>{
+        self.runner.chip8_mut().load_state(&state.to_vec()).map_err(|err| JsValue::from_str(&err.to_string()))
     }
 
     #[wasm_bindgen]
-    pub fn draw_screen(&mut self, scale: usize){
-        let display = self.chip8.get_display();
+    pub fn keypress(&mut self, evt: KeyboardEvent, pressed: bool) {
+        self.runner.input_mut().set_key(&evt.key(), pressed);
+    }
 
-        for i in 0..(SCREEN_WIDTH * SCREEN_HEIGHT){
-            if 1 == display[i]{
-                let x = i % SCREEN_WIDTH;
-                let y = i / SCREEN_WIDTH;
+    #[wasm_bindgen]
+    pub fn load_rom(&mut self, rom: Uint8Array) -> Result<(), JsValue>{
+        self.runner.chip8_mut().load_rom(&rom.to_vec()).map_err(|err| JsValue::from_str(&err.to_string()))
+    }
 
-                self.ctx.fill_rect( (x * scale) as f64, 
-                                    (y * scale) as f64, 
-                                    scale as f64, 
-                                    scale as f64 );
-            }
-        }
+    #[wasm_bindgen]
+    pub fn set_xo_chip(&mut self, enabled: bool){
+        self.runner.chip8_mut().set_quirks(if enabled {Quirks::XOCHIP} else {Quirks::default()});
+        self.runner.chip8_mut().set_xo_chip(enabled);
     }
-}
 
-fn key2btn(key: &str) -> Option<usize> {
-    match key {
-        "1" => Some(0x1),
-        "2" => Some(0x2),
-        "3" => Some(0x3),
-        "4" => Some(0xC),
-        "q" => Some(0x4),
-        "w" => Some(0x5),
-        "e" => Some(0x6),
-        "r" => Some(0xD),
-        "a" => Some(0x7),
-        "s" => Some(0x8),
-        "d" => Some(0x9),
-        "f" => Some(0xE),
-        "z" => Some(0xA),
-        "x" => Some(0x0),
-        "c" => Some(0xB),
-        "v" => Some(0xF),
-        _ =>   None,
+    // Redraws the rows that changed since the last draw
+    #[wasm_bindgen]
+    pub fn draw_screen(&mut self, scale: usize){
+        self.runner.display_mut().scale = scale;
+        self.runner.present();
     }
 }
 
+struct CanvasDisplay {
+    ctx: CanvasRenderingContext2d,  // For JS Canvas object
+    scale: usize,                   // canvas pixels per lo-res pixel
+    palette: [String; 4],           // PALETTE as CSS colours
+}
+
+impl Display for CanvasDisplay {
+    fn draw(&mut self, chip8: &Chip8, dirty_rows: u64){
+        let (width, height) = chip8.resolution();
 
+        // The canvas is sized for lo-res. Pixel edges are rounded to whole canvas pixels, so a hi-res pixel is
+        // 7 or 8 canvas pixels wide at scale 15 and the rows still cover the whole canvas
+        let (canvas_width, canvas_height) = (self.scale * SCREEN_WIDTH, self.scale * SCREEN_HEIGHT);
+        let left = |x: usize| (x * canvas_width / width) as f64;
+        let top = |y: usize| (y * canvas_height / height) as f64;
+
+        for y in (0..height).filter(|y| dirty_rows & (1 << y) != 0){
+            self.ctx.set_fill_style_str(&self.palette[0]);
+            self.ctx.fill_rect(0.0, top(y), canvas_width as f64, top(y + 1) - top(y));
+
+            // The fill style only changes when the colour does
+            let mut style: usize = 0;
+            for x in 0..width{
+                let color: usize = chip8.pixel(x, y) as usize;
+                if color != 0{
+                    if color != style{
+                        self.ctx.set_fill_style_str(&self.palette[color]);
+                        style = color;
+                    }
+                    self.ctx.fill_rect( left(x), 
+                                        top(y), 
+                                        left(x + 1) - left(x), 
+                                        top(y + 1) - top(y) );
+                }
+            }
+        }
+    }
+}
@@ -3,8 +3,8 @@ import init, * as wasm from "./wasm.js"
 const WIDTH = 64
 const HEIGHT = 32
 const SCALE = 15
-const TICKS_PER_FRAME = 10
 let anim_frame = 0
+let last_time = null     // requestAnimationFrame timestamp of the previous frame
 
 const canvas = document.getElementById("canvas")
 canvas.width = WIDTH * SCALE
@@ -15,12 +15,99 @@ ctx.fillStyle = "black"
 ctx.fillRect(0, 0, WIDTH * SCALE, HEIGHT * SCALE)
 
 const input = document.getElementById("fileinput")
+const mute = document.getElementById("mute")
+const volume = document.getElementById("volume")
+
+// Quick-save slot in localStorage, the state bytes are stored as base64
+const STATE_KEY = "chip8_state"
+
+function saveState(chip8){
+    const state = chip8.save_state()
+    let binary = ""
+    for (let i = 0; i < state.length; i++){
+        binary += String.fromCharCode(state[i])
+    }
+    localStorage.setItem(STATE_KEY, btoa(binary))
+}
+
+function loadState(chip8){
+    const saved = localStorage.getItem(STATE_KEY)
+    if (saved === null){
+        return
+    }
+
+    const state = Uint8Array.from(atob(saved), c => c.charCodeAt(0))
+    try {
+        chip8.load_state(state)
+    } catch (err) {
+        alert("Failed to load state: " + err)
+    }
+}
+function logDebugState(chip8, reason){
+    const hex = (val, digits) => val.toString(16).toUpperCase().padStart(digits, "0")
+    const registers = Array.from(chip8.registers(), (val, i) => "V" + hex(i, 1) + "=" + hex(val, 2))
+
+    console.log(reason + ": PC=" + hex(chip8.pc(), 4) + " I=" + hex(chip8.index_register(), 4))
+    console.log("  " + registers.join(" "))
+    console.log("  stack=" + Array.from(chip8.stack(), (addr) => hex(addr, 4)).join(" "))
+}
+
 async function run() {
     await init()
 
     let chip8 = new wasm.Chip8EngineWasm()
 
+    // Browsers keep audio locked until the user interacts with the page, so unlock it on the first gesture
+    const unlockAudio = function(){
+        if (!chip8.audio_unlocked()){
+            try {
+                chip8.unlock_audio()
+            } catch (err) {
+                console.log("Sound disabled: " + err)
+            }
+        }
+    }
+    document.addEventListener("click", unlockAudio)
+
+    chip8.set_muted(mute.checked)
+    chip8.set_volume(volume.value / 100)
+    mute.addEventListener("change", () => chip8.set_muted(mute.checked))
+    volume.addEventListener("input", () => chip8.set_volume(volume.value / 100))
+
     document.addEventListener("keydown", function(evt){
+        unlockAudio()
+
+        // F5 quick-saves and F9 quick-loads, same as the desktop frontend
+        if (evt.key === "F5" || evt.key === "F9"){
+            evt.preventDefault()
+            if (evt.key === "F5"){
+                saveState(chip8)
+            } else {
+                loadState(chip8)
+            }
+            return
+        }
+
+        // F6 pauses and continues, F7 steps, F8 steps over a call and F10 steps out of the current one
+        if (evt.key === "F6" || evt.key === "F7" || evt.key === "F8" || evt.key === "F10"){
+            evt.preventDefault()
+            if (evt.key === "F6"){
+                if (chip8.is_paused()){
+                    chip8.resume()
+                } else {
+                    chip8.pause()
+                    logDebugState(chip8, "paused")
+                }
+            } else if (evt.key === "F7"){
+                chip8.step()
+            } else if (evt.key === "F8"){
+                chip8.step_over()
+            } else {
+                chip8.step_out()
+            }
+            return
+        }
+
         chip8.keypress(evt, true)
     })
 
@@ -48,27 +135,43 @@ async function run() {
             let buffer = rom_file.result
             const rom = new Uint8Array(buffer)
             chip8.reset()
-            chip8.load_rom(rom)
-            gameloop(chip8)
+            try {
+                chip8.load_rom(rom)
+            } catch (err) {
+                alert("Failed to load ROM: " + err)
+                return
+            }
+            last_time = null
+            gameloop(chip8, performance.now())
         }
         rom_file.readAsArrayBuffer(file)
     }, false)
 }
 
-function gameloop(chip8){
-    for (let i = 0; i < TICKS_PER_FRAME; i++){
-        chip8.tick()
-    }
-    chip8.timers()
+function gameloop(chip8, time){
+    // The engine decides how much to run from the time since the last frame, whatever the refresh rate
+    const elapsed = last_time === null ? 0 : time - last_time
+    last_time = time
 
-    ctx.fillStyle = "black"
-    ctx.fillRect(0, 0, WIDTH * SCALE, HEIGHT * SCALE)
+    // Stop the game on the first emulation error instead of spinning on it every frame
+    try {
+        const stop = chip8.run_for(elapsed)
+        if (stop !== undefined){
+            logDebugState(chip8, stop)
+        }
+    } catch (err) {
+        anim_frame = 0
+        alert("Emulation stopped: " + err)
+        return
+    }
 
-    ctx.fillStyle = "white"
-    chip8.draw_screen(SCALE)
+    // Only the rows that changed are redrawn
+    if (chip8.screen_changed()){
+        chip8.draw_screen(SCALE)
+    }
 
-    anim_frame = window.requestAnimationFrame(() => {
-        gameloop(chip8)
+    anim_frame = window.requestAnimationFrame((time) => {
+        gameloop(chip8, time)
     })
 }
 